@@ -163,3 +163,11 @@ pub static LIBLO_GAME_OPENMW: c_uint = 11;
 /// Game code for The Elder Scrolls IV: Oblivion Remastered.
 #[no_mangle]
 pub static LIBLO_GAME_OBLIVION_REMASTERED: c_uint = 12;
+
+/// Game code for Enderal: Forgotten Stories.
+#[no_mangle]
+pub static LIBLO_GAME_ENDERAL: c_uint = 13;
+
+/// Game code for Enderal: Forgotten Stories (Special Edition).
+#[no_mangle]
+pub static LIBLO_GAME_ENDERAL_SE: c_uint = 14;
@@ -62,7 +62,8 @@ fn map_error(err: &Error) -> c_uint {
         Error::NoFilename(_)
         | Error::PluginParsingError(_, _)
         | Error::IniParsingError { .. }
-        | Error::VdfParsingError(_, _) => LIBLO_ERROR_FILE_PARSE_FAIL,
+        | Error::VdfParsingError(_, _)
+        | Error::JsonParsingError(_, _) => LIBLO_ERROR_FILE_PARSE_FAIL,
         Error::DecodeError(_) => LIBLO_ERROR_TEXT_DECODE_FAIL,
         Error::EncodeError(_) => LIBLO_ERROR_TEXT_ENCODE_FAIL,
         Error::PluginNotFound(_)
@@ -24,6 +24,8 @@ void test_game_id_values() {
   assert(LIBLO_GAME_STARFIELD == 10);
   assert(LIBLO_GAME_OPENMW == 11);
   assert(LIBLO_GAME_OBLIVION_REMASTERED == 12);
+  assert(LIBLO_GAME_ENDERAL == 13);
+  assert(LIBLO_GAME_ENDERAL_SE == 14);
 }
 
 void test_lo_get_version() {
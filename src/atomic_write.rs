@@ -0,0 +1,342 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::ffi::OsString;
+use std::fs::{create_dir_all, read_to_string, remove_file, rename, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use crate::enums::Error;
+
+const STAGED_FILE_EXTENSION: &str = ".tmp";
+const COMMIT_MANIFEST_EXTENSION: &str = ".commit";
+
+/// Write the given contents to the given path so that the file at that path
+/// is always either left as it was or completely replaced, even if the process
+/// is interrupted while writing.
+pub(crate) fn write_file_atomically(path: &Path, contents: &[u8]) -> Result<(), Error> {
+    write_files_atomically(&[(path, contents)])
+}
+
+/// Write all the given files atomically. All the files are fully written to
+/// staging paths before any of them replace their target paths, and they
+/// replace their targets in the order they're given. A manifest listing the
+/// staged files is written next to the first file before any of them replace
+/// their targets, so that if the process is interrupted while the files are
+/// replacing their targets, [recover_interrupted_writes] can be used to finish
+/// replacing them.
+pub(crate) fn write_files_atomically(files: &[(&Path, &[u8])]) -> Result<(), Error> {
+    let mut staged_paths = Vec::new();
+
+    for (path, contents) in files {
+        match stage_file(path, contents) {
+            Ok(staged_path) => staged_paths.push(staged_path),
+            Err(e) => {
+                for staged_path in staged_paths {
+                    remove_staged_file(&staged_path)?;
+                }
+                return Err(e);
+            }
+        }
+    }
+
+    let manifest_path = match files {
+        [(first_path, _), _, ..] => {
+            let manifest_path = commit_manifest_path(first_path)?;
+            let target_paths: Vec<_> = files.iter().map(|(path, _)| *path).collect();
+
+            let result = stage_file(&manifest_path, &manifest_contents(&target_paths)).and_then(
+                |staged_manifest_path| commit_file(&staged_manifest_path, &manifest_path),
+            );
+
+            if let Err(e) = result {
+                for staged_path in staged_paths {
+                    remove_staged_file(&staged_path)?;
+                }
+                return Err(e);
+            }
+
+            Some(manifest_path)
+        }
+        _ => None,
+    };
+
+    for ((path, _), staged_path) in files.iter().zip(staged_paths) {
+        commit_file(&staged_path, path)?;
+    }
+
+    if let Some(manifest_path) = manifest_path {
+        remove_staged_file(&manifest_path)?;
+        sync_parent_directory(&manifest_path)?;
+    }
+
+    Ok(())
+}
+
+/// Complete or undo a call to [write_files_atomically] for the given paths
+/// that was interrupted. If the commit manifest was written, all the staged
+/// files had been completely written, so those listed in the manifest that
+/// haven't yet replaced their targets do so. Any other staged files (e.g.
+/// those left behind by an interrupted write that hadn't finished staging, or
+/// by another application) are removed.
+pub(crate) fn recover_interrupted_writes(paths: &[&Path]) -> Result<(), Error> {
+    let Some(first_path) = paths.first() else {
+        return Ok(());
+    };
+
+    let manifest_path = commit_manifest_path(first_path)?;
+
+    if manifest_path.exists() {
+        let manifest =
+            read_to_string(&manifest_path).map_err(|e| Error::IoError(manifest_path.clone(), e))?;
+        let manifest_paths: Vec<_> = manifest.lines().collect();
+
+        for path in paths {
+            let staged_path = staged_path(path)?;
+            if staged_path.exists() && manifest_paths.contains(&path.to_string_lossy().as_ref()) {
+                commit_file(&staged_path, path)?;
+            }
+        }
+
+        remove_staged_file(&manifest_path)?;
+    }
+
+    for path in paths {
+        remove_staged_file(&staged_path(path)?)?;
+    }
+
+    Ok(())
+}
+
+fn staged_path(path: &Path) -> Result<PathBuf, Error> {
+    path_with_extension_appended(path, STAGED_FILE_EXTENSION)
+}
+
+fn commit_manifest_path(path: &Path) -> Result<PathBuf, Error> {
+    path_with_extension_appended(path, COMMIT_MANIFEST_EXTENSION)
+}
+
+fn path_with_extension_appended(path: &Path, extension: &str) -> Result<PathBuf, Error> {
+    let mut file_name = path
+        .file_name()
+        .map(OsString::from)
+        .ok_or_else(|| Error::NoFilename(path.to_path_buf()))?;
+    file_name.push(extension);
+
+    Ok(path.with_file_name(file_name))
+}
+
+fn manifest_contents(paths: &[&Path]) -> Vec<u8> {
+    let mut contents = Vec::new();
+    for path in paths {
+        contents.extend_from_slice(path.to_string_lossy().as_bytes());
+        contents.push(b'\n');
+    }
+    contents
+}
+
+fn stage_file(path: &Path, contents: &[u8]) -> Result<PathBuf, Error> {
+    if let Some(parent_path) = path.parent().filter(|p| !p.exists()) {
+        create_dir_all(parent_path).map_err(|e| Error::IoError(parent_path.to_path_buf(), e))?;
+    }
+
+    let staged_path = staged_path(path)?;
+
+    let result = File::create(&staged_path)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .map_err(|e| Error::IoError(staged_path.clone(), e));
+
+    if let Err(e) = result {
+        remove_staged_file(&staged_path)?;
+        return Err(e);
+    }
+
+    Ok(staged_path)
+}
+
+fn commit_file(staged_path: &Path, path: &Path) -> Result<(), Error> {
+    rename(staged_path, path).map_err(|e| Error::IoError(path.to_path_buf(), e))?;
+
+    sync_parent_directory(path)
+}
+
+fn remove_staged_file(staged_path: &Path) -> Result<(), Error> {
+    match remove_file(staged_path) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(Error::IoError(staged_path.to_path_buf(), e)),
+    }
+}
+
+#[cfg(unix)]
+fn sync_parent_directory(path: &Path) -> Result<(), Error> {
+    // On Unix a rename isn't guaranteed to be durable until the directory
+    // containing the renamed file has been synced.
+    match path.parent() {
+        Some(parent_path) => File::open(parent_path)
+            .and_then(|f| f.sync_all())
+            .map_err(|e| Error::IoError(parent_path.to_path_buf(), e)),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+#[expect(
+    clippy::unnecessary_wraps,
+    reason = "Has the same signature as the Unix implementation"
+)]
+fn sync_parent_directory(_path: &Path) -> Result<(), Error> {
+    // Directories can't be opened as files on Windows, and renames are
+    // journalled by NTFS.
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    #[test]
+    fn write_file_atomically_should_replace_an_existing_file() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("plugins.txt");
+        std::fs::write(&path, "old").unwrap();
+
+        write_file_atomically(&path, b"new").unwrap();
+
+        assert_eq!("new", std::fs::read_to_string(&path).unwrap());
+        assert!(!staged_path(&path).unwrap().exists());
+    }
+
+    #[test]
+    fn write_file_atomically_should_create_parent_directories() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("a/b/plugins.txt");
+
+        write_file_atomically(&path, b"new").unwrap();
+
+        assert_eq!("new", std::fs::read_to_string(&path).unwrap());
+    }
+
+    #[test]
+    fn write_files_atomically_should_not_replace_any_files_if_staging_fails() {
+        let tmp_dir = tempdir().unwrap();
+        let path1 = tmp_dir.path().join("loadorder.txt");
+        let path2 = tmp_dir.path().join("plugins.txt");
+        std::fs::write(&path1, "old").unwrap();
+        // Staging will fail because the staging path is a directory.
+        create_dir_all(staged_path(&path2).unwrap()).unwrap();
+
+        assert!(write_files_atomically(&[(&path1, b"new"), (&path2, b"new")]).is_err());
+
+        assert_eq!("old", std::fs::read_to_string(&path1).unwrap());
+        assert!(!staged_path(&path1).unwrap().exists());
+    }
+
+    #[test]
+    fn write_files_atomically_should_remove_the_commit_manifest() {
+        let tmp_dir = tempdir().unwrap();
+        let path1 = tmp_dir.path().join("loadorder.txt");
+        let path2 = tmp_dir.path().join("plugins.txt");
+
+        write_files_atomically(&[(&path1, b"new1"), (&path2, b"new2")]).unwrap();
+
+        assert_eq!("new1", std::fs::read_to_string(&path1).unwrap());
+        assert_eq!("new2", std::fs::read_to_string(&path2).unwrap());
+        assert!(!commit_manifest_path(&path1).unwrap().exists());
+    }
+
+    #[test]
+    fn recover_interrupted_writes_should_commit_remaining_files_listed_in_the_commit_manifest() {
+        let tmp_dir = tempdir().unwrap();
+        let path1 = tmp_dir.path().join("loadorder.txt");
+        let path2 = tmp_dir.path().join("plugins.txt");
+        std::fs::write(&path1, "new").unwrap();
+        std::fs::write(&path2, "old").unwrap();
+        std::fs::write(staged_path(&path2).unwrap(), "new").unwrap();
+        std::fs::write(
+            commit_manifest_path(&path1).unwrap(),
+            manifest_contents(&[&path1, &path2]),
+        )
+        .unwrap();
+
+        recover_interrupted_writes(&[&path1, &path2]).unwrap();
+
+        assert_eq!("new", std::fs::read_to_string(&path2).unwrap());
+        assert!(!staged_path(&path2).unwrap().exists());
+        assert!(!commit_manifest_path(&path1).unwrap().exists());
+    }
+
+    #[test]
+    fn recover_interrupted_writes_should_remove_staged_files_not_listed_in_the_commit_manifest() {
+        let tmp_dir = tempdir().unwrap();
+        let path1 = tmp_dir.path().join("loadorder.txt");
+        let path2 = tmp_dir.path().join("plugins.txt");
+        std::fs::write(&path1, "new").unwrap();
+        std::fs::write(&path2, "old").unwrap();
+        std::fs::write(staged_path(&path2).unwrap(), "new").unwrap();
+        std::fs::write(
+            commit_manifest_path(&path1).unwrap(),
+            manifest_contents(&[&path1]),
+        )
+        .unwrap();
+
+        recover_interrupted_writes(&[&path1, &path2]).unwrap();
+
+        assert_eq!("old", std::fs::read_to_string(&path2).unwrap());
+        assert!(!staged_path(&path2).unwrap().exists());
+    }
+
+    #[test]
+    fn recover_interrupted_writes_should_remove_stray_staged_files_if_there_is_no_commit_manifest()
+    {
+        let tmp_dir = tempdir().unwrap();
+        let path1 = tmp_dir.path().join("loadorder.txt");
+        let path2 = tmp_dir.path().join("plugins.txt");
+        std::fs::write(&path1, "old").unwrap();
+        std::fs::write(&path2, "old").unwrap();
+        std::fs::write(staged_path(&path2).unwrap(), "stale").unwrap();
+
+        recover_interrupted_writes(&[&path1, &path2]).unwrap();
+
+        assert_eq!("old", std::fs::read_to_string(&path2).unwrap());
+        assert!(!staged_path(&path2).unwrap().exists());
+    }
+
+    #[test]
+    fn recover_interrupted_writes_should_remove_staged_files_if_the_first_was_not_committed() {
+        let tmp_dir = tempdir().unwrap();
+        let path1 = tmp_dir.path().join("loadorder.txt");
+        let path2 = tmp_dir.path().join("plugins.txt");
+        std::fs::write(&path1, "old").unwrap();
+        std::fs::write(&path2, "old").unwrap();
+        std::fs::write(staged_path(&path1).unwrap(), "new").unwrap();
+        std::fs::write(staged_path(&path2).unwrap(), "new").unwrap();
+
+        recover_interrupted_writes(&[&path1, &path2]).unwrap();
+
+        assert_eq!("old", std::fs::read_to_string(&path1).unwrap());
+        assert_eq!("old", std::fs::read_to_string(&path2).unwrap());
+        assert!(!staged_path(&path1).unwrap().exists());
+        assert!(!staged_path(&path2).unwrap().exists());
+    }
+}
@@ -0,0 +1,717 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::collections::HashSet;
+use std::fs::{
+    copy, create_dir_all, metadata, read, read_dir, read_to_string, remove_dir_all, remove_file,
+    write, File, FileTimes,
+};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use unicase::UniCase;
+
+use crate::atomic_write::write_files_atomically;
+use crate::enums::{Error, GameId, LoadOrderMethod};
+use crate::game_settings::GameSettings;
+use crate::ghostable_path::GhostablePath;
+use crate::ini::with_morrowind_game_files_section;
+use crate::openmw_config::{openmw_cfg_contents_with_load_order, read_active_plugin_names};
+use crate::plugin::trim_dot_ghost;
+
+/// The name of the file in a backup that holds the modification times of the
+/// game's plugins, for games that use them to decide the load order.
+const TIMESTAMPS_FILE_NAME: &str = "timestamps.txt";
+
+/// A backup of a game's active plugins and load order files, and of its plugin
+/// timestamps if they decide its load order, made before they were overwritten
+/// by saving a load order.
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub struct Backup {
+    id: String,
+    time: SystemTime,
+}
+
+impl Backup {
+    /// The ID of the backup, which can be passed to
+    /// [WritableLoadOrder::restore_backup](crate::WritableLoadOrder::restore_backup).
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// The time at which the backup was made.
+    pub fn time(&self) -> SystemTime {
+        self.time
+    }
+}
+
+/// Copy the game's existing active plugins and load order files and record its
+/// plugin timestamps in a new backup, if a backup directory is set, then delete
+/// the oldest backups if there are more than the maximum number.
+pub(crate) fn back_up_files(game_settings: &GameSettings) -> Result<(), Error> {
+    let Some(backup_directory) = game_settings.backup_directory() else {
+        return Ok(());
+    };
+
+    let existing_paths: Vec<_> = backed_up_file_paths(game_settings)
+        .into_iter()
+        .filter(|p| p.exists())
+        .collect();
+
+    let timestamps = plugin_timestamps(game_settings)?;
+
+    if existing_paths.is_empty() && timestamps.is_empty() {
+        return Ok(());
+    }
+
+    let backup_path = create_backup_directory(backup_directory)?;
+
+    for path in existing_paths {
+        let Some(file_name) = path.file_name() else {
+            return Err(Error::NoFilename(path.to_path_buf()));
+        };
+
+        copy(path, backup_path.join(file_name))
+            .map_err(|e| Error::IoError(path.to_path_buf(), e))?;
+    }
+
+    if !timestamps.is_empty() {
+        let timestamps_path = backup_path.join(TIMESTAMPS_FILE_NAME);
+        write(&timestamps_path, timestamps_file_contents(&timestamps))
+            .map_err(|e| Error::IoError(timestamps_path, e))?;
+    }
+
+    prune_backups(game_settings)
+}
+
+pub(crate) fn list_backups(game_settings: &GameSettings) -> Result<Vec<Backup>, Error> {
+    let Some(backup_directory) = game_settings.backup_directory() else {
+        return Ok(Vec::new());
+    };
+
+    if !backup_directory.exists() {
+        return Ok(Vec::new());
+    }
+
+    let backups = list_backup_ids(backup_directory)?
+        .iter()
+        .filter_map(|id| to_backup(id))
+        .collect();
+
+    Ok(backups)
+}
+
+/// Replace the game's active plugins and load order files and its plugin
+/// timestamps with those in the given backup. Files that weren't present when
+/// the backup was made are deleted, and plugins that have been uninstalled
+/// since are skipped. The replaced files are themselves backed up first.
+///
+/// Morrowind.ini and openmw.cfg also hold other settings, so only the parts of
+/// them that hold the load order are restored.
+pub(crate) fn restore_backup(game_settings: &GameSettings, backup_id: &str) -> Result<(), Error> {
+    let backup_path = game_settings
+        .backup_directory()
+        .filter(|_| to_backup(backup_id).is_some())
+        .map(|d| d.join(backup_id))
+        .filter(|p| p.is_dir())
+        .ok_or_else(|| Error::BackupNotFound(backup_id.to_owned()))?;
+
+    // Read the backup before making a new backup, in case making the new
+    // backup deletes the backup that's being restored.
+    let mut files = Vec::new();
+    let mut absent_paths = Vec::new();
+    for path in backed_up_file_paths(game_settings) {
+        let Some(file_name) = path.file_name() else {
+            return Err(Error::NoFilename(path.to_path_buf()));
+        };
+
+        match restored_contents(game_settings, path, &backup_path.join(file_name))? {
+            Some(contents) => files.push((path, contents)),
+            None => absent_paths.push(path),
+        }
+    }
+
+    let timestamps = read_timestamps_file(&backup_path.join(TIMESTAMPS_FILE_NAME))?;
+
+    back_up_files(game_settings)?;
+
+    let files: Vec<_> = files
+        .iter()
+        .map(|(path, contents)| (*path, contents.as_slice()))
+        .collect();
+    write_files_atomically(&files)?;
+
+    for path in absent_paths.into_iter().filter(|p| p.exists()) {
+        remove_file(path).map_err(|e| Error::IoError(path.to_path_buf(), e))?;
+    }
+
+    for (plugin_name, time) in timestamps {
+        if let Ok(path) = game_settings.plugin_path(&plugin_name).resolve_path() {
+            set_modification_time(&path, time)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Get the contents that the file at the given path should be restored to
+/// from its backed up copy, or None if it should be deleted.
+fn restored_contents(
+    game_settings: &GameSettings,
+    path: &Path,
+    backed_up_path: &Path,
+) -> Result<Option<Vec<u8>>, Error> {
+    if !backed_up_path.exists() && !path.exists() {
+        return Ok(None);
+    }
+
+    let is_active_plugins_file = path == game_settings.active_plugins_file();
+    match game_settings.id() {
+        GameId::Morrowind if is_active_plugins_file => {
+            let contents = read_if_exists(path)?;
+            let backed_up_contents = read_if_exists(backed_up_path)?;
+
+            Ok(Some(with_morrowind_game_files_section(
+                &contents,
+                &backed_up_contents,
+            )))
+        }
+        GameId::OpenMW if is_active_plugins_file => {
+            let plugin_names = read_active_plugin_names(backed_up_path)?;
+            let plugin_names: Vec<_> = plugin_names.iter().map(String::as_str).collect();
+
+            openmw_cfg_contents_with_load_order(path, &plugin_names).map(Some)
+        }
+        _ if backed_up_path.exists() => read(backed_up_path)
+            .map(Some)
+            .map_err(|e| Error::IoError(backed_up_path.to_path_buf(), e)),
+        _ => Ok(None),
+    }
+}
+
+fn read_if_exists(path: &Path) -> Result<Vec<u8>, Error> {
+    if path.exists() {
+        read(path).map_err(|e| Error::IoError(path.to_path_buf(), e))
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Get the modification times of the game's installed plugins if they decide
+/// its load order, or an empty list otherwise.
+fn plugin_timestamps(game_settings: &GameSettings) -> Result<Vec<(String, SystemTime)>, Error> {
+    if game_settings.load_order_method() != LoadOrderMethod::Timestamp {
+        return Ok(Vec::new());
+    }
+
+    // If a plugin is installed in more than one directory, only the first
+    // path found is used by the game.
+    let mut plugin_names = HashSet::new();
+    let mut timestamps = Vec::new();
+    for path in game_settings.find_plugins() {
+        let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+
+        let plugin_name = trim_dot_ghost(file_name, game_settings.id());
+        if !plugin_names.insert(UniCase::new(plugin_name.to_owned())) {
+            continue;
+        }
+
+        let time = metadata(&path)
+            .and_then(|m| m.modified())
+            .map_err(|e| Error::IoError(path.clone(), e))?;
+
+        timestamps.push((plugin_name.to_owned(), time));
+    }
+
+    Ok(timestamps)
+}
+
+/// Timestamps are written one plugin per line, as the signed number of seconds
+/// and nanoseconds since the Unix epoch, then a tab, then the plugin name.
+fn timestamps_file_contents(timestamps: &[(String, SystemTime)]) -> String {
+    let mut contents = String::new();
+    for (plugin_name, time) in timestamps {
+        let timestamp = match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => format!("{}.{:09}", d.as_secs(), d.subsec_nanos()),
+            Err(e) => format!(
+                "-{}.{:09}",
+                e.duration().as_secs(),
+                e.duration().subsec_nanos()
+            ),
+        };
+
+        contents.push_str(&timestamp);
+        contents.push('\t');
+        contents.push_str(plugin_name);
+        contents.push('\n');
+    }
+
+    contents
+}
+
+/// Read the timestamps in the file at the given path, skipping any lines that
+/// aren't valid. Backups made for games that don't use timestamps have no such
+/// file, so no timestamps are returned for them.
+fn read_timestamps_file(path: &Path) -> Result<Vec<(String, SystemTime)>, Error> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let contents = read_to_string(path).map_err(|e| Error::IoError(path.to_path_buf(), e))?;
+
+    let timestamps = contents
+        .lines()
+        .filter_map(|line| {
+            let (timestamp, plugin_name) = line.split_once('\t')?;
+            parse_timestamp(timestamp).map(|time| (plugin_name.to_owned(), time))
+        })
+        .collect();
+
+    Ok(timestamps)
+}
+
+fn parse_timestamp(timestamp: &str) -> Option<SystemTime> {
+    let (is_before_epoch, timestamp) = match timestamp.strip_prefix('-') {
+        Some(t) => (true, t),
+        None => (false, timestamp),
+    };
+
+    let (secs, nanos) = timestamp.split_once('.')?;
+    let nanos = nanos.parse().ok().filter(|n| *n < 1_000_000_000)?;
+    let duration = Duration::new(secs.parse().ok()?, nanos);
+
+    if is_before_epoch {
+        UNIX_EPOCH.checked_sub(duration)
+    } else {
+        UNIX_EPOCH.checked_add(duration)
+    }
+}
+
+fn set_modification_time(path: &Path, time: SystemTime) -> Result<(), Error> {
+    let times = FileTimes::new()
+        .set_accessed(SystemTime::now())
+        .set_modified(time);
+
+    File::options()
+        .write(true)
+        .open(path)
+        .and_then(|f| f.set_times(times))
+        .map_err(|e| Error::IoError(path.to_path_buf(), e))
+}
+
+fn backed_up_file_paths(game_settings: &GameSettings) -> Vec<&Path> {
+    let mut paths = Vec::new();
+    if let Some(path) = game_settings.load_order_file() {
+        paths.push(path.as_path());
+    }
+    paths.push(game_settings.active_plugins_file().as_path());
+    paths
+}
+
+fn to_backup(id: &str) -> Option<Backup> {
+    // IDs are the backup time as a zero-padded count of nanoseconds since the
+    // Unix epoch, so that they sort in the order they were made.
+    if id.len() != BACKUP_ID_LENGTH || !id.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    id.parse().ok().map(|nanos| Backup {
+        id: id.to_owned(),
+        time: UNIX_EPOCH + Duration::from_nanos(nanos),
+    })
+}
+
+const BACKUP_ID_LENGTH: usize = 20;
+
+fn create_backup_directory(backup_directory: &Path) -> Result<PathBuf, Error> {
+    create_dir_all(backup_directory)
+        .map_err(|e| Error::IoError(backup_directory.to_path_buf(), e))?;
+
+    let mut nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .ok()
+        .and_then(|d| u64::try_from(d.as_nanos()).ok())
+        .unwrap_or_default();
+
+    // Avoid reusing the ID of an existing backup if the clock has gone
+    // backwards or saves are made in very quick succession.
+    if let Some(latest) = list_backup_ids(backup_directory)?.last() {
+        if let Ok(latest_nanos) = latest.parse::<u64>() {
+            nanos = nanos.max(latest_nanos.saturating_add(1));
+        }
+    }
+
+    let backup_path = backup_directory.join(format!("{nanos:020}"));
+    create_dir_all(&backup_path).map_err(|e| Error::IoError(backup_path.clone(), e))?;
+
+    Ok(backup_path)
+}
+
+fn list_backup_ids(backup_directory: &Path) -> Result<Vec<String>, Error> {
+    let mut ids: Vec<_> = read_dir(backup_directory)
+        .map_err(|e| Error::IoError(backup_directory.to_path_buf(), e))?
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .filter_map(|e| e.file_name().into_string().ok())
+        .filter(|n| to_backup(n).is_some())
+        .collect();
+
+    ids.sort();
+
+    Ok(ids)
+}
+
+fn prune_backups(game_settings: &GameSettings) -> Result<(), Error> {
+    let Some(backup_directory) = game_settings.backup_directory() else {
+        return Ok(());
+    };
+
+    let max_count = game_settings.max_backup_count();
+    if max_count == 0 {
+        return Ok(());
+    }
+
+    let ids = list_backup_ids(backup_directory)?;
+    let excess_count = ids.len().saturating_sub(max_count);
+
+    for id in ids.iter().take(excess_count) {
+        let path = backup_directory.join(id);
+        remove_dir_all(&path).map_err(|e| Error::IoError(path, e))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crate::tests::{create_file, set_file_timestamps};
+
+    fn game_settings(game_dir: &Path) -> GameSettings {
+        game_settings_for(GameId::Skyrim, game_dir)
+    }
+
+    fn game_settings_for(game_id: GameId, game_dir: &Path) -> GameSettings {
+        let mut settings =
+            GameSettings::with_local_path(game_id, &game_dir.join("game"), &game_dir.join("local"))
+                .unwrap();
+        settings.set_backup_directory(Some(game_dir.join("backups")));
+        settings
+    }
+
+    fn modification_time(path: &Path) -> SystemTime {
+        metadata(path).unwrap().modified().unwrap()
+    }
+
+    fn write_files(settings: &GameSettings, contents: &str) {
+        create_dir_all(settings.active_plugins_file().parent().unwrap()).unwrap();
+        std::fs::write(settings.active_plugins_file(), contents).unwrap();
+        std::fs::write(settings.load_order_file().unwrap(), contents).unwrap();
+    }
+
+    #[test]
+    fn back_up_files_should_do_nothing_if_no_backup_directory_is_set() {
+        let tmp_dir = tempdir().unwrap();
+        let mut settings = game_settings(tmp_dir.path());
+        settings.set_backup_directory(None);
+        write_files(&settings, "a");
+
+        back_up_files(&settings).unwrap();
+
+        assert!(!tmp_dir.path().join("backups").exists());
+    }
+
+    #[test]
+    fn back_up_files_should_do_nothing_if_no_files_exist() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings(tmp_dir.path());
+
+        back_up_files(&settings).unwrap();
+
+        assert!(list_backups(&settings).unwrap().is_empty());
+    }
+
+    #[test]
+    fn back_up_files_should_copy_existing_files_into_a_new_backup() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings(tmp_dir.path());
+        write_files(&settings, "a");
+
+        back_up_files(&settings).unwrap();
+
+        let backups = list_backups(&settings).unwrap();
+        assert_eq!(1, backups.len());
+
+        let backup_path = tmp_dir.path().join("backups").join(backups[0].id());
+        assert_eq!(
+            "a",
+            std::fs::read_to_string(backup_path.join("Plugins.txt")).unwrap()
+        );
+        assert_eq!(
+            "a",
+            std::fs::read_to_string(backup_path.join("loadorder.txt")).unwrap()
+        );
+    }
+
+    #[test]
+    fn back_up_files_should_delete_the_oldest_backups_past_the_max_count() {
+        let tmp_dir = tempdir().unwrap();
+        let mut settings = game_settings(tmp_dir.path());
+        settings.set_max_backup_count(2);
+        write_files(&settings, "a");
+
+        back_up_files(&settings).unwrap();
+        let first_backup = list_backups(&settings).unwrap().remove(0);
+        back_up_files(&settings).unwrap();
+        back_up_files(&settings).unwrap();
+
+        let backups = list_backups(&settings).unwrap();
+        assert_eq!(2, backups.len());
+        assert!(!backups.contains(&first_backup));
+        assert!(backups[0].time() < backups[1].time());
+    }
+
+    #[test]
+    fn list_backups_should_ignore_entries_that_are_not_backups() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings(tmp_dir.path());
+        let backup_directory = tmp_dir.path().join("backups");
+        create_dir_all(backup_directory.join("other")).unwrap();
+        create_dir_all(backup_directory.join("00000000000000000001")).unwrap();
+        std::fs::write(backup_directory.join("00000000000000000002"), "").unwrap();
+
+        let backups = list_backups(&settings).unwrap();
+
+        assert_eq!(1, backups.len());
+        assert_eq!("00000000000000000001", backups[0].id());
+        assert_eq!(UNIX_EPOCH + Duration::from_nanos(1), backups[0].time());
+    }
+
+    #[test]
+    fn restore_backup_should_error_if_the_backup_does_not_exist() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings(tmp_dir.path());
+
+        match restore_backup(&settings, "00000000000000000001").unwrap_err() {
+            Error::BackupNotFound(id) => assert_eq!("00000000000000000001", id),
+            e => panic!("Expected backup not found error, got {e:?}"),
+        }
+
+        assert!(restore_backup(&settings, "../backups").is_err());
+    }
+
+    #[test]
+    fn restore_backup_should_replace_files_and_back_up_the_replaced_files() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings(tmp_dir.path());
+        write_files(&settings, "a");
+        back_up_files(&settings).unwrap();
+        write_files(&settings, "b");
+
+        let backup = list_backups(&settings).unwrap().remove(0);
+        restore_backup(&settings, backup.id()).unwrap();
+
+        assert_eq!(
+            "a",
+            std::fs::read_to_string(settings.active_plugins_file()).unwrap()
+        );
+        assert_eq!(
+            "a",
+            std::fs::read_to_string(settings.load_order_file().unwrap()).unwrap()
+        );
+
+        let backups = list_backups(&settings).unwrap();
+        assert_eq!(2, backups.len());
+        let backup_path = tmp_dir.path().join("backups").join(backups[1].id());
+        assert_eq!(
+            "b",
+            std::fs::read_to_string(backup_path.join("Plugins.txt")).unwrap()
+        );
+    }
+
+    #[test]
+    fn restore_backup_should_delete_files_that_are_not_in_the_backup() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings(tmp_dir.path());
+        create_dir_all(settings.active_plugins_file().parent().unwrap()).unwrap();
+        std::fs::write(settings.active_plugins_file(), "a").unwrap();
+        back_up_files(&settings).unwrap();
+        write_files(&settings, "b");
+
+        let backup = list_backups(&settings).unwrap().remove(0);
+        restore_backup(&settings, backup.id()).unwrap();
+
+        assert_eq!(
+            "a",
+            std::fs::read_to_string(settings.active_plugins_file()).unwrap()
+        );
+        assert!(!settings.load_order_file().unwrap().exists());
+    }
+
+    #[test]
+    fn back_up_files_should_record_plugin_timestamps_for_timestamp_based_games() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings_for(GameId::Oblivion, tmp_dir.path());
+        let plugin_path = settings.plugins_directory().join("Blank.esp");
+        create_file(&plugin_path);
+        set_file_timestamps(&plugin_path, 10);
+        create_file(&settings.plugins_directory().join("Blank.esm.ghost"));
+
+        back_up_files(&settings).unwrap();
+
+        let backups = list_backups(&settings).unwrap();
+        assert_eq!(1, backups.len());
+
+        let timestamps = read_timestamps_file(
+            &tmp_dir
+                .path()
+                .join("backups")
+                .join(backups[0].id())
+                .join(TIMESTAMPS_FILE_NAME),
+        )
+        .unwrap();
+        assert_eq!(2, timestamps.len());
+        assert!(
+            timestamps.contains(&("Blank.esp".to_owned(), UNIX_EPOCH + Duration::from_secs(10)))
+        );
+        assert!(timestamps.iter().any(|(name, _)| name == "Blank.esm"));
+    }
+
+    #[test]
+    fn back_up_files_should_not_record_plugin_timestamps_for_other_games() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings(tmp_dir.path());
+        create_file(&settings.plugins_directory().join("Blank.esp"));
+        write_files(&settings, "a");
+
+        back_up_files(&settings).unwrap();
+
+        let backups = list_backups(&settings).unwrap();
+        let backup_path = tmp_dir.path().join("backups").join(backups[0].id());
+        assert!(!backup_path.join(TIMESTAMPS_FILE_NAME).exists());
+    }
+
+    #[test]
+    fn restore_backup_should_restore_plugin_timestamps() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings_for(GameId::Oblivion, tmp_dir.path());
+        let plugin_path = settings.plugins_directory().join("Blank.esp");
+        let ghosted_plugin_path = settings.plugins_directory().join("Blank.esm");
+        create_file(&plugin_path);
+        create_file(&ghosted_plugin_path);
+        set_file_timestamps(&plugin_path, 10);
+        set_file_timestamps(&ghosted_plugin_path, 20);
+        back_up_files(&settings).unwrap();
+
+        set_file_timestamps(&plugin_path, 30);
+        let ghosted_plugin_path = ghosted_plugin_path.as_ghosted_path().unwrap();
+        std::fs::rename(
+            settings.plugins_directory().join("Blank.esm"),
+            &ghosted_plugin_path,
+        )
+        .unwrap();
+        set_file_timestamps(&ghosted_plugin_path, 40);
+
+        let backup = list_backups(&settings).unwrap().remove(0);
+        restore_backup(&settings, backup.id()).unwrap();
+
+        assert_eq!(
+            UNIX_EPOCH + Duration::from_secs(10),
+            modification_time(&plugin_path)
+        );
+        assert_eq!(
+            UNIX_EPOCH + Duration::from_secs(20),
+            modification_time(&ghosted_plugin_path)
+        );
+    }
+
+    #[test]
+    fn restore_backup_should_skip_timestamps_of_plugins_that_are_no_longer_installed() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings_for(GameId::Oblivion, tmp_dir.path());
+        let plugin_path = settings.plugins_directory().join("Blank.esp");
+        create_file(&plugin_path);
+        back_up_files(&settings).unwrap();
+        remove_file(&plugin_path).unwrap();
+
+        let backup = list_backups(&settings).unwrap().remove(0);
+        restore_backup(&settings, backup.id()).unwrap();
+
+        assert!(!plugin_path.exists());
+    }
+
+    #[test]
+    fn timestamps_should_round_trip_before_and_after_the_unix_epoch() {
+        let after = UNIX_EPOCH + Duration::new(10, 5);
+        let before = UNIX_EPOCH - Duration::new(10, 5);
+        let contents =
+            timestamps_file_contents(&[("a.esp".to_owned(), after), ("b.esp".to_owned(), before)]);
+
+        assert_eq!("10.000000005\ta.esp\n-10.000000005\tb.esp\n", contents);
+        assert_eq!(Some(after), parse_timestamp("10.000000005"));
+        assert_eq!(Some(before), parse_timestamp("-10.000000005"));
+        assert_eq!(None, parse_timestamp("10.1000000000"));
+        assert_eq!(None, parse_timestamp("10"));
+    }
+
+    #[test]
+    fn restore_backup_should_only_restore_the_game_files_section_of_morrowind_ini() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings_for(GameId::Morrowind, tmp_dir.path());
+        let ini_path = settings.active_plugins_file();
+        create_dir_all(ini_path.parent().unwrap()).unwrap();
+        std::fs::write(ini_path, "[General]\na=1\n[Game Files]\nGameFile0=a.esm\n").unwrap();
+        back_up_files(&settings).unwrap();
+        std::fs::write(ini_path, "[General]\na=2\n[Game Files]\nGameFile0=b.esm\n").unwrap();
+
+        let backup = list_backups(&settings).unwrap().remove(0);
+        restore_backup(&settings, backup.id()).unwrap();
+
+        assert_eq!(
+            "[General]\na=2\n[Game Files]\nGameFile0=a.esm\n",
+            std::fs::read_to_string(ini_path).unwrap()
+        );
+    }
+
+    #[test]
+    fn restore_backup_should_only_restore_the_content_lines_of_openmw_cfg() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings_for(GameId::OpenMW, tmp_dir.path());
+        let cfg_path = settings.active_plugins_file();
+        create_dir_all(cfg_path.parent().unwrap()).unwrap();
+        std::fs::write(cfg_path, "fallback=a\ncontent=a.esm\ncontent=b.esp\n").unwrap();
+        back_up_files(&settings).unwrap();
+        std::fs::write(cfg_path, "fallback=b\ncontent=c.esm\n").unwrap();
+
+        let backup = list_backups(&settings).unwrap().remove(0);
+        restore_backup(&settings, backup.id()).unwrap();
+
+        assert_eq!(
+            vec!["a.esm", "b.esp"],
+            read_active_plugin_names(cfg_path).unwrap()
+        );
+        let contents = std::fs::read_to_string(cfg_path).unwrap();
+        assert!(contents.contains("fallback=b"));
+        assert!(!contents.contains("fallback=a"));
+    }
+}
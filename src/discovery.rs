@@ -0,0 +1,895 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::collections::HashSet;
+use std::fs::{canonicalize, read_dir, read_to_string};
+use std::path::{Path, PathBuf};
+
+use keyvalues_parser::{Value, Vdf};
+
+use crate::enums::{Error, GameId};
+use crate::game_settings::GameSettings;
+use crate::ini::to_vdf_parsing_error;
+
+/// The store or launcher that a game install was found through.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum GameSource {
+    Steam,
+    Gog,
+    Epic,
+}
+
+/// A game install found by [discover_installs].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct GameInstall {
+    game_id: GameId,
+    game_path: PathBuf,
+    source: GameSource,
+}
+
+impl GameInstall {
+    pub fn game_id(&self) -> GameId {
+        self.game_id
+    }
+
+    pub fn game_path(&self) -> &Path {
+        &self.game_path
+    }
+
+    pub fn source(&self) -> GameSource {
+        self.source
+    }
+
+    /// Create settings for the install, as [GameSettings::new] does.
+    pub fn game_settings(&self) -> Result<GameSettings, Error> {
+        GameSettings::new(self.game_id, &self.game_path)
+    }
+}
+
+/// The game installs found by one of the discovery functions, along with
+/// the errors encountered while looking for them. A Steam, GOG or Epic Games
+/// Launcher file or directory that can't be read or parsed is skipped and its
+/// error recorded, so that it doesn't hide the installs found through others.
+#[derive(Debug, Default)]
+pub struct DiscoveredInstalls {
+    installs: Vec<GameInstall>,
+    errors: Vec<Error>,
+}
+
+impl DiscoveredInstalls {
+    pub fn installs(&self) -> &[GameInstall] {
+        &self.installs
+    }
+
+    pub fn errors(&self) -> &[Error] {
+        &self.errors
+    }
+
+    pub fn into_installs(self) -> Vec<GameInstall> {
+        self.installs
+    }
+
+    fn extend(&mut self, other: DiscoveredInstalls) {
+        self.installs.extend(other.installs);
+        self.errors.extend(other.errors);
+    }
+
+    fn deduplicated(self) -> Self {
+        Self {
+            installs: deduplicate_installs(self.installs),
+            errors: self.errors,
+        }
+    }
+}
+
+/// Steam app IDs of the supported games.
+const STEAM_APP_IDS: &[(u32, GameId)] = &[
+    (22320, GameId::Morrowind),
+    (22330, GameId::Oblivion),
+    (900_883, GameId::Oblivion),
+    (72850, GameId::Skyrim),
+    (933_480, GameId::Enderal),
+    (22300, GameId::Fallout3),
+    (22370, GameId::Fallout3),
+    (22380, GameId::FalloutNV),
+    (22490, GameId::FalloutNV),
+    (377_160, GameId::Fallout4),
+    (489_830, GameId::SkyrimSE),
+    (976_620, GameId::EnderalSE),
+    (611_660, GameId::Fallout4VR),
+    (611_670, GameId::SkyrimVR),
+    (1_716_740, GameId::Starfield),
+    (2_623_190, GameId::OblivionRemastered),
+];
+
+/// GOG product IDs of the supported games, which are used in the names of
+/// the `goggame-<id>.info` files that GOG installs alongside each game.
+const GOG_PRODUCT_IDS: &[(&str, GameId)] = &[
+    ("1440163901", GameId::Morrowind),
+    ("1435828767", GameId::Morrowind),
+    ("1242989820", GameId::Oblivion),
+    ("1458058109", GameId::Oblivion),
+    ("1711230643", GameId::SkyrimSE),
+    ("1708684988", GameId::EnderalSE),
+    ("1454315831", GameId::Fallout3),
+    ("1248282609", GameId::Fallout3),
+    ("1312824873", GameId::FalloutNV),
+    ("1454587428", GameId::FalloutNV),
+    ("1998527297", GameId::Fallout4),
+];
+
+/// Epic Games Store app names of the supported games, as given in the
+/// `AppName` field of the Epic Games Launcher's manifest files.
+const EPIC_APP_NAMES: &[(&str, GameId)] = &[
+    ("ac82db5035584c7f8a2c548d98c86b2c", GameId::SkyrimSE),
+    ("adeae8bbfc94427db57c7dfecce3f1d4", GameId::Fallout3),
+    ("5daeb974a22a435988892319b3a4f476", GameId::FalloutNV),
+    ("61d52ce4d09d41e48800c22784d13ae8", GameId::Fallout4),
+];
+
+/// Find installs of supported games in the current user's default Steam
+/// install locations, the default GOG install directories and the Epic Games
+/// Launcher's default manifests directory. Each install is only listed once,
+/// even if it can be reached through more than one path.
+pub fn discover_installs() -> DiscoveredInstalls {
+    let mut discovered = DiscoveredInstalls::default();
+
+    for steam_root in default_steam_roots() {
+        if steam_root.is_dir() {
+            discovered.extend(discover_steam_installs(&steam_root));
+        }
+    }
+
+    for games_directory in default_gog_games_directories() {
+        discovered.extend(discover_gog_installs(&games_directory));
+    }
+
+    if let Some(manifests_directory) = default_epic_manifests_directory() {
+        discovered.extend(discover_epic_installs(&manifests_directory));
+    }
+
+    discovered.deduplicated()
+}
+
+/// Find installs of supported games in the libraries of the Steam install at
+/// the given path, which is the directory that contains Steam's `steamapps`
+/// directory.
+pub fn discover_steam_installs(steam_root: &Path) -> DiscoveredInstalls {
+    let mut discovered = DiscoveredInstalls::default();
+
+    for library_path in steam_library_paths(steam_root, &mut discovered.errors) {
+        let steamapps_path = library_path.join("steamapps");
+
+        for (app_id, game_id) in STEAM_APP_IDS {
+            let manifest_path = steamapps_path.join(format!("appmanifest_{app_id}.acf"));
+            if !manifest_path.exists() {
+                continue;
+            }
+
+            let install_dir = match read_steam_install_dir(&manifest_path) {
+                Ok(Some(install_dir)) => install_dir,
+                Ok(None) => continue,
+                Err(e) => {
+                    discovered.errors.push(e);
+                    continue;
+                }
+            };
+
+            let game_path = steamapps_path.join("common").join(install_dir);
+            if game_path.is_dir() {
+                discovered.installs.push(GameInstall {
+                    game_id: *game_id,
+                    game_path,
+                    source: GameSource::Steam,
+                });
+            }
+        }
+    }
+
+    discovered.deduplicated()
+}
+
+/// Find installs of supported games made by GOG's installers in the given
+/// directory or its immediate subdirectories. GOG installs are recognised by
+/// the `goggame-<id>.info` file that is installed alongside each game.
+pub fn discover_gog_installs(games_directory: &Path) -> DiscoveredInstalls {
+    if !games_directory.is_dir() {
+        return DiscoveredInstalls::default();
+    }
+
+    let entries = match read_dir(games_directory) {
+        Ok(entries) => entries,
+        Err(e) => return read_dir_error(games_directory, e),
+    };
+
+    let mut game_paths: Vec<_> = entries
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .map(|e| e.path())
+        .collect();
+
+    game_paths.sort();
+    game_paths.insert(0, games_directory.to_path_buf());
+
+    let installs = game_paths
+        .into_iter()
+        .filter_map(|game_path| {
+            find_gog_game_id(&game_path).map(|game_id| GameInstall {
+                game_id,
+                game_path,
+                source: GameSource::Gog,
+            })
+        })
+        .collect();
+
+    DiscoveredInstalls {
+        installs,
+        errors: Vec::new(),
+    }
+    .deduplicated()
+}
+
+/// Find installs of supported games that are listed in the Epic Games
+/// Launcher manifest (`.item`) files in the given directory. Installs whose
+/// directories don't exist are skipped.
+pub fn discover_epic_installs(manifests_directory: &Path) -> DiscoveredInstalls {
+    if !manifests_directory.is_dir() {
+        return DiscoveredInstalls::default();
+    }
+
+    let entries = match read_dir(manifests_directory) {
+        Ok(entries) => entries,
+        Err(e) => return read_dir_error(manifests_directory, e),
+    };
+
+    let mut manifest_paths: Vec<_> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| {
+            p.extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("item"))
+        })
+        .collect();
+
+    manifest_paths.sort();
+
+    let mut discovered = DiscoveredInstalls::default();
+    for manifest_path in manifest_paths {
+        let content = match read_to_string(&manifest_path) {
+            Ok(content) => content,
+            Err(e) => {
+                discovered.errors.push(Error::IoError(manifest_path, e));
+                continue;
+            }
+        };
+
+        let manifest: serde_json::Value = match serde_json::from_str(&content) {
+            Ok(manifest) => manifest,
+            Err(e) => {
+                discovered
+                    .errors
+                    .push(Error::JsonParsingError(manifest_path, e.to_string()));
+                continue;
+            }
+        };
+
+        let game_id = json_string_value(&manifest, "AppName").and_then(|app_name| {
+            EPIC_APP_NAMES
+                .iter()
+                .find(|(name, _)| *name == app_name)
+                .map(|(_, game_id)| *game_id)
+        });
+
+        let Some(game_id) = game_id else {
+            continue;
+        };
+
+        if let Some(game_path) = json_string_value(&manifest, "InstallLocation").map(PathBuf::from)
+        {
+            if game_path.is_dir() {
+                discovered.installs.push(GameInstall {
+                    game_id,
+                    game_path,
+                    source: GameSource::Epic,
+                });
+            }
+        }
+    }
+
+    discovered.deduplicated()
+}
+
+fn read_dir_error(path: &Path, error: std::io::Error) -> DiscoveredInstalls {
+    DiscoveredInstalls {
+        installs: Vec::new(),
+        errors: vec![Error::IoError(path.to_path_buf(), error)],
+    }
+}
+
+/// Get the ID of the supported game that GOG installed at the given path, if
+/// any.
+pub(crate) fn find_gog_game_id(game_path: &Path) -> Option<GameId> {
+    GOG_PRODUCT_IDS
+        .iter()
+        .find(|(product_id, _)| {
+            game_path
+                .join(format!("goggame-{product_id}.info"))
+                .exists()
+        })
+        .map(|(_, game_id)| *game_id)
+}
+
+/// Get the path of the Wine prefix that Proton uses for the Steam install of
+/// the game at the given path, if there is one.
+#[cfg(not(windows))]
+pub(crate) fn find_proton_prefix(game_id: GameId, game_path: &Path) -> Option<PathBuf> {
+    let install_dir = game_path.file_name()?;
+    let steamapps_path = game_path.parent()?.parent()?;
+
+    STEAM_APP_IDS
+        .iter()
+        .filter(|(_, id)| {
+            // Enderal and Enderal Special Edition may also be run as Skyrim
+            // and Skyrim Special Edition.
+            *id == game_id
+                || matches!(
+                    (game_id, id),
+                    (GameId::Skyrim, GameId::Enderal) | (GameId::SkyrimSE, GameId::EnderalSE)
+                )
+        })
+        .find_map(|(app_id, _)| {
+            let manifest_path = steamapps_path.join(format!("appmanifest_{app_id}.acf"));
+            let manifest_install_dir = read_steam_install_dir(&manifest_path).ok().flatten()?;
+            if install_dir != manifest_install_dir.as_str() {
+                return None;
+            }
+
+            let prefix_path = steamapps_path
+                .join("compatdata")
+                .join(app_id.to_string())
+                .join("pfx");
+            prefix_path.is_dir().then_some(prefix_path)
+        })
+}
+
+#[cfg(windows)]
+fn default_steam_roots() -> Vec<PathBuf> {
+    let program_files = std::env::var_os("ProgramFiles(x86)")
+        .map_or_else(|| PathBuf::from("C:\\Program Files (x86)"), PathBuf::from);
+
+    vec![program_files.join("Steam")]
+}
+
+#[cfg(not(windows))]
+fn default_steam_roots() -> Vec<PathBuf> {
+    let mut roots = Vec::new();
+
+    if let Some(home_path) = dirs::home_dir() {
+        roots.push(home_path.join(".steam/steam"));
+        roots.push(home_path.join(".var/app/com.valvesoftware.Steam/.local/share/Steam"));
+    }
+
+    if let Some(data_path) = dirs::data_dir() {
+        roots.push(data_path.join("Steam"));
+    }
+
+    roots
+}
+
+#[cfg(windows)]
+fn default_gog_games_directories() -> Vec<PathBuf> {
+    let program_files = std::env::var_os("ProgramFiles(x86)")
+        .map_or_else(|| PathBuf::from("C:\\Program Files (x86)"), PathBuf::from);
+
+    vec![
+        PathBuf::from("C:\\GOG Games"),
+        program_files.join("GOG Galaxy\\Games"),
+    ]
+}
+
+#[cfg(not(windows))]
+fn default_gog_games_directories() -> Vec<PathBuf> {
+    // Heroic Games Launcher installs GOG games into ~/Games/Heroic by default.
+    dirs::home_dir()
+        .map(|home_path| vec![home_path.join("GOG Games"), home_path.join("Games/Heroic")])
+        .unwrap_or_default()
+}
+
+#[cfg(windows)]
+fn default_epic_manifests_directory() -> Option<PathBuf> {
+    let program_data = std::env::var_os("ProgramData")
+        .map_or_else(|| PathBuf::from("C:\\ProgramData"), PathBuf::from);
+
+    Some(program_data.join("Epic\\EpicGamesLauncher\\Data\\Manifests"))
+}
+
+#[cfg(not(windows))]
+fn default_epic_manifests_directory() -> Option<PathBuf> {
+    // The Epic Games Launcher doesn't run natively outside of Windows.
+    None
+}
+
+/// Get the paths of the Steam libraries listed in Steam's libraryfolders.vdf,
+/// starting with the Steam install itself. Libraries that don't exist are
+/// skipped, and so are the libraries listed in a file that can't be read,
+/// with its error added to the given errors.
+fn steam_library_paths(steam_root: &Path, errors: &mut Vec<Error>) -> Vec<PathBuf> {
+    let mut paths = vec![steam_root.to_path_buf()];
+
+    // Different versions of Steam have kept the file in one or both of these
+    // locations, so read any that exist.
+    for relative_path in ["steamapps/libraryfolders.vdf", "config/libraryfolders.vdf"] {
+        let vdf_path = steam_root.join(relative_path);
+        if vdf_path.exists() {
+            match read_steam_library_folders(&vdf_path) {
+                Ok(library_paths) => paths.extend(library_paths),
+                Err(e) => errors.push(e),
+            }
+        }
+    }
+
+    let mut seen_paths = HashSet::new();
+    paths
+        .retain(|p| p.is_dir() && seen_paths.insert(canonicalize(p).unwrap_or_else(|_| p.clone())));
+
+    paths
+}
+
+fn read_steam_library_folders(vdf_path: &Path) -> Result<Vec<PathBuf>, Error> {
+    let content =
+        std::fs::read_to_string(vdf_path).map_err(|e| Error::IoError(vdf_path.to_path_buf(), e))?;
+
+    let vdf = Vdf::parse(&content).map_err(|e| to_vdf_parsing_error(vdf_path, e))?;
+
+    let Some(folders) = vdf.value.get_obj() else {
+        return Ok(Vec::new());
+    };
+
+    // Libraries are listed under numeric keys. Newer versions of Steam give
+    // each library an object with a path, older versions just give the path.
+    let paths = folders
+        .iter()
+        .filter(|(key, _)| key.bytes().all(|b| b.is_ascii_digit()))
+        .filter_map(|(_, values)| values.first())
+        .filter_map(|value| match value {
+            Value::Str(path) => Some(PathBuf::from(path.as_ref())),
+            Value::Obj(obj) => obj
+                .get("path")
+                .and_then(|v| v.first())
+                .and_then(Value::get_str)
+                .map(PathBuf::from),
+        })
+        .collect();
+
+    Ok(paths)
+}
+
+fn read_steam_install_dir(manifest_path: &Path) -> Result<Option<String>, Error> {
+    let content = std::fs::read_to_string(manifest_path)
+        .map_err(|e| Error::IoError(manifest_path.to_path_buf(), e))?;
+
+    let install_dir = Vdf::parse(&content)
+        .map_err(|e| to_vdf_parsing_error(manifest_path, e))?
+        .value
+        .get_obj()
+        .and_then(|o| o.get("installdir"))
+        .and_then(|v| v.first())
+        .and_then(Value::get_str)
+        .filter(|d| !d.is_empty())
+        .map(str::to_owned);
+
+    Ok(install_dir)
+}
+
+/// Get the value of the given top-level string field in an Epic Games
+/// Launcher manifest.
+fn json_string_value<'a>(manifest: &'a serde_json::Value, key: &str) -> Option<&'a str> {
+    manifest.get(key).and_then(serde_json::Value::as_str)
+}
+
+fn deduplicate_installs(installs: Vec<GameInstall>) -> Vec<GameInstall> {
+    let mut seen = HashSet::new();
+
+    installs
+        .into_iter()
+        .filter(|i| {
+            let path = canonicalize(&i.game_path).unwrap_or_else(|_| i.game_path.clone());
+            seen.insert((i.game_id, path))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::{create_dir_all, write};
+
+    use tempfile::tempdir;
+
+    fn write_app_manifest(library_path: &Path, app_id: u32, install_dir: &str) {
+        let steamapps_path = library_path.join("steamapps");
+        create_dir_all(steamapps_path.join("common").join(install_dir)).unwrap();
+        write(
+            steamapps_path.join(format!("appmanifest_{app_id}.acf")),
+            format!(
+                "\"AppState\"\n{{\n\t\"appid\"\t\t\"{app_id}\"\n\t\"installdir\"\t\t\"{install_dir}\"\n}}\n"
+            ),
+        )
+        .unwrap();
+    }
+
+    fn write_library_folders(steam_root: &Path, relative_path: &str, content: &str) {
+        let path = steam_root.join(relative_path);
+        create_dir_all(path.parent().unwrap()).unwrap();
+        write(path, content).unwrap();
+    }
+
+    fn escape(path: &Path) -> String {
+        path.to_str().unwrap().replace('\\', "\\\\")
+    }
+
+    #[test]
+    fn discover_steam_installs_should_find_games_in_the_steam_root_library() {
+        let tmp_dir = tempdir().unwrap();
+        let steam_root = tmp_dir.path().join("Steam");
+        write_app_manifest(&steam_root, 489_830, "Skyrim Special Edition");
+
+        let installs = discover_steam_installs(&steam_root).into_installs();
+
+        assert_eq!(
+            vec![GameInstall {
+                game_id: GameId::SkyrimSE,
+                game_path: steam_root.join("steamapps/common/Skyrim Special Edition"),
+                source: GameSource::Steam,
+            }],
+            installs
+        );
+    }
+
+    #[test]
+    fn discover_steam_installs_should_find_games_in_all_libraries() {
+        let tmp_dir = tempdir().unwrap();
+        let steam_root = tmp_dir.path().join("Steam");
+        let library_path = tmp_dir.path().join("Library");
+        write_app_manifest(&steam_root, 22330, "Oblivion");
+        write_app_manifest(&library_path, 377_160, "Fallout 4");
+        write_library_folders(
+            &steam_root,
+            "config/libraryfolders.vdf",
+            &format!(
+                "\"libraryfolders\"\n{{\n\t\"0\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n\t\"1\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t\t\"label\"\t\t\"\"\n\t}}\n}}\n",
+                escape(&steam_root),
+                escape(&library_path)
+            ),
+        );
+
+        let installs = discover_steam_installs(&steam_root).into_installs();
+
+        assert_eq!(
+            vec![
+                (
+                    GameId::Oblivion,
+                    steam_root.join("steamapps/common/Oblivion")
+                ),
+                (
+                    GameId::Fallout4,
+                    library_path.join("steamapps/common/Fallout 4")
+                ),
+            ],
+            installs
+                .iter()
+                .map(|i| (i.game_id(), i.game_path().to_path_buf()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn discover_steam_installs_should_read_the_old_library_folders_format() {
+        let tmp_dir = tempdir().unwrap();
+        let steam_root = tmp_dir.path().join("Steam");
+        let library_path = tmp_dir.path().join("Library");
+        write_app_manifest(&library_path, 22380, "Fallout New Vegas");
+        write_library_folders(
+            &steam_root,
+            "steamapps/libraryfolders.vdf",
+            &format!(
+                "\"LibraryFolders\"\n{{\n\t\"TimeNextStatsReport\"\t\t\"0\"\n\t\"1\"\t\t\"{}\"\n}}\n",
+                escape(&library_path)
+            ),
+        );
+
+        let installs = discover_steam_installs(&steam_root).into_installs();
+
+        assert_eq!(1, installs.len());
+        assert_eq!(GameId::FalloutNV, installs[0].game_id());
+        assert_eq!(GameSource::Steam, installs[0].source());
+    }
+
+    #[test]
+    fn discover_steam_installs_should_skip_missing_libraries_and_game_directories() {
+        let tmp_dir = tempdir().unwrap();
+        let steam_root = tmp_dir.path().join("Steam");
+        write_app_manifest(&steam_root, 72850, "Skyrim");
+        std::fs::remove_dir(steam_root.join("steamapps/common/Skyrim")).unwrap();
+        write_library_folders(
+            &steam_root,
+            "config/libraryfolders.vdf",
+            &format!(
+                "\"libraryfolders\"\n{{\n\t\"1\"\n\t{{\n\t\t\"path\"\t\t\"{}\"\n\t}}\n}}\n",
+                escape(&tmp_dir.path().join("missing"))
+            ),
+        );
+
+        assert!(discover_steam_installs(&steam_root)
+            .into_installs()
+            .is_empty());
+    }
+
+    #[test]
+    fn discover_steam_installs_should_return_an_empty_vec_if_the_steam_root_does_not_exist() {
+        let tmp_dir = tempdir().unwrap();
+
+        let installs = discover_steam_installs(&tmp_dir.path().join("Steam")).into_installs();
+
+        assert!(installs.is_empty());
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn find_proton_prefix_should_return_the_compatdata_prefix_for_the_game_app_id() {
+        let tmp_dir = tempdir().unwrap();
+        let library_path = tmp_dir.path().join("Library");
+        write_app_manifest(&library_path, 489_830, "Skyrim Special Edition");
+        write_app_manifest(&library_path, 976_620, "Enderal Special Edition");
+        let prefix_path = library_path.join("steamapps/compatdata/976620/pfx");
+        create_dir_all(&prefix_path).unwrap();
+        create_dir_all(library_path.join("steamapps/compatdata/489830/pfx")).unwrap();
+
+        let game_path = library_path.join("steamapps/common/Enderal Special Edition");
+
+        assert_eq!(
+            Some(prefix_path),
+            find_proton_prefix(GameId::EnderalSE, &game_path)
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn find_proton_prefix_should_find_an_enderal_prefix_for_the_equivalent_skyrim_game_id() {
+        let tmp_dir = tempdir().unwrap();
+        let library_path = tmp_dir.path().join("Library");
+        write_app_manifest(&library_path, 976_620, "Enderal Special Edition");
+        let prefix_path = library_path.join("steamapps/compatdata/976620/pfx");
+        create_dir_all(&prefix_path).unwrap();
+
+        let game_path = library_path.join("steamapps/common/Enderal Special Edition");
+
+        assert_eq!(
+            Some(prefix_path),
+            find_proton_prefix(GameId::SkyrimSE, &game_path)
+        );
+        assert!(find_proton_prefix(GameId::Skyrim, &game_path).is_none());
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn find_proton_prefix_should_return_none_if_there_is_no_prefix() {
+        let tmp_dir = tempdir().unwrap();
+        let library_path = tmp_dir.path().join("Library");
+        write_app_manifest(&library_path, 489_830, "Skyrim Special Edition");
+
+        let game_path = library_path.join("steamapps/common/Skyrim Special Edition");
+
+        assert!(find_proton_prefix(GameId::SkyrimSE, &game_path).is_none());
+        assert!(find_proton_prefix(GameId::SkyrimSE, Path::new("game")).is_none());
+    }
+
+    #[test]
+    fn discover_gog_installs_should_find_games_with_gog_info_files() {
+        let tmp_dir = tempdir().unwrap();
+        let skyrim_path = tmp_dir.path().join("Skyrim Special Edition");
+        let fallout_path = tmp_dir.path().join("Fallout 3");
+        create_dir_all(&skyrim_path).unwrap();
+        create_dir_all(&fallout_path).unwrap();
+        create_dir_all(tmp_dir.path().join("Other Game")).unwrap();
+        write(skyrim_path.join("goggame-1711230643.info"), "{}").unwrap();
+        write(fallout_path.join("goggame-1454315831.info"), "{}").unwrap();
+        write(tmp_dir.path().join("Other Game/goggame-1.info"), "{}").unwrap();
+
+        let installs = discover_gog_installs(tmp_dir.path()).into_installs();
+
+        assert_eq!(
+            vec![
+                GameInstall {
+                    game_id: GameId::Fallout3,
+                    game_path: fallout_path,
+                    source: GameSource::Gog,
+                },
+                GameInstall {
+                    game_id: GameId::SkyrimSE,
+                    game_path: skyrim_path,
+                    source: GameSource::Gog,
+                },
+            ],
+            installs
+        );
+    }
+
+    #[test]
+    fn discover_gog_installs_should_check_the_given_directory_itself() {
+        let tmp_dir = tempdir().unwrap();
+        write(tmp_dir.path().join("goggame-1312824873.info"), "{}").unwrap();
+
+        let installs = discover_gog_installs(tmp_dir.path()).into_installs();
+
+        assert_eq!(1, installs.len());
+        assert_eq!(GameId::FalloutNV, installs[0].game_id());
+        assert_eq!(tmp_dir.path(), installs[0].game_path());
+    }
+
+    #[test]
+    fn discover_epic_installs_should_find_games_listed_in_manifests() {
+        let tmp_dir = tempdir().unwrap();
+        let manifests_path = tmp_dir.path().join("Manifests");
+        let game_path = tmp_dir.path().join("SkyrimSE");
+        create_dir_all(&manifests_path).unwrap();
+        create_dir_all(&game_path).unwrap();
+        write(
+            manifests_path.join("A.item"),
+            format!(
+                "{{\n\t\"FormatVersion\": 0,\n\t\"InstallLocation\": \"{}\",\n\t\"AppName\": \"ac82db5035584c7f8a2c548d98c86b2c\"\n}}",
+                escape(&game_path)
+            ),
+        )
+        .unwrap();
+        write(
+            manifests_path.join("B.item"),
+            format!(
+                "{{\"InstallLocation\": \"{}\", \"AppName\": \"Other\"}}",
+                escape(tmp_dir.path())
+            ),
+        )
+        .unwrap();
+        write(
+            manifests_path.join("C.item"),
+            format!(
+                "{{\"InstallLocation\": \"{}\", \"AppName\": \"5daeb974a22a435988892319b3a4f476\"}}",
+                escape(&tmp_dir.path().join("missing"))
+            ),
+        )
+        .unwrap();
+
+        let installs = discover_epic_installs(&manifests_path).into_installs();
+
+        assert_eq!(
+            vec![GameInstall {
+                game_id: GameId::SkyrimSE,
+                game_path,
+                source: GameSource::Epic,
+            }],
+            installs
+        );
+    }
+
+    #[test]
+    fn discover_epic_installs_should_return_an_empty_vec_if_the_directory_does_not_exist() {
+        let tmp_dir = tempdir().unwrap();
+
+        let installs = discover_epic_installs(&tmp_dir.path().join("Manifests")).into_installs();
+
+        assert!(installs.is_empty());
+    }
+
+    #[test]
+    fn discover_epic_installs_should_unescape_json_strings() {
+        let tmp_dir = tempdir().unwrap();
+        let manifests_path = tmp_dir.path().join("Manifests");
+        let game_path = tmp_dir.path().join("Skyrim SE");
+        create_dir_all(&manifests_path).unwrap();
+        create_dir_all(&game_path).unwrap();
+        write(
+            manifests_path.join("A.item"),
+            format!(
+                "{{\"AppName\": \"\\u0061c82db5035584c7f8a2c548d98c86b2c\", \"InstallLocation\": \"{}\"}}",
+                escape(&game_path)
+            ),
+        )
+        .unwrap();
+
+        let installs = discover_epic_installs(&manifests_path).into_installs();
+
+        assert_eq!(1, installs.len());
+        assert_eq!(game_path, installs[0].game_path());
+    }
+
+    #[test]
+    fn discover_epic_installs_should_skip_and_record_an_invalid_manifest() {
+        let tmp_dir = tempdir().unwrap();
+        let manifests_path = tmp_dir.path().join("Manifests");
+        let game_path = tmp_dir.path().join("SkyrimSE");
+        create_dir_all(&manifests_path).unwrap();
+        create_dir_all(&game_path).unwrap();
+        write(manifests_path.join("A.item"), "{\"AppName\": ").unwrap();
+        write(
+            manifests_path.join("B.item"),
+            format!(
+                "{{\"InstallLocation\": \"{}\", \"AppName\": \"ac82db5035584c7f8a2c548d98c86b2c\"}}",
+                escape(&game_path)
+            ),
+        )
+        .unwrap();
+
+        let discovered = discover_epic_installs(&manifests_path);
+
+        assert_eq!(1, discovered.installs().len());
+        match discovered.errors() {
+            [Error::JsonParsingError(path, _)] => {
+                assert_eq!(&manifests_path.join("A.item"), path);
+            }
+            e => panic!("Unexpected errors: {e:?}"),
+        }
+    }
+
+    #[test]
+    fn discover_steam_installs_should_skip_and_record_an_invalid_app_manifest() {
+        let tmp_dir = tempdir().unwrap();
+        let steam_root = tmp_dir.path().join("Steam");
+        write_app_manifest(&steam_root, 22330, "Oblivion");
+        write(
+            steam_root.join("steamapps/appmanifest_22320.acf"),
+            "\"AppState\"\n{",
+        )
+        .unwrap();
+
+        let discovered = discover_steam_installs(&steam_root);
+
+        assert_eq!(1, discovered.installs().len());
+        assert_eq!(GameId::Oblivion, discovered.installs()[0].game_id());
+        assert_eq!(1, discovered.errors().len());
+        match &discovered.errors()[0] {
+            Error::VdfParsingError(path, _) => {
+                assert_eq!(&steam_root.join("steamapps/appmanifest_22320.acf"), path);
+            }
+            e => panic!("Unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn discover_steam_installs_should_skip_and_record_an_invalid_library_folders_file() {
+        let tmp_dir = tempdir().unwrap();
+        let steam_root = tmp_dir.path().join("Steam");
+        write_app_manifest(&steam_root, 22330, "Oblivion");
+        write_library_folders(
+            &steam_root,
+            "config/libraryfolders.vdf",
+            "\"libraryfolders\"\n{",
+        );
+
+        let discovered = discover_steam_installs(&steam_root);
+
+        assert_eq!(1, discovered.installs().len());
+        match discovered.errors() {
+            [Error::VdfParsingError(path, _)] => {
+                assert_eq!(&steam_root.join("config/libraryfolders.vdf"), path);
+            }
+            e => panic!("Unexpected errors: {e:?}"),
+        }
+    }
+}
@@ -17,6 +17,7 @@
  * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
  */
 
+use std::collections::BTreeMap;
 use std::error;
 use std::ffi::OsString;
 use std::fmt;
@@ -25,6 +26,9 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::slice::EscapeAscii;
 
+use crate::game_settings::OBLIVION_REMASTERED_RELATIVE_DATA_PATH;
+use crate::is_enderal;
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[non_exhaustive]
 pub enum LoadOrderMethod {
@@ -35,6 +39,7 @@ pub enum LoadOrderMethod {
 }
 
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 #[non_exhaustive]
 pub enum GameId {
     Morrowind = 1,
@@ -49,15 +54,124 @@ pub enum GameId {
     Starfield,
     OpenMW,
     OblivionRemastered,
+    Enderal,
+    EnderalSE,
+}
+
+/// How confident [GameId::detect] is that a directory contains a game.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum DetectionConfidence {
+    /// The game's master file was found, but other games use the same one.
+    Low,
+    /// A file or directory that only the game uses was found, but not the
+    /// game's executable.
+    Medium,
+    /// The game's executable was found.
+    High,
 }
 
+const GAME_EXECUTABLES: &[(&str, GameId)] = &[
+    ("Morrowind.exe", GameId::Morrowind),
+    ("Oblivion.exe", GameId::Oblivion),
+    ("TESV.exe", GameId::Skyrim),
+    ("Fallout3.exe", GameId::Fallout3),
+    ("FalloutNV.exe", GameId::FalloutNV),
+    ("Fallout4.exe", GameId::Fallout4),
+    ("SkyrimSE.exe", GameId::SkyrimSE),
+    ("Fallout4VR.exe", GameId::Fallout4VR),
+    ("SkyrimVR.exe", GameId::SkyrimVR),
+    ("Starfield.exe", GameId::Starfield),
+    ("openmw", GameId::OpenMW),
+    ("openmw.exe", GameId::OpenMW),
+    ("OblivionRemastered.exe", GameId::OblivionRemastered),
+];
+
+/// Master files relative to the game path, and the games that they may
+/// belong to.
+const GAME_MASTER_FILES: &[(&str, &[GameId])] = &[
+    ("Data Files/Morrowind.esm", &[GameId::Morrowind]),
+    ("Data/Oblivion.esm", &[GameId::Oblivion]),
+    (
+        "Data/Skyrim.esm",
+        &[GameId::Skyrim, GameId::SkyrimSE, GameId::SkyrimVR],
+    ),
+    ("Data/SkyrimVR.esm", &[GameId::SkyrimVR]),
+    ("Data/Fallout3.esm", &[GameId::Fallout3]),
+    ("Data/FalloutNV.esm", &[GameId::FalloutNV]),
+    ("Data/Fallout4.esm", &[GameId::Fallout4, GameId::Fallout4VR]),
+    ("Data/Fallout4_VR.esm", &[GameId::Fallout4VR]),
+    ("Data/Starfield.esm", &[GameId::Starfield]),
+];
+
 impl GameId {
+    /// Inspect the given directory to find which games it may be the install
+    /// path of. Candidates are returned in descending order of confidence, and
+    /// the result is empty if the directory doesn't look like any supported
+    /// game's install path.
+    pub fn detect(game_path: &Path) -> Vec<(GameId, DetectionConfidence)> {
+        // Enderal and Enderal Special Edition share a launcher name, and are
+        // run using the Skyrim and Skyrim Special Edition executables
+        // respectively, so Skyrim candidates are replaced by their Enderal
+        // equivalents if the launcher is present.
+        let is_enderal = is_enderal(game_path);
+
+        let mut candidates = BTreeMap::new();
+        let mut add_candidate = |game_id: GameId, confidence: DetectionConfidence| {
+            let game_id = match game_id {
+                GameId::Skyrim if is_enderal => GameId::Enderal,
+                GameId::SkyrimSE if is_enderal => GameId::EnderalSE,
+                _ => game_id,
+            };
+            let existing = candidates.entry(game_id).or_insert(confidence);
+            *existing = confidence.max(*existing);
+        };
+
+        for (executable, game_id) in GAME_EXECUTABLES {
+            if game_path.join(executable).exists() {
+                add_candidate(*game_id, DetectionConfidence::High);
+            }
+        }
+
+        for (master_file, game_ids) in GAME_MASTER_FILES {
+            if game_path.join(master_file).exists() {
+                let confidence = if game_ids.len() == 1 {
+                    DetectionConfidence::Medium
+                } else {
+                    DetectionConfidence::Low
+                };
+
+                for game_id in *game_ids {
+                    add_candidate(*game_id, confidence);
+                }
+            }
+        }
+
+        if game_path
+            .join(OBLIVION_REMASTERED_RELATIVE_DATA_PATH)
+            .join("Oblivion.esm")
+            .exists()
+        {
+            add_candidate(GameId::OblivionRemastered, DetectionConfidence::Medium);
+        }
+
+        if is_enderal {
+            add_candidate(GameId::Enderal, DetectionConfidence::Medium);
+            add_candidate(GameId::EnderalSE, DetectionConfidence::Medium);
+        }
+
+        let mut candidates: Vec<_> = candidates.into_iter().collect();
+        candidates.sort_by_key(|(_, confidence)| std::cmp::Reverse(*confidence));
+
+        candidates
+    }
+
     pub fn to_esplugin_id(self) -> esplugin::GameId {
         match self {
             GameId::Morrowind | GameId::OpenMW => esplugin::GameId::Morrowind,
             GameId::Oblivion | GameId::OblivionRemastered => esplugin::GameId::Oblivion,
-            GameId::Skyrim => esplugin::GameId::Skyrim,
-            GameId::SkyrimSE | GameId::SkyrimVR => esplugin::GameId::SkyrimSE,
+            GameId::Skyrim | GameId::Enderal => esplugin::GameId::Skyrim,
+            GameId::SkyrimSE | GameId::SkyrimVR | GameId::EnderalSE => esplugin::GameId::SkyrimSE,
             GameId::Fallout3 => esplugin::GameId::Fallout3,
             GameId::FalloutNV => esplugin::GameId::FalloutNV,
             GameId::Fallout4 | GameId::Fallout4VR => esplugin::GameId::Fallout4,
@@ -68,7 +182,12 @@ impl GameId {
     pub fn supports_light_plugins(self) -> bool {
         matches!(
             self,
-            Self::Fallout4 | Self::Fallout4VR | Self::SkyrimSE | Self::SkyrimVR | Self::Starfield
+            Self::Fallout4
+                | Self::Fallout4VR
+                | Self::SkyrimSE
+                | Self::SkyrimVR
+                | Self::EnderalSE
+                | Self::Starfield
         )
     }
 
@@ -80,6 +199,12 @@ impl GameId {
         self != GameId::OpenMW
     }
 
+    /// Morrowind's records are identified by string IDs instead of FormIDs,
+    /// so its plugins (and OpenMW's) aren't given mod indexes.
+    pub(crate) fn has_mod_indexes(self) -> bool {
+        !matches!(self, GameId::Morrowind | GameId::OpenMW)
+    }
+
     pub(crate) fn treats_master_files_differently(self) -> bool {
         !matches!(self, GameId::OpenMW | GameId::OblivionRemastered)
     }
@@ -129,12 +254,25 @@ pub enum Error {
         message: String,
     },
     VdfParsingError(PathBuf, String),
+    JsonParsingError(PathBuf, String),
     SystemError(i32, OsString),
     InvalidBlueprintPluginPosition {
         name: String,
         pos: usize,
         expected_pos: usize,
     },
+    BackupNotFound(String),
+    FileChangedSinceLoad(PathBuf),
+    ProfileNotFound(String),
+    InvalidProfileName(String),
+    InvalidProfile(PathBuf),
+    /// The profile store's game doesn't match the game of the profile being
+    /// loaded or of the load order being saved or applied, which is given by
+    /// `game_id`.
+    ProfileGameMismatch {
+        name: String,
+        game_id: GameId,
+    },
 }
 
 #[cfg(windows)]
@@ -189,10 +327,21 @@ impl fmt::Display for Error {
             } => write!(f, "Failed to parse ini file at \"{}\", error at line {line}, column {column}: {message}", escape_ascii(path)),
             Error::VdfParsingError(path, message) =>
                 write!(f, "Failed to parse VDF file at \"{}\": {message}", escape_ascii(path)),
+            Error::JsonParsingError(path, message) =>
+                write!(f, "Failed to parse JSON file at \"{}\": {message}", escape_ascii(path)),
             Error::SystemError(code, message) =>
                 write!(f, "Error returned by the operating system, code {code}: \"{}\"", message.as_encoded_bytes().escape_ascii()),
             Error::InvalidBlueprintPluginPosition{ name, pos, expected_pos } =>
                 write!(f, "Attempted to load the blueprint plugin \"{name}\" at position {pos}, its expected position is {expected_pos}"),
+            Error::BackupNotFound(id) => write!(f, "The backup \"{id}\" could not be found"),
+            Error::FileChangedSinceLoad(path) =>
+                write!(f, "The file at \"{}\" has been changed since the load order was loaded", escape_ascii(path)),
+            Error::ProfileNotFound(name) => write!(f, "The profile \"{name}\" could not be found"),
+            Error::InvalidProfileName(name) => write!(f, "\"{name}\" is not a valid profile name"),
+            Error::InvalidProfile(path) =>
+                write!(f, "The profile at \"{}\" could not be parsed", escape_ascii(path)),
+            Error::ProfileGameMismatch { name, .. } =>
+                write!(f, "The profile \"{name}\" is not for the same game as the load order"),
         }
     }
 }
@@ -215,6 +364,128 @@ fn escape_ascii(path: &Path) -> EscapeAscii<'_> {
 mod tests {
     use super::*;
 
+    use std::fs::{create_dir_all, File};
+
+    use tempfile::tempdir;
+
+    fn create_file(path: &Path) {
+        create_dir_all(path.parent().unwrap()).unwrap();
+        File::create(path).unwrap();
+    }
+
+    #[test]
+    fn detect_should_return_an_empty_vec_if_no_game_is_found() {
+        let tmp_dir = tempdir().unwrap();
+
+        assert!(GameId::detect(tmp_dir.path()).is_empty());
+    }
+
+    #[test]
+    fn detect_should_be_highly_confident_of_a_game_with_its_executable() {
+        let tmp_dir = tempdir().unwrap();
+        create_file(&tmp_dir.path().join("SkyrimSE.exe"));
+        create_file(&tmp_dir.path().join("Data/Skyrim.esm"));
+
+        assert_eq!(
+            vec![
+                (GameId::SkyrimSE, DetectionConfidence::High),
+                (GameId::Skyrim, DetectionConfidence::Low),
+                (GameId::SkyrimVR, DetectionConfidence::Low),
+            ],
+            GameId::detect(tmp_dir.path())
+        );
+    }
+
+    #[test]
+    fn detect_should_tell_apart_fallout_4_and_fallout_4_vr_by_master_file() {
+        let tmp_dir = tempdir().unwrap();
+        create_file(&tmp_dir.path().join("Data/Fallout4.esm"));
+
+        assert_eq!(
+            vec![
+                (GameId::Fallout4, DetectionConfidence::Low),
+                (GameId::Fallout4VR, DetectionConfidence::Low),
+            ],
+            GameId::detect(tmp_dir.path())
+        );
+
+        create_file(&tmp_dir.path().join("Data/Fallout4_VR.esm"));
+
+        assert_eq!(
+            vec![
+                (GameId::Fallout4VR, DetectionConfidence::Medium),
+                (GameId::Fallout4, DetectionConfidence::Low),
+            ],
+            GameId::detect(tmp_dir.path())
+        );
+    }
+
+    #[test]
+    fn detect_should_find_morrowind_and_openmw() {
+        let tmp_dir = tempdir().unwrap();
+        create_file(&tmp_dir.path().join("Data Files/Morrowind.esm"));
+
+        assert_eq!(
+            vec![(GameId::Morrowind, DetectionConfidence::Medium)],
+            GameId::detect(tmp_dir.path())
+        );
+
+        let tmp_dir = tempdir().unwrap();
+        create_file(&tmp_dir.path().join("openmw"));
+
+        assert_eq!(
+            vec![(GameId::OpenMW, DetectionConfidence::High)],
+            GameId::detect(tmp_dir.path())
+        );
+    }
+
+    #[test]
+    fn detect_should_find_oblivion_remastered_by_its_data_layout() {
+        let tmp_dir = tempdir().unwrap();
+        create_file(
+            &tmp_dir
+                .path()
+                .join(OBLIVION_REMASTERED_RELATIVE_DATA_PATH)
+                .join("Oblivion.esm"),
+        );
+
+        assert_eq!(
+            vec![(GameId::OblivionRemastered, DetectionConfidence::Medium)],
+            GameId::detect(tmp_dir.path())
+        );
+    }
+
+    #[test]
+    fn detect_should_find_enderal_and_enderal_se_if_only_the_enderal_launcher_is_present() {
+        let tmp_dir = tempdir().unwrap();
+        create_file(&tmp_dir.path().join("Enderal Launcher.exe"));
+
+        assert_eq!(
+            vec![
+                (GameId::Enderal, DetectionConfidence::Medium),
+                (GameId::EnderalSE, DetectionConfidence::Medium),
+            ],
+            GameId::detect(tmp_dir.path())
+        );
+    }
+
+    #[test]
+    fn detect_should_find_enderal_se_instead_of_skyrim_se_if_the_enderal_launcher_is_present() {
+        let tmp_dir = tempdir().unwrap();
+        create_file(&tmp_dir.path().join("Enderal Launcher.exe"));
+        create_file(&tmp_dir.path().join("SkyrimSE.exe"));
+        create_file(&tmp_dir.path().join("Data/Skyrim.esm"));
+
+        assert_eq!(
+            vec![
+                (GameId::EnderalSE, DetectionConfidence::High),
+                (GameId::Enderal, DetectionConfidence::Medium),
+                (GameId::SkyrimVR, DetectionConfidence::Low),
+            ],
+            GameId::detect(tmp_dir.path())
+        );
+    }
+
     #[test]
     fn game_id_should_map_to_libespm_id_correctly() {
         assert_eq!(
@@ -234,6 +505,11 @@ mod tests {
             esplugin::GameId::SkyrimSE,
             GameId::SkyrimVR.to_esplugin_id()
         );
+        assert_eq!(esplugin::GameId::Skyrim, GameId::Enderal.to_esplugin_id());
+        assert_eq!(
+            esplugin::GameId::SkyrimSE,
+            GameId::EnderalSE.to_esplugin_id()
+        );
         assert_eq!(
             esplugin::GameId::Fallout3,
             GameId::Fallout3.to_esplugin_id()
@@ -264,6 +540,8 @@ mod tests {
         assert!(!GameId::Skyrim.supports_light_plugins());
         assert!(GameId::SkyrimSE.supports_light_plugins());
         assert!(GameId::SkyrimVR.supports_light_plugins());
+        assert!(!GameId::Enderal.supports_light_plugins());
+        assert!(GameId::EnderalSE.supports_light_plugins());
         assert!(!GameId::Fallout3.supports_light_plugins());
         assert!(!GameId::FalloutNV.supports_light_plugins());
         assert!(GameId::Fallout4.supports_light_plugins());
@@ -26,8 +26,12 @@ use std::path::Path;
 use std::path::PathBuf;
 use std::time::SystemTime;
 
+use crate::backup::{list_backups, Backup};
+use crate::discovery::find_gog_game_id;
+#[cfg(not(windows))]
+use crate::discovery::find_proton_prefix;
 use crate::enums::{Error, GameId, LoadOrderMethod};
-use crate::ini::{test_files, use_my_games_directory};
+use crate::ini::{test_files, test_files_ini_paths, use_my_games_directory};
 use crate::is_enderal;
 use crate::load_order::{
     AsteriskBasedLoadOrder, OpenMWLoadOrder, TextfileBasedLoadOrder, TimestampBasedLoadOrder,
@@ -48,8 +52,13 @@ pub struct GameSettings {
     early_loading_plugins: Vec<String>,
     test_files: Vec<String>,
     additional_plugins_directories: Vec<PathBuf>,
+    backup_directory: Option<PathBuf>,
+    max_backup_count: usize,
+    fail_save_if_stale: bool,
 }
 
+const DEFAULT_MAX_BACKUP_COUNT: usize = 10;
+
 const SKYRIM_HARDCODED_PLUGINS: &[&str] = &["Skyrim.esm"];
 
 const SKYRIM_SE_HARDCODED_PLUGINS: &[&str] = &[
@@ -69,6 +78,18 @@ const SKYRIM_VR_HARDCODED_PLUGINS: &[&str] = &[
     "SkyrimVR.esm",
 ];
 
+// The hardcoded plugins are compiled into the game executable, not read from
+// any file. Enderal is run by an unmodified TESV.exe and Enderal Special
+// Edition by an unmodified SkyrimSE.exe, and both install the masters that
+// those executables expect, so they hardcode the same plugins as Skyrim and
+// Skyrim Special Edition. Enderal's own master isn't hardcoded: its launcher
+// activates it, so it's handled as an implicitly active plugin instead.
+const ENDERAL_HARDCODED_PLUGINS: &[&str] = SKYRIM_HARDCODED_PLUGINS;
+
+const ENDERAL_SE_HARDCODED_PLUGINS: &[&str] = SKYRIM_SE_HARDCODED_PLUGINS;
+
+const ENDERAL_MASTER_FILE: &str = "Enderal - Forgotten Stories.esm";
+
 const FALLOUT4_HARDCODED_PLUGINS: &[&str] = &[
     "Fallout4.esm",
     "DLCRobot.esm",
@@ -113,7 +134,8 @@ const MS_FO4_VAULT_TEC_PATH: &str = "../../Fallout 4- Vault-Tec Workshop (PC)/Co
 
 const PLUGINS_TXT: &str = "Plugins.txt";
 
-const OBLIVION_REMASTERED_RELATIVE_DATA_PATH: &str = "OblivionRemastered/Content/Dev/ObvData/Data";
+pub(crate) const OBLIVION_REMASTERED_RELATIVE_DATA_PATH: &str =
+    "OblivionRemastered/Content/Dev/ObvData/Data";
 
 struct ImplicitlyActivePlugins {
     early_loading_plugins: Vec<String>,
@@ -123,6 +145,13 @@ struct ImplicitlyActivePlugins {
 
 impl GameSettings {
     pub fn new(game_id: GameId, game_path: &Path) -> Result<GameSettings, Error> {
+        // Windows games run using Proton keep their local app data and My
+        // Games folders inside the game's Proton prefix.
+        #[cfg(not(windows))]
+        if let Some(user_profile_path) = proton_user_profile_path(game_id, game_path) {
+            return GameSettings::with_wine_user_profile(game_id, game_path, &user_profile_path);
+        }
+
         let local_path = local_path(game_id, game_path)?.unwrap_or_default();
         GameSettings::with_local_path(game_id, game_path, &local_path)
     }
@@ -137,6 +166,39 @@ impl GameSettings {
         GameSettings::with_local_and_my_games_paths(game_id, game_path, local_path, my_games_path)
     }
 
+    /// Create settings for a game that is run using Wine or Proton, with its
+    /// local app data and My Games folders inside the Windows user profile of
+    /// the given Wine prefix, which is the directory that contains `drive_c`.
+    pub fn with_wine_prefix(
+        game_id: GameId,
+        game_path: &Path,
+        wine_prefix: &Path,
+    ) -> Result<GameSettings, Error> {
+        if game_id == GameId::OpenMW {
+            // OpenMW is run natively, so doesn't use a Wine prefix.
+            return GameSettings::new(game_id, game_path);
+        }
+
+        let user_profile_path = wine_user_profile_path(wine_prefix)?;
+
+        GameSettings::with_wine_user_profile(game_id, game_path, &user_profile_path)
+    }
+
+    fn with_wine_user_profile(
+        game_id: GameId,
+        game_path: &Path,
+        user_profile_path: &Path,
+    ) -> Result<GameSettings, Error> {
+        let local_path = appdata_folder_name(game_id, game_path)
+            .map(|f| user_profile_path.join("AppData").join("Local").join(f))
+            .unwrap_or_default();
+        let my_games_path = my_games_folder_name(game_id, game_path)
+            .map(|f| user_profile_path.join("Documents").join("My Games").join(f))
+            .unwrap_or_default();
+
+        GameSettings::with_local_and_my_games_paths(game_id, game_path, &local_path, my_games_path)
+    }
+
     pub(crate) fn with_local_and_my_games_paths(
         game_id: GameId,
         game_path: &Path,
@@ -172,6 +234,9 @@ impl GameSettings {
             early_loading_plugins,
             test_files,
             additional_plugins_directories,
+            backup_directory: None,
+            max_backup_count: DEFAULT_MAX_BACKUP_COUNT,
+            fail_save_if_stale: false,
         })
     }
 
@@ -189,9 +254,12 @@ impl GameSettings {
             GameId::Morrowind | GameId::Oblivion | GameId::Fallout3 | GameId::FalloutNV => {
                 LoadOrderMethod::Timestamp
             }
-            GameId::Skyrim | GameId::OblivionRemastered => LoadOrderMethod::Textfile,
+            GameId::Skyrim | GameId::Enderal | GameId::OblivionRemastered => {
+                LoadOrderMethod::Textfile
+            }
             GameId::SkyrimSE
             | GameId::SkyrimVR
+            | GameId::EnderalSE
             | GameId::Fallout4
             | GameId::Fallout4VR
             | GameId::Starfield => LoadOrderMethod::Asterisk,
@@ -213,6 +281,7 @@ impl GameSettings {
             GameId::Morrowind | GameId::OpenMW => "Morrowind.esm",
             GameId::Oblivion | GameId::OblivionRemastered => "Oblivion.esm",
             GameId::Skyrim | GameId::SkyrimSE | GameId::SkyrimVR => "Skyrim.esm",
+            GameId::Enderal | GameId::EnderalSE => ENDERAL_MASTER_FILE,
             GameId::Fallout3 => "Fallout3.esm",
             GameId::FalloutNV => "FalloutNV.esm",
             GameId::Fallout4 | GameId::Fallout4VR => "Fallout4.esm",
@@ -266,6 +335,74 @@ impl GameSettings {
         self.additional_plugins_directories = paths;
     }
 
+    pub fn backup_directory(&self) -> Option<&Path> {
+        self.backup_directory.as_deref()
+    }
+
+    /// Set the directory that the active plugins and load order files are
+    /// backed up into before they are overwritten when saving a load order.
+    /// Backups are not made if no directory is set, which is the default.
+    pub fn set_backup_directory(&mut self, path: Option<PathBuf>) {
+        self.backup_directory = path;
+    }
+
+    pub fn max_backup_count(&self) -> usize {
+        self.max_backup_count
+    }
+
+    /// Set how many backups to keep: once there are more, the oldest backups
+    /// are deleted. Zero means that no backups are deleted. Defaults to 10.
+    pub fn set_max_backup_count(&mut self, count: usize) {
+        self.max_backup_count = count;
+    }
+
+    /// Get the backups in the backup directory, sorted from oldest to newest.
+    pub fn backups(&self) -> Result<Vec<Backup>, Error> {
+        list_backups(self)
+    }
+
+    pub fn fail_save_if_stale(&self) -> bool {
+        self.fail_save_if_stale
+    }
+
+    /// Set whether saving a load order should fail with
+    /// [Error::FileChangedSinceLoad] if any of the files that it was loaded
+    /// from have been changed since it was loaded, instead of overwriting
+    /// those changes. Defaults to false.
+    pub fn set_fail_save_if_stale(&mut self, fail_save_if_stale: bool) {
+        self.fail_save_if_stale = fail_save_if_stale;
+    }
+
+    /// Get the paths of the files that the load order and active plugins are
+    /// read from, including the files that determine which plugins are
+    /// implicitly active. Paths to files that don't exist are included, as
+    /// they would be read if they did exist.
+    pub(crate) fn load_order_source_files(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut paths = vec![self.plugins_file_path.clone()];
+        paths.extend(self.load_order_path.iter().cloned());
+        paths.extend(self.early_loading_plugins_source_files()?);
+        paths.extend(self.test_files_source_files()?);
+
+        Ok(paths)
+    }
+
+    /// Get the paths of the files that early-loading plugins are read from,
+    /// other than those that are hardcoded.
+    pub(crate) fn early_loading_plugins_source_files(&self) -> Result<Vec<PathBuf>, Error> {
+        let mut paths = ccc_file_paths(self.id, &self.game_path, &self.my_games_path);
+
+        if self.id == GameId::OpenMW {
+            paths.extend(openmw_config::non_user_config_file_paths(&self.game_path)?);
+        }
+
+        Ok(paths)
+    }
+
+    /// Get the paths of the ini files that test files are read from.
+    pub(crate) fn test_files_source_files(&self) -> Result<Vec<PathBuf>, Error> {
+        test_files_ini_paths(self.id, &self.game_path, &self.my_games_path)
+    }
+
     /// Find installed plugins and return them in their "inactive load order",
     /// which is generally the order in which the game launcher would display
     /// them if they were all inactive, ignoring rules like master files
@@ -387,6 +524,47 @@ fn local_path(game_id: GameId, game_path: &Path) -> Result<Option<PathBuf>, Erro
     }
 }
 
+/// Get the path of the Windows user profile folder in the Proton prefix of the
+/// Steam install of the game at the given path, if the game has a local app
+/// data or My Games folder to find in it. Returns None if there is no prefix
+/// or it has no user profile, so that the folders are found as they would be
+/// for a native install.
+#[cfg(not(windows))]
+fn proton_user_profile_path(game_id: GameId, game_path: &Path) -> Option<PathBuf> {
+    if game_id == GameId::OpenMW
+        || (appdata_folder_name(game_id, game_path).is_none()
+            && my_games_folder_name(game_id, game_path).is_none())
+    {
+        return None;
+    }
+
+    find_proton_prefix(game_id, game_path).and_then(|p| wine_user_profile_path(&p).ok())
+}
+
+/// Get the path of the Windows user profile folder in the given Wine prefix.
+/// Proton always uses a user named steamuser, while Wine uses the name of the
+/// Linux user that created the prefix.
+fn wine_user_profile_path(wine_prefix: &Path) -> Result<PathBuf, Error> {
+    let users_path = wine_prefix.join("drive_c").join("users");
+
+    let steam_user_path = users_path.join("steamuser");
+    if steam_user_path.is_dir() {
+        return Ok(steam_user_path);
+    }
+
+    let mut user_paths: Vec<_> = read_dir(&users_path)
+        .map_err(|e| Error::IoError(users_path.clone(), e))?
+        .flatten()
+        .filter(|e| e.file_type().is_ok_and(|t| t.is_dir()))
+        .filter(|e| e.file_name() != "Public")
+        .map(|e| e.path())
+        .collect();
+
+    user_paths.sort();
+
+    user_paths.into_iter().next().ok_or(Error::NoLocalAppData)
+}
+
 // The local path can vary depending on where the game was bought from.
 fn appdata_folder_name(game_id: GameId, game_path: &Path) -> Option<&'static str> {
     match game_id {
@@ -395,6 +573,8 @@ fn appdata_folder_name(game_id: GameId, game_path: &Path) -> Option<&'static str
         GameId::Skyrim => Some(skyrim_appdata_folder_name(game_path)),
         GameId::SkyrimSE => Some(skyrim_se_appdata_folder_name(game_path)),
         GameId::SkyrimVR => Some("Skyrim VR"),
+        GameId::Enderal => Some("enderal"),
+        GameId::EnderalSE => Some(enderal_se_appdata_folder_name(game_path)),
         GameId::Fallout3 => Some("Fallout3"),
         GameId::FalloutNV => Some(falloutnv_appdata_folder_name(game_path)),
         GameId::Fallout4 => Some(fallout4_appdata_folder_name(game_path)),
@@ -413,19 +593,12 @@ fn skyrim_appdata_folder_name(game_path: &Path) -> &'static str {
 }
 
 fn skyrim_se_appdata_folder_name(game_path: &Path) -> &'static str {
-    let is_gog_install = game_path.join("Galaxy64.dll").exists();
-
     if is_enderal(game_path) {
-        if is_gog_install {
-            "Enderal Special Edition GOG"
-        } else {
-            "Enderal Special Edition"
-        }
-    } else if is_gog_install {
-        // Galaxy64.dll is only installed by GOG's installer.
+        // It's not actually Skyrim Special Edition, it's Enderal Special Edition.
+        enderal_se_appdata_folder_name(game_path)
+    } else if is_gog_install(GameId::SkyrimSE, game_path) {
         "Skyrim Special Edition GOG"
-    } else if game_path.join("EOSSDK-Win64-Shipping.dll").exists() {
-        // EOSSDK-Win64-Shipping.dll is only installed by Epic.
+    } else if is_epic_install(GameId::SkyrimSE, game_path) {
         "Skyrim Special Edition EPIC"
     } else if is_microsoft_store_install(GameId::SkyrimSE, game_path) {
         "Skyrim Special Edition MS"
@@ -435,9 +608,16 @@ fn skyrim_se_appdata_folder_name(game_path: &Path) -> &'static str {
     }
 }
 
+fn enderal_se_appdata_folder_name(game_path: &Path) -> &'static str {
+    if is_gog_install(GameId::EnderalSE, game_path) {
+        "Enderal Special Edition GOG"
+    } else {
+        "Enderal Special Edition"
+    }
+}
+
 fn falloutnv_appdata_folder_name(game_path: &Path) -> &'static str {
-    if game_path.join("EOSSDK-Win32-Shipping.dll").exists() {
-        // EOSSDK-Win32-Shipping.dll is only installed by Epic.
+    if is_epic_install(GameId::FalloutNV, game_path) {
         "FalloutNV_Epic"
     } else {
         "FalloutNV"
@@ -447,8 +627,7 @@ fn falloutnv_appdata_folder_name(game_path: &Path) -> &'static str {
 fn fallout4_appdata_folder_name(game_path: &Path) -> &'static str {
     if is_microsoft_store_install(GameId::Fallout4, game_path) {
         "Fallout4 MS"
-    } else if game_path.join("EOSSDK-Win64-Shipping.dll").exists() {
-        // EOSSDK-Win64-Shipping.dll is only installed by Epic.
+    } else if is_epic_install(GameId::Fallout4, game_path) {
         "Fallout4 EPIC"
     } else {
         "Fallout4"
@@ -480,6 +659,7 @@ fn my_games_folder_name(game_id: GameId, game_path: &Path) -> Option<&'static st
         GameId::OpenMW => Some("OpenMW"),
         GameId::OblivionRemastered => Some("Oblivion Remastered"),
         GameId::Skyrim => Some(skyrim_my_games_folder_name(game_path)),
+        GameId::Enderal => Some("Enderal"),
         // For all other games the name is the same as the AppData\Local folder name.
         _ => appdata_folder_name(game_id, game_path),
     }
@@ -507,6 +687,20 @@ fn is_microsoft_store_install(game_id: GameId, game_path: &Path) -> bool {
     }
 }
 
+fn is_gog_install(game_id: GameId, game_path: &Path) -> bool {
+    // Galaxy64.dll is only installed by GOG's installer.
+    game_path.join("Galaxy64.dll").exists() || find_gog_game_id(game_path) == Some(game_id)
+}
+
+fn is_epic_install(game_id: GameId, game_path: &Path) -> bool {
+    // The Epic Online Services SDK DLL is only installed by Epic.
+    match game_id {
+        GameId::FalloutNV => game_path.join("EOSSDK-Win32-Shipping.dll").exists(),
+        GameId::SkyrimSE | GameId::Fallout4 => game_path.join("EOSSDK-Win64-Shipping.dll").exists(),
+        _ => false,
+    }
+}
+
 #[cfg(windows)]
 fn documents_path(_local_path: &Path) -> Option<PathBuf> {
     dirs::document_dir()
@@ -580,7 +774,7 @@ fn load_order_path(
 ) -> Option<PathBuf> {
     const LOADORDER_TXT: &str = "loadorder.txt";
     match game_id {
-        GameId::Skyrim => Some(local_path.join(LOADORDER_TXT)),
+        GameId::Skyrim | GameId::Enderal => Some(local_path.join(LOADORDER_TXT)),
         GameId::OblivionRemastered => plugins_file_path.parent().map(|p| p.join(LOADORDER_TXT)),
         _ => None,
     }
@@ -620,7 +814,7 @@ fn oblivion_plugins_file_path(game_path: &Path, local_path: &Path) -> Result<Pat
 fn ccc_file_paths(game_id: GameId, game_path: &Path, my_games_path: &Path) -> Vec<PathBuf> {
     match game_id {
         GameId::Fallout4 => vec![game_path.join("Fallout4.ccc")],
-        GameId::SkyrimSE => vec![game_path.join("Skyrim.ccc")],
+        GameId::SkyrimSE | GameId::EnderalSE => vec![game_path.join("Skyrim.ccc")],
         // If the My Games CCC file is present, it overrides the other, even if empty.
         GameId::Starfield => vec![
             my_games_path.join("Starfield.ccc"),
@@ -635,6 +829,8 @@ fn hardcoded_plugins(game_id: GameId) -> &'static [&'static str] {
         GameId::Skyrim => SKYRIM_HARDCODED_PLUGINS,
         GameId::SkyrimSE => SKYRIM_SE_HARDCODED_PLUGINS,
         GameId::SkyrimVR => SKYRIM_VR_HARDCODED_PLUGINS,
+        GameId::Enderal => ENDERAL_HARDCODED_PLUGINS,
+        GameId::EnderalSE => ENDERAL_SE_HARDCODED_PLUGINS,
         GameId::Fallout4 => FALLOUT4_HARDCODED_PLUGINS,
         GameId::Fallout4VR => FALLOUT4VR_HARDCODED_PLUGINS,
         GameId::Starfield => STARFIELD_HARDCODED_PLUGINS,
@@ -767,12 +963,18 @@ fn implicitly_active_plugins(
         let nam_plugins = find_nam_plugins(&game_path.join("Data"))?;
 
         plugin_names.extend(nam_plugins);
-    } else if game_id == GameId::Skyrim {
+    } else if matches!(game_id, GameId::Skyrim | GameId::Enderal) {
         // Update.esm is always active, but loads after all other masters if it is not made to load
         // earlier (e.g. by listing in plugins.txt or by being a master of another master).
         plugin_names.push("Update.esm".to_owned());
     }
 
+    if matches!(game_id, GameId::Enderal | GameId::EnderalSE) {
+        // Enderal's launcher always activates its main master file, as the game can't run without
+        // it, but like Update.esm it doesn't load early.
+        plugin_names.push(ENDERAL_MASTER_FILE.to_owned());
+    }
+
     deduplicate(&mut plugin_names);
 
     Ok(plugin_names)
@@ -1050,6 +1252,144 @@ mod tests {
         assert_eq!(config_path, settings.my_games_path);
     }
 
+    #[test]
+    #[cfg(not(windows))]
+    fn new_should_use_the_proton_prefix_of_a_steam_install_on_linux() {
+        let tmp_dir = tempdir().unwrap();
+        let steamapps_path = tmp_dir.path().join("steamapps");
+        let game_path = steamapps_path.join("common/Skyrim Special Edition");
+        let user_profile_path =
+            steamapps_path.join("compatdata/489830/pfx/drive_c/users/steamuser");
+        create_dir_all(&game_path).unwrap();
+        create_dir_all(&user_profile_path).unwrap();
+        std::fs::write(
+            steamapps_path.join("appmanifest_489830.acf"),
+            "\"AppState\"\n{\n\t\"installdir\"\t\t\"Skyrim Special Edition\"\n}\n",
+        )
+        .unwrap();
+
+        let settings = GameSettings::new(GameId::SkyrimSE, &game_path).unwrap();
+
+        assert_eq!(
+            &user_profile_path.join("AppData/Local/Skyrim Special Edition/Plugins.txt"),
+            settings.active_plugins_file()
+        );
+        assert_eq!(
+            user_profile_path.join("Documents/My Games/Skyrim Special Edition"),
+            settings.my_games_path
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn new_should_ignore_a_proton_prefix_with_no_user_profile_on_linux() {
+        let tmp_dir = tempdir().unwrap();
+        let steamapps_path = tmp_dir.path().join("steamapps");
+        let game_path = steamapps_path.join("common/Morrowind");
+        create_dir_all(&game_path).unwrap();
+        create_dir_all(steamapps_path.join("compatdata/22320/pfx/drive_c/users")).unwrap();
+        std::fs::write(
+            steamapps_path.join("appmanifest_22320.acf"),
+            "\"AppState\"\n{\n\t\"installdir\"\t\t\"Morrowind\"\n}\n",
+        )
+        .unwrap();
+
+        let settings = GameSettings::new(GameId::Morrowind, &game_path).unwrap();
+
+        assert_eq!(
+            &game_path.join("Morrowind.ini"),
+            settings.active_plugins_file()
+        );
+        assert_eq!(PathBuf::new(), settings.my_games_path);
+    }
+
+    #[test]
+    fn with_wine_prefix_should_use_paths_in_the_prefix_user_profile() {
+        let tmp_dir = tempdir().unwrap();
+        let prefix_path = tmp_dir.path().join("pfx");
+        let user_profile_path = prefix_path.join("drive_c/users/steamuser");
+        create_dir_all(&user_profile_path).unwrap();
+        create_dir_all(prefix_path.join("drive_c/users/Public")).unwrap();
+
+        let settings = GameSettings::with_wine_prefix(
+            GameId::Starfield,
+            &tmp_dir.path().join("game"),
+            &prefix_path,
+        )
+        .unwrap();
+
+        assert_eq!(
+            &user_profile_path.join("AppData/Local/Starfield/Plugins.txt"),
+            settings.active_plugins_file()
+        );
+        assert_eq!(
+            user_profile_path.join("Documents/My Games/Starfield"),
+            settings.my_games_path
+        );
+        assert_eq!(
+            &[user_profile_path.join("Documents/My Games/Starfield/Data")],
+            settings.additional_plugins_directories()
+        );
+    }
+
+    #[test]
+    fn with_wine_prefix_should_use_the_first_non_public_user_if_there_is_no_steamuser() {
+        let tmp_dir = tempdir().unwrap();
+        let prefix_path = tmp_dir.path().join("pfx");
+        create_dir_all(prefix_path.join("drive_c/users/Public")).unwrap();
+        create_dir_all(prefix_path.join("drive_c/users/user")).unwrap();
+
+        let settings = GameSettings::with_wine_prefix(
+            GameId::Oblivion,
+            &tmp_dir.path().join("game"),
+            &prefix_path,
+        )
+        .unwrap();
+
+        assert_eq!(
+            &prefix_path.join("drive_c/users/user/AppData/Local/Oblivion/Plugins.txt"),
+            settings.active_plugins_file()
+        );
+    }
+
+    #[test]
+    fn with_wine_prefix_should_use_an_empty_local_path_for_games_without_one() {
+        let tmp_dir = tempdir().unwrap();
+        let prefix_path = tmp_dir.path().join("pfx");
+        let user_profile_path = prefix_path.join("drive_c/users/steamuser");
+        create_dir_all(&user_profile_path).unwrap();
+
+        let settings = GameSettings::with_wine_prefix(
+            GameId::OblivionRemastered,
+            &tmp_dir.path().join("game"),
+            &prefix_path,
+        )
+        .unwrap();
+
+        assert_eq!(
+            user_profile_path.join("Documents/My Games/Oblivion Remastered"),
+            settings.my_games_path
+        );
+    }
+
+    #[test]
+    fn with_wine_prefix_should_error_if_the_prefix_has_no_user_profile() {
+        let tmp_dir = tempdir().unwrap();
+        let prefix_path = tmp_dir.path().join("pfx");
+        create_dir_all(prefix_path.join("drive_c/users/Public")).unwrap();
+
+        match GameSettings::with_wine_prefix(
+            GameId::Skyrim,
+            &tmp_dir.path().join("game"),
+            &prefix_path,
+        )
+        .unwrap_err()
+        {
+            Error::NoLocalAppData => {}
+            e => panic!("Unexpected error: {e}"),
+        }
+    }
+
     #[test]
     fn id_should_be_the_id_the_struct_was_created_with() {
         let settings = game_with_generic_paths(GameId::Morrowind);
@@ -1116,6 +1456,15 @@ mod tests {
         assert_eq!(LoadOrderMethod::Textfile, settings.load_order_method());
     }
 
+    #[test]
+    fn load_order_method_should_be_textfile_for_enderal_and_asterisk_for_enderal_se() {
+        let mut settings = game_with_generic_paths(GameId::Enderal);
+        assert_eq!(LoadOrderMethod::Textfile, settings.load_order_method());
+
+        settings = game_with_generic_paths(GameId::EnderalSE);
+        assert_eq!(LoadOrderMethod::Asterisk, settings.load_order_method());
+    }
+
     #[test]
     fn load_order_method_should_be_asterisk_for_tes5se_tes5vr_fo4_fo4vr_and_starfield() {
         let mut settings = game_with_generic_paths(GameId::SkyrimSE);
@@ -1162,6 +1511,12 @@ mod tests {
         settings = game_with_generic_paths(GameId::SkyrimVR);
         assert_eq!("Skyrim.esm", settings.master_file());
 
+        settings = game_with_generic_paths(GameId::Enderal);
+        assert_eq!("Enderal - Forgotten Stories.esm", settings.master_file());
+
+        settings = game_with_generic_paths(GameId::EnderalSE);
+        assert_eq!("Enderal - Forgotten Stories.esm", settings.master_file());
+
         settings = game_with_generic_paths(GameId::Fallout3);
         assert_eq!("Fallout3.esm", settings.master_file());
 
@@ -1199,6 +1554,12 @@ mod tests {
         folder = appdata_folder_name(GameId::SkyrimVR, game_path).unwrap();
         assert_eq!("Skyrim VR", folder);
 
+        folder = appdata_folder_name(GameId::Enderal, game_path).unwrap();
+        assert_eq!("enderal", folder);
+
+        folder = appdata_folder_name(GameId::EnderalSE, game_path).unwrap();
+        assert_eq!("Enderal Special Edition", folder);
+
         folder = appdata_folder_name(GameId::Fallout3, game_path).unwrap();
         assert_eq!("Fallout3", folder);
 
@@ -1230,6 +1591,46 @@ mod tests {
         assert_eq!("Skyrim Special Edition GOG", folder);
     }
 
+    #[test]
+    fn appdata_folder_name_for_enderal_se_should_have_gog_suffix_if_a_gog_info_file_is_in_game_path(
+    ) {
+        let tmp_dir = tempdir().unwrap();
+        let game_path = tmp_dir.path();
+
+        File::create(game_path.join("goggame-1708684988.info")).unwrap();
+
+        let mut folder = appdata_folder_name(GameId::EnderalSE, game_path).unwrap();
+        assert_eq!("Enderal Special Edition GOG", folder);
+
+        File::create(game_path.join("Enderal Launcher.exe")).unwrap();
+
+        folder = appdata_folder_name(GameId::SkyrimSE, game_path).unwrap();
+        assert_eq!("Enderal Special Edition GOG", folder);
+    }
+
+    #[test]
+    fn appdata_folder_name_for_skyrim_se_should_have_gog_suffix_if_a_gog_info_file_is_in_game_path()
+    {
+        let tmp_dir = tempdir().unwrap();
+        let game_path = tmp_dir.path();
+
+        File::create(game_path.join("goggame-1711230643.info")).unwrap();
+
+        let folder = appdata_folder_name(GameId::SkyrimSE, game_path).unwrap();
+        assert_eq!("Skyrim Special Edition GOG", folder);
+    }
+
+    #[test]
+    fn appdata_folder_name_for_skyrim_se_should_ignore_gog_info_files_for_other_games() {
+        let tmp_dir = tempdir().unwrap();
+        let game_path = tmp_dir.path();
+
+        File::create(game_path.join("goggame-1454587428.info")).unwrap();
+
+        let folder = appdata_folder_name(GameId::SkyrimSE, game_path).unwrap();
+        assert_eq!("Skyrim Special Edition", folder);
+    }
+
     #[test]
     fn appdata_folder_name_for_skyrim_se_should_have_epic_suffix_if_eossdk_dll_is_in_game_path() {
         let tmp_dir = tempdir().unwrap();
@@ -1407,6 +1808,16 @@ mod tests {
             .unwrap();
         assert_eq!(parent_path.join("Skyrim VR"), path);
 
+        let path = my_games_path(GameId::Enderal, empty_path, local_path)
+            .unwrap()
+            .unwrap();
+        assert_eq!(parent_path.join("Enderal"), path);
+
+        let path = my_games_path(GameId::EnderalSE, empty_path, local_path)
+            .unwrap()
+            .unwrap();
+        assert_eq!(parent_path.join("Enderal Special Edition"), path);
+
         let path = my_games_path(GameId::Fallout3, empty_path, local_path)
             .unwrap()
             .unwrap();
@@ -1654,6 +2065,20 @@ mod tests {
         ];
         assert_eq!(plugins, settings.early_loading_plugins());
 
+        settings = game_with_generic_paths(GameId::Enderal);
+        plugins = vec!["Skyrim.esm"];
+        assert_eq!(plugins, settings.early_loading_plugins());
+
+        settings = game_with_generic_paths(GameId::EnderalSE);
+        plugins = vec![
+            "Skyrim.esm",
+            "Update.esm",
+            "Dawnguard.esm",
+            "HearthFires.esm",
+            "Dragonborn.esm",
+        ];
+        assert_eq!(plugins, settings.early_loading_plugins());
+
         settings = game_with_generic_paths(GameId::Fallout4);
         plugins = vec![
             "Fallout4.esm",
@@ -2116,6 +2541,39 @@ mod tests {
         assert!(plugins.contains(&"Update.esm".to_owned()));
     }
 
+    #[test]
+    fn implicitly_active_plugins_should_include_update_esm_and_the_enderal_master_for_enderal() {
+        let settings = game_with_generic_paths(GameId::Enderal);
+
+        assert_eq!(
+            vec![
+                "Skyrim.esm",
+                "Update.esm",
+                "Enderal - Forgotten Stories.esm"
+            ],
+            settings.implicitly_active_plugins()
+        );
+        assert!(!settings.loads_early("Enderal - Forgotten Stories.esm"));
+    }
+
+    #[test]
+    fn implicitly_active_plugins_should_include_the_enderal_master_for_enderal_se() {
+        let settings = game_with_generic_paths(GameId::EnderalSE);
+
+        assert_eq!(
+            vec![
+                "Skyrim.esm",
+                "Update.esm",
+                "Dawnguard.esm",
+                "HearthFires.esm",
+                "Dragonborn.esm",
+                "Enderal - Forgotten Stories.esm",
+            ],
+            settings.implicitly_active_plugins()
+        );
+        assert!(!settings.loads_early("Enderal - Forgotten Stories.esm"));
+    }
+
     #[test]
     fn implicitly_active_plugins_should_include_plugins_with_nam_files_for_games_other_than_fallout_nv(
     ) {
@@ -2192,9 +2650,18 @@ mod tests {
             settings.load_order_file().unwrap()
         );
 
+        settings = game_with_generic_paths(GameId::Enderal);
+        assert_eq!(
+            Path::new("local/loadorder.txt"),
+            settings.load_order_file().unwrap()
+        );
+
         settings = game_with_generic_paths(GameId::SkyrimSE);
         assert!(settings.load_order_file().is_none());
 
+        settings = game_with_generic_paths(GameId::EnderalSE);
+        assert!(settings.load_order_file().is_none());
+
         settings = game_with_generic_paths(GameId::OpenMW);
         assert!(settings.load_order_file().is_none());
 
@@ -2463,6 +2930,35 @@ mod tests {
         assert_eq!(other_dir.join(plugin_name_3), plugin_3_path);
     }
 
+    #[test]
+    fn load_order_source_files_should_include_plugins_ccc_and_ini_files() {
+        let settings = game_with_generic_paths(GameId::Fallout4);
+
+        assert_eq!(
+            vec![
+                Path::new("local").join("Plugins.txt"),
+                Path::new("game").join("Fallout4.ccc"),
+                Path::new("my games").join("Fallout4.ini"),
+                Path::new("my games").join("Fallout4Custom.ini"),
+            ],
+            settings.load_order_source_files().unwrap()
+        );
+    }
+
+    #[test]
+    fn load_order_source_files_should_include_the_load_order_file_if_there_is_one() {
+        let settings = game_with_generic_paths(GameId::Skyrim);
+
+        assert_eq!(
+            vec![
+                Path::new("local").join("Plugins.txt"),
+                Path::new("local").join("loadorder.txt"),
+                Path::new("my games").join("Skyrim.ini"),
+            ],
+            settings.load_order_source_files().unwrap()
+        );
+    }
+
     #[test]
     fn refresh_implicitly_active_plugins_should_update_early_loading_and_implicitly_active_plugins()
     {
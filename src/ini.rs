@@ -17,7 +17,7 @@
  * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use encoding_rs::WINDOWS_1252;
 
@@ -25,14 +25,19 @@ use crate::{Error, GameId};
 
 type TestFiles = [Option<String>; 10];
 
+pub(crate) const GAME_FILES_HEADER: &[u8] = b"[Game Files]";
+
 fn read_ini(ini_path: &Path) -> Result<ini::Ini, Error> {
-    // Read ini as Windows-1252 bytes and then convert to UTF-8 before parsing,
-    // as the ini crate expects the content to be valid UTF-8.
     let contents =
         std::fs::read(ini_path).map_err(|e| Error::IoError(ini_path.to_path_buf(), e))?;
 
-    // My Games is used if bUseMyGamesDirectory is not present or set to 1.
-    let contents = WINDOWS_1252.decode_without_bom_handling(&contents).0;
+    parse_ini(ini_path, &contents)
+}
+
+fn parse_ini(ini_path: &Path, contents: &[u8]) -> Result<ini::Ini, Error> {
+    // Read ini as Windows-1252 bytes and then convert to UTF-8 before parsing,
+    // as the ini crate expects the content to be valid UTF-8.
+    let contents = WINDOWS_1252.decode_without_bom_handling(contents).0;
 
     ini::Ini::load_from_str_opt(
         &contents,
@@ -60,8 +65,13 @@ pub(crate) fn use_my_games_directory(ini_path: &Path) -> Result<bool, Error> {
     }
 }
 
-pub(crate) fn read_morrowind_active_plugins(ini_path: &Path) -> Result<Vec<String>, Error> {
-    let ini = read_ini(ini_path)?;
+/// Get the active plugins listed in the given Morrowind.ini contents, which
+/// were read from the given path.
+pub(crate) fn parse_morrowind_active_plugins(
+    ini_path: &Path,
+    contents: &[u8],
+) -> Result<Vec<String>, Error> {
+    let ini = parse_ini(ini_path, contents)?;
 
     let mut plugins = Vec::new();
     if let Some(section) = ini.section(Some("Game Files")) {
@@ -81,6 +91,57 @@ pub(crate) fn read_morrowind_active_plugins(ini_path: &Path) -> Result<Vec<Strin
     Ok(plugins)
 }
 
+/// Get the given Morrowind.ini contents with their [Game Files] section
+/// replaced by the [Game Files] section in the other given contents, leaving
+/// the rest of the ini untouched. If the other contents have no such section,
+/// the section is removed, and if the given contents have no such section, it's
+/// appended.
+pub(crate) fn with_morrowind_game_files_section(
+    ini_contents: &[u8],
+    source_ini_contents: &[u8],
+) -> Vec<u8> {
+    let lines: Vec<_> = ini_contents.split_inclusive(|b| *b == b'\n').collect();
+    let source_lines: Vec<_> = source_ini_contents
+        .split_inclusive(|b| *b == b'\n')
+        .collect();
+
+    let (start, end) = game_files_section_bounds(&lines).unwrap_or((lines.len(), lines.len()));
+    let section = game_files_section_bounds(&source_lines)
+        .map(|(start, end)| source_lines.iter().take(end).skip(start))
+        .into_iter()
+        .flatten();
+
+    let mut contents = Vec::new();
+    for line in lines
+        .iter()
+        .take(start)
+        .chain(section)
+        .chain(lines.iter().skip(end))
+    {
+        if !contents.is_empty() && !contents.ends_with(b"\n") {
+            contents.push(b'\n');
+        }
+        contents.extend_from_slice(line);
+    }
+
+    contents
+}
+
+/// Get the indices of the first line of the [Game Files] section and of the
+/// first line after it.
+fn game_files_section_bounds(lines: &[&[u8]]) -> Option<(usize, usize)> {
+    let start = lines
+        .iter()
+        .position(|l| l.starts_with(GAME_FILES_HEADER))?;
+    let end = lines
+        .iter()
+        .skip(start + 1)
+        .position(|l| l.starts_with(b"["))
+        .map_or(lines.len(), |i| start + 1 + i);
+
+    Some((start, end))
+}
+
 fn read_test_files(ini_path: &Path) -> Result<TestFiles, Error> {
     if !ini_path.exists() {
         return Ok(TestFiles::default());
@@ -170,6 +231,10 @@ pub(crate) fn test_files(
             let test_files = read_test_files(&my_games_path.join(filename))?;
             Ok(filter_test_files(test_files))
         }
+        GameId::Enderal | GameId::EnderalSE => {
+            let test_files = read_test_files(&my_games_path.join("Enderal.ini"))?;
+            Ok(filter_test_files(test_files))
+        }
         GameId::SkyrimVR => {
             let test_files = read_test_files(&my_games_path.join("SkyrimVR.ini"))?;
             Ok(filter_test_files(test_files))
@@ -209,6 +274,54 @@ pub(crate) fn test_files(
     }
 }
 
+/// Get the paths of the ini files that [test_files] may read for the given
+/// game, including any that don't currently exist.
+pub(crate) fn test_files_ini_paths(
+    game_id: GameId,
+    game_path: &Path,
+    my_games_path: &Path,
+) -> Result<Vec<PathBuf>, Error> {
+    let paths = match game_id {
+        GameId::Morrowind | GameId::OpenMW | GameId::OblivionRemastered => Vec::new(),
+        GameId::Oblivion => vec![
+            game_path.join("Oblivion.ini"),
+            my_games_path.join("Oblivion.ini"),
+        ],
+        GameId::Skyrim | GameId::SkyrimSE => {
+            let filename = if crate::is_enderal(game_path) {
+                "Enderal.ini"
+            } else {
+                "Skyrim.ini"
+            };
+
+            vec![my_games_path.join(filename)]
+        }
+        GameId::Enderal | GameId::EnderalSE => vec![my_games_path.join("Enderal.ini")],
+        GameId::SkyrimVR => vec![my_games_path.join("SkyrimVR.ini")],
+        GameId::Fallout3 => vec![my_games_path.join("FALLOUT.INI")],
+        GameId::FalloutNV => vec![my_games_path.join("Fallout.ini")],
+        GameId::Fallout4 => vec![
+            my_games_path.join("Fallout4.ini"),
+            my_games_path.join("Fallout4Custom.ini"),
+        ],
+        GameId::Fallout4VR => vec![
+            my_games_path.join("Fallout4VR.ini"),
+            my_games_path.join("Fallout4VRCustom.ini"),
+        ],
+        GameId::Starfield => {
+            let language = starfield_language(game_path)?;
+
+            vec![
+                game_path.join("Starfield.ini"),
+                my_games_path.join("StarfieldCustom.ini"),
+                my_games_path.join(format!("Starfield_{language}.INI")),
+            ]
+        }
+    };
+
+    Ok(paths)
+}
+
 fn starfield_language(game_path: &Path) -> Result<&'static str, Error> {
     let steam_acf_path = game_path.join("../../appmanifest_1716740.acf");
 
@@ -228,20 +341,7 @@ fn read_steam_language_config(appmanifest_acf_path: &Path) -> Result<Option<Stri
         .map_err(|e| Error::IoError(appmanifest_acf_path.to_path_buf(), e))?;
 
     let language = keyvalues_parser::Vdf::parse(&content)
-        .map_err(|e| {
-            let detail = match e {
-                keyvalues_parser::error::Error::EscapedParseError(e) => e.to_string(),
-
-                keyvalues_parser::error::Error::RawParseError(e) => e.to_string(),
-
-                keyvalues_parser::error::Error::RenderError(e) => e.to_string(),
-                keyvalues_parser::error::Error::RawRenderError { invalid_char } => {
-                    format!("Invalid character \"{invalid_char}\"")
-                }
-            };
-
-            Error::VdfParsingError(appmanifest_acf_path.to_path_buf(), detail)
-        })?
+        .map_err(|e| to_vdf_parsing_error(appmanifest_acf_path, e))?
         .value
         .get_obj()
         .and_then(|o| o.get("UserConfig"))
@@ -255,6 +355,21 @@ fn read_steam_language_config(appmanifest_acf_path: &Path) -> Result<Option<Stri
     Ok(language)
 }
 
+pub(crate) fn to_vdf_parsing_error(path: &Path, error: keyvalues_parser::error::Error) -> Error {
+    let detail = match error {
+        keyvalues_parser::error::Error::EscapedParseError(e) => e.to_string(),
+
+        keyvalues_parser::error::Error::RawParseError(e) => e.to_string(),
+
+        keyvalues_parser::error::Error::RenderError(e) => e.to_string(),
+        keyvalues_parser::error::Error::RawRenderError { invalid_char } => {
+            format!("Invalid character \"{invalid_char}\"")
+        }
+    };
+
+    Error::VdfParsingError(path.to_path_buf(), detail)
+}
+
 fn map_steam_language<T: AsRef<str>>(steam_language: T) -> &'static str {
     match steam_language.as_ref() {
         "german" => "de",
@@ -380,8 +495,6 @@ fn map_windows_system_language<T: AsRef<str>>(system_language: T) -> &'static st
 
 #[cfg(test)]
 mod tests {
-    use std::path::PathBuf;
-
     use tempfile::tempdir;
 
     use super::*;
@@ -815,7 +928,7 @@ mod tests {
     }
 
     #[test]
-    fn read_morrowind_active_plugins_should_return_valid_lines() {
+    fn parse_morrowind_active_plugins_should_return_valid_lines() {
         let tmp_dir = tempdir().unwrap();
         let ini_path = tmp_dir.path().join("ini.ini");
 
@@ -835,7 +948,8 @@ mod tests {
         )
         .unwrap();
 
-        let plugins = read_morrowind_active_plugins(&ini_path).unwrap();
+        let contents = std::fs::read(&ini_path).unwrap();
+        let plugins = parse_morrowind_active_plugins(&ini_path, &contents).unwrap();
 
         assert_eq!(
             vec![
@@ -848,6 +962,43 @@ mod tests {
         );
     }
 
+    #[test]
+    fn with_morrowind_game_files_section_should_only_replace_the_game_files_section() {
+        let contents = with_morrowind_game_files_section(
+            b"[General]\na=1\n[Game Files]\nGameFile0=a.esm\n[Other]\nb=2\n",
+            b"[General]\na=3\n[Game Files]\nGameFile0=b.esm\nGameFile1=c.esp\n",
+        );
+
+        assert_eq!(
+            b"[General]\na=1\n[Game Files]\nGameFile0=b.esm\nGameFile1=c.esp\n[Other]\nb=2\n"
+                .as_slice(),
+            contents
+        );
+    }
+
+    #[test]
+    fn with_morrowind_game_files_section_should_append_the_section_if_it_is_missing() {
+        let contents = with_morrowind_game_files_section(
+            b"[General]\na=1",
+            b"[Game Files]\nGameFile0=b.esm\n",
+        );
+
+        assert_eq!(
+            b"[General]\na=1\n[Game Files]\nGameFile0=b.esm\n".as_slice(),
+            contents
+        );
+    }
+
+    #[test]
+    fn with_morrowind_game_files_section_should_remove_the_section_if_the_source_has_none() {
+        let contents = with_morrowind_game_files_section(
+            b"[General]\na=1\n[Game Files]\nGameFile0=a.esm\n",
+            b"[General]\na=3\n",
+        );
+
+        assert_eq!(b"[General]\na=1\n".as_slice(), contents);
+    }
+
     #[test]
     fn read_test_files_should_return_array_of_nones_if_path_does_not_exist() {
         let test_files = read_test_files(Path::new("missing.ini")).unwrap();
@@ -1053,6 +1204,21 @@ mod tests {
         assert_eq!(vec!["a"], files);
     }
 
+    #[test]
+    fn test_files_for_enderal_game_ids_should_read_from_my_games_enderal_ini() {
+        let tmp_dir = tempdir().unwrap();
+        let (game_path, my_games_path) = prep_dirs(&tmp_dir);
+
+        let ini_path = my_games_path.join("Enderal.ini");
+        std::fs::write(&ini_path, "[General]\nsTestFile1=a").unwrap();
+
+        let files = test_files(GameId::Enderal, &game_path, &my_games_path).unwrap();
+        assert_eq!(vec!["a"], files);
+
+        let files = test_files(GameId::EnderalSE, &game_path, &my_games_path).unwrap();
+        assert_eq!(vec!["a"], files);
+    }
+
     #[test]
     fn test_files_for_skyrimvr_should_read_from_my_games_skyrimvr_ini() {
         let tmp_dir = tempdir().unwrap();
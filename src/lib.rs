@@ -33,6 +33,9 @@
     )
 )]
 
+mod atomic_write;
+mod backup;
+mod discovery;
 mod enums;
 mod game_settings;
 mod ghostable_path;
@@ -40,12 +43,29 @@ mod ini;
 mod load_order;
 mod openmw_config;
 mod plugin;
+mod profile;
 #[cfg(test)]
 mod tests;
+#[cfg(feature = "watcher")]
+mod watcher;
 
-pub use crate::enums::{Error, GameId, LoadOrderMethod};
+pub use crate::backup::Backup;
+pub use crate::discovery::{
+    discover_epic_installs, discover_gog_installs, discover_installs, discover_steam_installs,
+    DiscoveredInstalls, GameInstall, GameSource,
+};
+pub use crate::enums::{DetectionConfidence, Error, GameId, LoadOrderMethod};
 pub use crate::game_settings::GameSettings;
-pub use crate::load_order::{ReadableLoadOrder, WritableLoadOrder};
+pub use crate::load_order::{
+    ActivationPolicy, DependencyGraph, FileWrite, LoadOrderDiff, LoadOrderOperation,
+    LoadOrderRepair, LoadOrderSnapshot, LoadOrderTransaction, MasterDiagnostics, ModIndex,
+    PluginInfo, PluginMove, PluginRepair, PluginSlotUsage, PluginSnapshot, ReadableLoadOrder,
+    RepairReason, SavePlan, SlotUsage, TimestampChange, ValidationIssue, WritableLoadOrder,
+};
+pub use crate::plugin::ActiveState;
+pub use crate::profile::ProfileStore;
+#[cfg(feature = "watcher")]
+pub use crate::watcher::{LoadOrderEvent, LoadOrderWatcher, WatcherHandle};
 
 fn is_enderal(game_path: &std::path::Path) -> bool {
     game_path.join("Enderal Launcher.exe").exists()
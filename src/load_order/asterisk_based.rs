@@ -17,28 +17,33 @@
  * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
  */
 use std::collections::HashSet;
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::mem;
 
 use unicase::UniCase;
 
-use super::mutable::{hoist_masters, read_plugin_names, MutableLoadOrder};
+use super::fingerprint::FileFingerprints;
+use super::history::{redo, undo, EditHistory};
+use super::mutable::{decode_plugin_names, hoist_masters, read_plugin_names, MutableLoadOrder};
 use super::readable::{ReadableLoadOrder, ReadableLoadOrderBase};
+use super::save_plan::{plan_plugin_timestamps, FileWrite, SavePlan};
 use super::strict_encode;
-use super::timestamp_based::save_load_order_using_timestamps;
+use super::transaction::LoadOrderTransaction;
 use super::writable::{
-    activate, add, create_parent_dirs, deactivate, remove, set_active_plugins, WritableLoadOrder,
+    activate, activate_many, activate_with_masters, add, deactivate, deactivate_with_dependents,
+    remove, restore_backup, save, set_active_plugins, set_load_order, set_plugin_index,
+    transaction, ActivationPolicy, WritableLoadOrder,
 };
 use crate::enums::{Error, GameId};
 use crate::game_settings::GameSettings;
-use crate::load_order::timestamp_based::save_partial_load_order_using_timestamps;
 use crate::load_order::writable::blueprint_ships_base_plugin_name;
-use crate::plugin::{trim_dot_ghost, Plugin};
+use crate::plugin::{trim_dot_ghost, Plugin, PluginCache};
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub(crate) struct AsteriskBasedLoadOrder {
     game_settings: GameSettings,
     plugins: Vec<Plugin>,
+    file_fingerprints: FileFingerprints,
+    history: EditHistory,
 }
 
 impl AsteriskBasedLoadOrder {
@@ -46,10 +51,15 @@ impl AsteriskBasedLoadOrder {
         Self {
             game_settings,
             plugins: Vec::new(),
+            file_fingerprints: FileFingerprints::default(),
+            history: EditHistory::default(),
         }
     }
 
-    fn read_from_active_plugins_file(&self) -> Result<Vec<(String, bool)>, Error> {
+    fn read_from_active_plugins_file(
+        &self,
+        file_fingerprints: &mut FileFingerprints,
+    ) -> Result<Vec<(String, bool)>, Error> {
         if self.ignore_active_plugins_file() {
             if self.game_settings.id() == GameId::Starfield {
                 // For Starfield, if the active plugins file is being ignored, it's because there
@@ -66,10 +76,10 @@ impl AsteriskBasedLoadOrder {
                 Ok(Vec::new())
             }
         } else {
-            read_plugin_names(
-                self.game_settings().active_plugins_file(),
-                owning_plugin_line_mapper,
-            )
+            match file_fingerprints.read_file(self.game_settings().active_plugins_file())? {
+                Some(content) => decode_plugin_names(&content, owning_plugin_line_mapper),
+                None => Ok(Vec::new()),
+            }
         }
     }
 
@@ -127,6 +137,14 @@ impl MutableLoadOrder for AsteriskBasedLoadOrder {
     fn plugins_mut(&mut self) -> &mut Vec<Plugin> {
         &mut self.plugins
     }
+
+    fn edit_history_mut(&mut self) -> Option<&mut EditHistory> {
+        Some(&mut self.history)
+    }
+
+    fn file_fingerprints_mut(&mut self) -> Option<&mut FileFingerprints> {
+        Some(&mut self.file_fingerprints)
+    }
 }
 
 impl WritableLoadOrder for AsteriskBasedLoadOrder {
@@ -137,10 +155,19 @@ impl WritableLoadOrder for AsteriskBasedLoadOrder {
     fn load(&mut self) -> Result<(), Error> {
         self.plugins_mut().clear();
 
-        let plugin_tuples = self.read_from_active_plugins_file()?;
+        self.reload()
+    }
+
+    fn reload(&mut self) -> Result<(), Error> {
+        self.history.clear();
+
+        let cache = PluginCache::new(mem::take(self.plugins_mut()));
+
+        let mut file_fingerprints = FileFingerprints::default();
+        let plugin_tuples = self.read_from_active_plugins_file(&mut file_fingerprints)?;
         let paths = self.game_settings.find_plugins();
 
-        self.load_unique_plugins(&plugin_tuples, &paths);
+        self.load_unique_plugins(&plugin_tuples, &paths, &cache);
 
         self.add_implicitly_active_plugins()?;
 
@@ -150,15 +177,17 @@ impl WritableLoadOrder for AsteriskBasedLoadOrder {
 
         hoist_masters(&mut self.plugins)?;
 
+        self.file_fingerprints = file_fingerprints.finish(self)?;
+
         Ok(())
     }
 
     fn save(&mut self) -> Result<(), Error> {
-        let path = self.game_settings().active_plugins_file();
-        create_parent_dirs(path)?;
+        save(self)
+    }
 
-        let file = File::create(path).map_err(|e| Error::IoError(path.clone(), e))?;
-        let mut writer = BufWriter::new(file);
+    fn plan_save(&self) -> Result<SavePlan, Error> {
+        let mut contents = Vec::new();
         for plugin in self.plugins() {
             if self.game_settings().loads_early(plugin.name()) {
                 // Skip early loading plugins, but not implicitly active plugins
@@ -179,20 +208,23 @@ impl WritableLoadOrder for AsteriskBasedLoadOrder {
             }
 
             if plugin.is_active() {
-                write!(writer, "*").map_err(|e| Error::IoError(path.clone(), e))?;
+                contents.push(b'*');
             }
-            writer
-                .write_all(&strict_encode(plugin.name())?)
-                .map_err(|e| Error::IoError(path.clone(), e))?;
-            writeln!(writer).map_err(|e| Error::IoError(path.clone(), e))?;
+            contents.extend_from_slice(&strict_encode(plugin.name())?);
+            contents.push(b'\n');
         }
 
-        if self.ignore_active_plugins_file() {
+        let file_writes = vec![FileWrite::new(
+            self.game_settings().active_plugins_file().clone(),
+            contents,
+        )];
+
+        let timestamp_changes = if self.ignore_active_plugins_file() {
             // If the active plugins file is being ignored there's no harm in
             // writing to it, but it won't actually have any impact on the load
             // order used by the game. In that case, the only way to set the
             // load order is to modify plugin timestamps, so do that.
-            save_load_order_using_timestamps(&mut self.plugins)?;
+            plan_plugin_timestamps(self.plugins.iter())?
         } else if self.game_settings.id() == GameId::Starfield {
             // Blueprint plugins and BlueprintShips plugins get removed from
             // plugins.txt by Starfield after the file is read. However,
@@ -209,12 +241,12 @@ impl WritableLoadOrder for AsteriskBasedLoadOrder {
             // official BlueprintShips plugins (as of 2026-04-13) are blueprint
             // masters, and there are no other official blueprint plugins.
             // I don't know how common blueprint plugins are in mods.
-            let blueprint_masters_iter =
-                self.plugins.iter_mut().filter(|p| p.is_blueprint_master());
-            save_partial_load_order_using_timestamps(blueprint_masters_iter)?;
-        }
+            plan_plugin_timestamps(self.plugins.iter().filter(|p| p.is_blueprint_master()))?
+        } else {
+            Vec::new()
+        };
 
-        Ok(())
+        Ok(SavePlan::new(file_writes, timestamp_changes))
     }
 
     fn add(&mut self, plugin_name: &str) -> Result<usize, Error> {
@@ -226,11 +258,11 @@ impl WritableLoadOrder for AsteriskBasedLoadOrder {
     }
 
     fn set_load_order(&mut self, plugin_names: &[&str]) -> Result<(), Error> {
-        self.replace_plugins(plugin_names)
+        set_load_order(self, plugin_names)
     }
 
     fn set_plugin_index(&mut self, plugin_name: &str, position: usize) -> Result<usize, Error> {
-        MutableLoadOrder::set_plugin_index(self, plugin_name, position)
+        set_plugin_index(self, plugin_name, position)
     }
 
     fn is_self_consistent(&self) -> Result<bool, Error> {
@@ -290,13 +322,64 @@ impl WritableLoadOrder for AsteriskBasedLoadOrder {
         activate(self, plugin_name)
     }
 
+    fn activate_with_masters(&mut self, plugin_name: &str) -> Result<(), Error> {
+        activate_with_masters(self, plugin_name)
+    }
+
     fn deactivate(&mut self, plugin_name: &str) -> Result<(), Error> {
         deactivate(self, plugin_name)
     }
 
+    fn deactivate_with_dependents(&mut self, plugin_name: &str) -> Result<(), Error> {
+        deactivate_with_dependents(self, plugin_name)
+    }
+
     fn set_active_plugins(&mut self, active_plugin_names: &[&str]) -> Result<(), Error> {
         set_active_plugins(self, active_plugin_names)
     }
+
+    fn activate_many(
+        &mut self,
+        plugin_names: &[&str],
+        policy: ActivationPolicy,
+    ) -> Result<Vec<String>, Error> {
+        activate_many(self, plugin_names, policy)
+    }
+
+    fn transaction(
+        &mut self,
+        edits: &mut dyn FnMut(&mut LoadOrderTransaction<'_>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        transaction(self, edits)
+    }
+
+    fn restore_backup(&mut self, backup_id: &str) -> Result<(), Error> {
+        restore_backup(self, backup_id)
+    }
+
+    fn is_stale(&self) -> Result<bool, Error> {
+        self.file_fingerprints.is_stale(self.game_settings())
+    }
+
+    fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    fn undo(&mut self) -> Result<bool, Error> {
+        undo(self)
+    }
+
+    fn redo(&mut self) -> Result<bool, Error> {
+        redo(self)
+    }
 }
 
 fn starts_with_blueprint_ships(plugin_name: &str) -> bool {
@@ -331,7 +414,7 @@ mod tests {
     use crate::load_order::tests::*;
     use crate::plugin::ActiveState;
     use crate::tests::{copy_to_dir, copy_to_test_dir, set_file_timestamps, NON_ASCII};
-    use std::fs::{create_dir_all, remove_dir_all};
+    use std::fs::{create_dir_all, remove_dir_all, File};
     use std::path::Path;
     use std::time::Duration;
     use tempfile::tempdir;
@@ -353,6 +436,8 @@ mod tests {
         AsteriskBasedLoadOrder {
             game_settings,
             plugins,
+            file_fingerprints: FileFingerprints::default(),
+            history: EditHistory::default(),
         }
     }
 
@@ -958,6 +1043,27 @@ mod tests {
         assert_eq!(vec!["Blank.esp"], load_order.active_plugin_names());
     }
 
+    #[test]
+    fn plan_save_should_return_the_active_plugins_file_that_save_writes() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::SkyrimSE, tmp_dir.path());
+        let path = load_order.game_settings().active_plugins_file().clone();
+
+        let plan = load_order.plan_save().unwrap();
+
+        assert_eq!(1, plan.file_writes().len());
+        assert_eq!(path, plan.file_writes()[0].path());
+        assert!(plan.timestamp_changes().is_empty());
+        assert!(!path.exists());
+
+        load_order.save().unwrap();
+
+        assert_eq!(
+            plan.file_writes()[0].contents(),
+            std::fs::read(&path).unwrap()
+        );
+    }
+
     #[test]
     fn save_should_write_unghosted_plugin_names() {
         let tmp_dir = tempdir().unwrap();
@@ -1343,4 +1449,171 @@ mod tests {
 
         assert!(load_order.is_ambiguous().unwrap());
     }
+
+    #[test]
+    fn save_should_back_up_the_existing_active_plugins_file_if_a_backup_directory_is_set() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::SkyrimSE, tmp_dir.path());
+
+        load_order
+            .game_settings_mut()
+            .set_backup_directory(Some(tmp_dir.path().join("backups")));
+
+        load_order.save().unwrap();
+        assert!(load_order.game_settings().backups().unwrap().is_empty());
+
+        load_order.activate("Blank - Different.esp").unwrap();
+        load_order.save().unwrap();
+
+        let backups = load_order.game_settings().backups().unwrap();
+        assert_eq!(1, backups.len());
+
+        load_order.restore_backup(backups[0].id()).unwrap();
+
+        assert_eq!(vec!["Blank.esp"], load_order.active_plugin_names());
+        assert_eq!(2, load_order.game_settings().backups().unwrap().len());
+    }
+
+    #[test]
+    fn is_stale_should_be_true_if_the_active_plugins_file_changed_since_load() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::SkyrimSE, tmp_dir.path());
+
+        assert!(!load_order.is_stale().unwrap());
+
+        load_order.load().unwrap();
+        assert!(!load_order.is_stale().unwrap());
+
+        write_active_plugins_file(load_order.game_settings(), &["*Blank - Different.esp"]);
+        assert!(load_order.is_stale().unwrap());
+
+        load_order.load().unwrap();
+        assert!(!load_order.is_stale().unwrap());
+    }
+
+    #[test]
+    fn is_stale_should_be_false_after_saving() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::SkyrimSE, tmp_dir.path());
+
+        load_order.load().unwrap();
+        load_order.activate("Blank - Different.esp").unwrap();
+        load_order.save().unwrap();
+
+        assert!(!load_order.is_stale().unwrap());
+    }
+
+    #[test]
+    fn save_should_error_if_stale_and_configured_to_fail_if_stale() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::SkyrimSE, tmp_dir.path());
+
+        load_order.load().unwrap();
+        write_active_plugins_file(load_order.game_settings(), &["*Blank - Different.esp"]);
+
+        load_order.game_settings_mut().set_fail_save_if_stale(true);
+
+        match load_order.save().unwrap_err() {
+            Error::FileChangedSinceLoad(path) => {
+                assert_eq!(load_order.game_settings().active_plugins_file(), &path);
+            }
+            e => panic!("Expected FileChangedSinceLoad error, got {e:?}"),
+        }
+
+        load_order.game_settings_mut().set_fail_save_if_stale(false);
+        assert!(load_order.save().is_ok());
+    }
+
+    #[test]
+    fn transaction_should_apply_all_edits_if_none_fail() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::SkyrimSE, tmp_dir.path());
+
+        load_order
+            .transaction(&mut |lo| {
+                lo.add("Blank.esm")?;
+                lo.activate("Blank.esm")?;
+                lo.deactivate("Blank.esp")
+            })
+            .unwrap();
+
+        assert_eq!(
+            vec!["Blank.esm", "Blank.esp", "Blank - Different.esp"],
+            load_order.plugin_names()
+        );
+        assert_eq!(vec!["Blank.esm"], load_order.active_plugin_names());
+    }
+
+    #[test]
+    fn transaction_should_restore_the_previous_state_if_an_edit_fails() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::SkyrimSE, tmp_dir.path());
+
+        let plugin_names = load_order.plugin_names().join(",");
+        let active_plugin_names = load_order.active_plugin_names().join(",");
+
+        let result = load_order.transaction(&mut |lo| {
+            lo.add("Blank.esm")?;
+            lo.activate("Blank.esm")?;
+            lo.set_plugin_index("Blank - Different.esp", 1)?;
+            lo.activate("missing.esp")
+        });
+
+        match result.unwrap_err() {
+            Error::PluginNotFound(n) => assert_eq!("missing.esp", n),
+            e => panic!("Expected plugin not found error, got {e:?}"),
+        }
+
+        assert_eq!(plugin_names, load_order.plugin_names().join(","));
+        assert_eq!(
+            active_plugin_names,
+            load_order.active_plugin_names().join(",")
+        );
+    }
+
+    #[test]
+    fn transaction_should_allow_edits_that_are_only_valid_once_all_are_made() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::SkyrimSE, tmp_dir.path());
+
+        load_order.add("Blank.esm").unwrap();
+
+        load_order
+            .transaction(&mut |lo| {
+                // This temporarily puts a non-master before a master.
+                lo.set_plugin_index("Blank - Different.esp", 0)?;
+                lo.set_plugin_index("Blank.esm", 0)?;
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(
+            vec!["Blank.esm", "Blank - Different.esp", "Blank.esp"],
+            load_order.plugin_names()
+        );
+    }
+
+    #[test]
+    fn transaction_should_validate_the_load_order_once_all_edits_are_made() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::SkyrimSE, tmp_dir.path());
+
+        load_order.add("Blank.esm").unwrap();
+        let plugin_names = load_order.plugin_names().join(",");
+
+        let result = load_order.transaction(&mut |lo| {
+            lo.set_plugin_index("Blank - Different.esp", 0)?;
+            Ok(())
+        });
+
+        match result.unwrap_err() {
+            Error::NonMasterBeforeMaster { master, non_master } => {
+                assert_eq!("Blank.esm", master);
+                assert_eq!("Blank - Different.esp", non_master);
+            }
+            e => panic!("Expected non-master before master error, got {e:?}"),
+        }
+
+        assert_eq!(plugin_names, load_order.plugin_names().join(","));
+    }
 }
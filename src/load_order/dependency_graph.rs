@@ -0,0 +1,433 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::Write;
+
+use unicase::UniCase;
+
+use crate::enums::Error;
+use crate::plugin::Plugin;
+
+/// A graph of the plugins in a load order and the master dependencies
+/// between them, as listed by each plugin. Only dependencies between plugins
+/// that are in the load order are included.
+///
+/// The graph is independent of the load order that it was created from, so
+/// doesn't reflect any later changes to it.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DependencyGraph {
+    plugin_names: Vec<String>,
+    indexes: HashMap<UniCase<String>, usize>,
+    masters: Vec<Vec<usize>>,
+    dependents: Vec<Vec<usize>>,
+}
+
+impl DependencyGraph {
+    pub(super) fn new(plugins: &[Plugin]) -> Result<Self, Error> {
+        let plugin_names: Vec<String> = plugins.iter().map(|p| p.name().to_owned()).collect();
+        let indexes: HashMap<UniCase<String>, usize> = plugin_names
+            .iter()
+            .enumerate()
+            .map(|(i, n)| (UniCase::new(n.clone()), i))
+            .collect();
+
+        let mut masters = vec![Vec::new(); plugins.len()];
+        let mut dependents = vec![Vec::new(); plugins.len()];
+
+        for ((index, plugin), plugin_masters) in plugins.iter().enumerate().zip(&mut masters) {
+            for master in plugin.masters()? {
+                if let Some(master_index) = indexes.get(&UniCase::new(master)) {
+                    plugin_masters.push(*master_index);
+                    if let Some(master_dependents) = dependents.get_mut(*master_index) {
+                        master_dependents.push(index);
+                    }
+                }
+            }
+        }
+
+        for dependents in &mut dependents {
+            dependents.sort_unstable();
+            dependents.dedup();
+        }
+
+        Ok(Self {
+            plugin_names,
+            indexes,
+            masters,
+            dependents,
+        })
+    }
+
+    /// Get the names of the plugins in the graph, in load order.
+    pub fn plugin_names(&self) -> Vec<&str> {
+        self.plugin_names.iter().map(String::as_str).collect()
+    }
+
+    /// Get the given plugin's masters, in the order that the plugin lists
+    /// them, or `None` if the plugin isn't in the graph.
+    pub fn masters(&self, plugin_name: &str) -> Option<Vec<&str>> {
+        self.edges(&self.masters, plugin_name)
+    }
+
+    /// Get the plugins that have the given plugin as a master, in load order,
+    /// or `None` if the plugin isn't in the graph.
+    pub fn dependents(&self, plugin_name: &str) -> Option<Vec<&str>> {
+        self.edges(&self.dependents, plugin_name)
+    }
+
+    /// Get the given plugin's masters and their masters, and so on, in load
+    /// order, or `None` if the plugin isn't in the graph.
+    pub fn transitive_masters(&self, plugin_name: &str) -> Option<Vec<&str>> {
+        self.transitive_edges(&self.masters, plugin_name)
+    }
+
+    /// Get the plugins that have the given plugin as a master, directly or
+    /// indirectly, in load order, or `None` if the plugin isn't in the graph.
+    pub fn transitive_dependents(&self, plugin_name: &str) -> Option<Vec<&str>> {
+        self.transitive_edges(&self.dependents, plugin_name)
+    }
+
+    /// Get each group of plugins that directly or indirectly have each other
+    /// as masters, including plugins that are their own master. Plugins are
+    /// listed in load order within each cycle, and cycles are ordered by
+    /// their first plugin.
+    pub fn cycles(&self) -> Vec<Vec<&str>> {
+        let mut cycles: Vec<Vec<usize>> = strongly_connected_components(&self.masters)
+            .into_iter()
+            .filter(|component| match component.as_slice() {
+                [index] => self
+                    .masters
+                    .get(*index)
+                    .is_some_and(|masters| masters.contains(index)),
+                _ => true,
+            })
+            .map(|mut component| {
+                component.sort_unstable();
+                component
+            })
+            .collect();
+
+        cycles.sort_unstable();
+
+        cycles
+            .into_iter()
+            .map(|component| self.names(component))
+            .collect()
+    }
+
+    /// Get the plugins in an order where every plugin comes after its
+    /// masters, keeping plugins as close to their load order positions as
+    /// possible. Returns `None` if the graph has cycles.
+    pub fn topological_order(&self) -> Option<Vec<&str>> {
+        let mut remaining_masters: Vec<usize> = self.masters.iter().map(Vec::len).collect();
+        let mut ready: BTreeSet<usize> = remaining_masters
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count == 0)
+            .map(|(i, _)| i)
+            .collect();
+
+        let mut order = Vec::with_capacity(self.plugin_names.len());
+        while let Some(index) = ready.pop_first() {
+            order.push(index);
+
+            for dependent in self.dependents.get(index).into_iter().flatten() {
+                if let Some(count) = remaining_masters.get_mut(*dependent) {
+                    // A plugin may list the same master more than once.
+                    let occurrences = self
+                        .masters
+                        .get(*dependent)
+                        .map_or(0, |m| m.iter().filter(|i| **i == index).count());
+                    *count -= occurrences;
+                    if *count == 0 {
+                        ready.insert(*dependent);
+                    }
+                }
+            }
+        }
+
+        (order.len() == self.plugin_names.len()).then(|| self.names(order))
+    }
+
+    /// Write the graph in the Graphviz DOT language, with an edge from each
+    /// plugin to each of its masters.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n");
+
+        for (name, masters) in self.plugin_names.iter().zip(&self.masters) {
+            // Writing to a String can't fail.
+            _ = writeln!(dot, "    {};", dot_id(name));
+
+            for master in masters.iter().filter_map(|i| self.plugin_names.get(*i)) {
+                _ = writeln!(dot, "    {} -> {};", dot_id(name), dot_id(master));
+            }
+        }
+
+        dot.push('}');
+        dot.push('\n');
+
+        dot
+    }
+
+    fn index_of(&self, plugin_name: &str) -> Option<usize> {
+        self.indexes
+            .get(&UniCase::new(plugin_name.to_owned()))
+            .copied()
+    }
+
+    fn names(&self, indexes: Vec<usize>) -> Vec<&str> {
+        indexes
+            .into_iter()
+            .filter_map(|i| self.plugin_names.get(i))
+            .map(String::as_str)
+            .collect()
+    }
+
+    fn edges(&self, edges: &[Vec<usize>], plugin_name: &str) -> Option<Vec<&str>> {
+        let index = self.index_of(plugin_name)?;
+
+        edges.get(index).map(|e| self.names(e.clone()))
+    }
+
+    fn transitive_edges(&self, edges: &[Vec<usize>], plugin_name: &str) -> Option<Vec<&str>> {
+        let start = self.index_of(plugin_name)?;
+
+        let mut visited = BTreeSet::new();
+        let mut stack = vec![start];
+        while let Some(index) = stack.pop() {
+            for next in edges.get(index).into_iter().flatten() {
+                if visited.insert(*next) {
+                    stack.push(*next);
+                }
+            }
+        }
+
+        // A plugin in a cycle is its own transitive master and dependent,
+        // but that's not useful to report.
+        visited.remove(&start);
+
+        Some(self.names(visited.into_iter().collect()))
+    }
+}
+
+fn dot_id(name: &str) -> String {
+    format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Tarjan's strongly connected components algorithm, using an explicit stack
+/// instead of recursion so that long chains of masters can't overflow the
+/// call stack.
+fn strongly_connected_components(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    const UNVISITED: usize = usize::MAX;
+
+    let mut indexes = vec![UNVISITED; edges.len()];
+    let mut low_links = vec![0; edges.len()];
+    let mut on_stack = vec![false; edges.len()];
+    let mut stack = Vec::new();
+    let mut next_index = 0;
+    let mut components = Vec::new();
+
+    for root in 0..edges.len() {
+        if indexes.get(root) != Some(&UNVISITED) {
+            continue;
+        }
+
+        // Each frame is a node and the position of the next edge to follow.
+        let mut call_stack = vec![(root, 0)];
+        while let Some((node, edge_pos)) = call_stack.pop() {
+            if edge_pos == 0 {
+                set(&mut indexes, node, next_index);
+                set(&mut low_links, node, next_index);
+                next_index += 1;
+                stack.push(node);
+                set(&mut on_stack, node, true);
+            }
+
+            let next = edges.get(node).and_then(|e| e.get(edge_pos)).copied();
+            if let Some(next) = next {
+                call_stack.push((node, edge_pos + 1));
+
+                if get(&indexes, next) == UNVISITED {
+                    call_stack.push((next, 0));
+                } else if get(&on_stack, next) {
+                    let low_link = get(&low_links, node).min(get(&indexes, next));
+                    set(&mut low_links, node, low_link);
+                }
+                continue;
+            }
+
+            if get(&low_links, node) == get(&indexes, node) {
+                let mut component = Vec::new();
+                while let Some(member) = stack.pop() {
+                    set(&mut on_stack, member, false);
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                components.push(component);
+            }
+
+            if let Some((parent, _)) = call_stack.last() {
+                let low_link = get(&low_links, *parent).min(get(&low_links, node));
+                set(&mut low_links, *parent, low_link);
+            }
+        }
+    }
+
+    components
+}
+
+fn get<T: Copy + Default>(values: &[T], index: usize) -> T {
+    values.get(index).copied().unwrap_or_default()
+}
+
+fn set<T>(values: &mut [T], index: usize, value: T) {
+    if let Some(element) = values.get_mut(index) {
+        *element = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crate::enums::GameId;
+    use crate::load_order::tests::game_settings_for_test;
+    use crate::tests::copy_to_test_dir;
+    use crate::GameSettings;
+
+    fn load_plugins(settings: &GameSettings, files: &[(&str, &str)]) -> Vec<Plugin> {
+        files
+            .iter()
+            .map(|(source, name)| {
+                copy_to_test_dir(source, name, settings);
+                Plugin::new(name, settings).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dependency_graph_should_link_plugins_to_their_masters() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings_for_test(GameId::Oblivion, tmp_dir.path());
+        let plugins = load_plugins(
+            &settings,
+            &[
+                (
+                    "Blank - Plugin Dependent.esp",
+                    "Blank - Plugin Dependent.esp",
+                ),
+                ("Blank.esm", "Blank.esm"),
+                ("Blank - Master Dependent.esp", "Blank.esp"),
+                ("Blank - Different.esp", "Blank - Different.esp"),
+            ],
+        );
+
+        let graph = DependencyGraph::new(&plugins).unwrap();
+
+        assert_eq!(
+            vec!["Blank.esp"],
+            graph.masters("blank - plugin dependent.esp").unwrap()
+        );
+        assert_eq!(
+            vec!["Blank.esm", "Blank.esp"],
+            graph
+                .transitive_masters("Blank - Plugin Dependent.esp")
+                .unwrap()
+        );
+        assert_eq!(vec!["Blank.esp"], graph.dependents("Blank.esm").unwrap());
+        assert_eq!(
+            vec!["Blank - Plugin Dependent.esp", "Blank.esp"],
+            graph.transitive_dependents("Blank.esm").unwrap()
+        );
+        assert!(graph.masters("missing.esp").is_none());
+        assert!(graph.cycles().is_empty());
+        assert_eq!(
+            vec![
+                "Blank.esm",
+                "Blank.esp",
+                "Blank - Plugin Dependent.esp",
+                "Blank - Different.esp"
+            ],
+            graph.topological_order().unwrap()
+        );
+    }
+
+    #[test]
+    fn dependency_graph_should_find_cycles() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings_for_test(GameId::Oblivion, tmp_dir.path());
+        let plugins = load_plugins(
+            &settings,
+            &[
+                ("Blank - Master Dependent.esp", "Blank.esm"),
+                ("Blank.esp", "Blank.esp"),
+                (
+                    "Blank - Different Plugin Dependent.esp",
+                    "Blank - Different.esm",
+                ),
+                (
+                    "Blank - Different Master Dependent.esp",
+                    "Blank - Different.esp",
+                ),
+            ],
+        );
+
+        let graph = DependencyGraph::new(&plugins).unwrap();
+
+        assert_eq!(
+            vec![
+                vec!["Blank.esm"],
+                vec!["Blank - Different.esm", "Blank - Different.esp"]
+            ],
+            graph.cycles()
+        );
+        assert!(graph.topological_order().is_none());
+        assert_eq!(
+            vec!["Blank - Different.esm"],
+            graph.transitive_masters("Blank - Different.esp").unwrap()
+        );
+    }
+
+    #[test]
+    fn to_dot_should_write_an_edge_from_each_plugin_to_each_of_its_masters() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings_for_test(GameId::Oblivion, tmp_dir.path());
+        let plugins = load_plugins(
+            &settings,
+            &[
+                ("Blank.esm", "Blank.esm"),
+                (
+                    "Blank - Master Dependent.esp",
+                    "Blank - Master Dependent.esp",
+                ),
+            ],
+        );
+
+        let graph = DependencyGraph::new(&plugins).unwrap();
+
+        assert_eq!(
+            "digraph {\n    \"Blank.esm\";\n    \"Blank - Master Dependent.esp\";\n    \"Blank - Master Dependent.esp\" -> \"Blank.esm\";\n}\n",
+            graph.to_dot()
+        );
+    }
+}
@@ -0,0 +1,583 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::collections::{HashMap, HashSet};
+
+use unicase::UniCase;
+
+use super::readable::ReadableLoadOrderBase;
+use super::snapshot::{LoadOrderSnapshot, PluginSnapshot};
+use super::writable::WritableLoadOrder;
+use crate::enums::{Error, GameId};
+use crate::plugin::{trim_dot_ghost, Plugin};
+
+/// A plugin that has a different position relative to the other plugins in
+/// one load order compared to another.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PluginMove {
+    name: String,
+    old_index: usize,
+    new_index: usize,
+}
+
+impl PluginMove {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn old_index(&self) -> usize {
+        self.old_index
+    }
+
+    pub fn new_index(&self) -> usize {
+        self.new_index
+    }
+}
+
+/// An edit that can be made to a load order, corresponding to the
+/// [WritableLoadOrder] function of the same name.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum LoadOrderOperation {
+    Remove(String),
+    SetPluginIndex { name: String, index: usize },
+    Activate(String),
+    Deactivate(String),
+}
+
+/// The differences between two load orders.
+///
+/// Plugins are matched case-insensitively and ignoring any `.ghost` file
+/// extension, and a plugin is only counted as moved if its position relative
+/// to the other plugins that are in both load orders has changed, not if it
+/// has just been shifted by other plugins being added, removed or moved.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoadOrderDiff {
+    added: Vec<String>,
+    removed: Vec<String>,
+    moved: Vec<PluginMove>,
+    activated: Vec<String>,
+    deactivated: Vec<String>,
+    operations: Vec<LoadOrderOperation>,
+}
+
+impl LoadOrderDiff {
+    pub(super) fn new(old: &LoadOrderSnapshot, new: &LoadOrderSnapshot) -> Self {
+        let game_id = old.game_id();
+        let old_indexes = index_plugins(old.plugins(), game_id);
+        let new_indexes = index_plugins(new.plugins(), game_id);
+
+        let mut diff = LoadOrderDiff::default();
+
+        for plugin in old.plugins() {
+            if !new_indexes.contains_key(&plugin_key(plugin, game_id)) {
+                diff.removed.push(plugin.name().to_owned());
+                diff.operations
+                    .push(LoadOrderOperation::Remove(plugin.name().to_owned()));
+            }
+        }
+
+        // Plugins in the longest sequence of common plugins that are in the
+        // same relative order in both load orders don't need to be moved.
+        let common_plugin_new_indexes: Vec<usize> = old
+            .plugins()
+            .iter()
+            .filter_map(|p| new_indexes.get(&plugin_key(p, game_id)).copied())
+            .collect();
+        let unmoved_new_indexes: HashSet<usize> =
+            longest_increasing_subsequence(&common_plugin_new_indexes)
+                .into_iter()
+                .filter_map(|i| common_plugin_new_indexes.get(i).copied())
+                .collect();
+
+        // Simulate the moves, so that each operation's index is correct when
+        // the operations are applied in order.
+        let mut current: Vec<UniCase<&str>> = old
+            .plugins()
+            .iter()
+            .map(|p| plugin_key(p, game_id))
+            .filter(|k| new_indexes.contains_key(k))
+            .collect();
+
+        let mut previous_key = None;
+        for (new_index, plugin) in new.plugins().iter().enumerate() {
+            let key = plugin_key(plugin, game_id);
+
+            if !unmoved_new_indexes.contains(&new_index) {
+                if let Some(position) = current.iter().position(|k| *k == key) {
+                    current.remove(position);
+                }
+
+                // Put the plugin after the plugin that it should load after,
+                // which has already been put in its correct relative position.
+                let index = previous_key
+                    .and_then(|p| current.iter().position(|k| *k == p))
+                    .map_or(0, |i| i + 1);
+                current.insert(index, key);
+
+                diff.operations.push(LoadOrderOperation::SetPluginIndex {
+                    name: plugin.name().to_owned(),
+                    index,
+                });
+
+                match old_indexes.get(&key) {
+                    Some(old_index) => diff.moved.push(PluginMove {
+                        name: plugin.name().to_owned(),
+                        old_index: *old_index,
+                        new_index,
+                    }),
+                    None => diff.added.push(plugin.name().to_owned()),
+                }
+            }
+
+            previous_key = Some(key);
+        }
+
+        for plugin in new.plugins() {
+            let was_active = old_indexes
+                .get(&plugin_key(plugin, game_id))
+                .and_then(|i| old.plugins().get(*i))
+                .is_some_and(PluginSnapshot::is_active);
+
+            if was_active && !plugin.is_active() {
+                diff.deactivated.push(plugin.name().to_owned());
+            } else if !was_active && plugin.is_active() {
+                diff.activated.push(plugin.name().to_owned());
+            }
+        }
+
+        // Deactivate plugins first so that activating plugins doesn't exceed
+        // the active plugin limits.
+        diff.operations.extend(
+            diff.deactivated
+                .iter()
+                .cloned()
+                .map(LoadOrderOperation::Deactivate),
+        );
+        diff.operations.extend(
+            diff.activated
+                .iter()
+                .cloned()
+                .map(LoadOrderOperation::Activate),
+        );
+
+        diff
+    }
+
+    pub fn added(&self) -> &[String] {
+        &self.added
+    }
+
+    pub fn removed(&self) -> &[String] {
+        &self.removed
+    }
+
+    pub fn moved(&self) -> &[PluginMove] {
+        &self.moved
+    }
+
+    pub fn activated(&self) -> &[String] {
+        &self.activated
+    }
+
+    pub fn deactivated(&self) -> &[String] {
+        &self.deactivated
+    }
+
+    /// The edits that turn the old load order into the new load order, when
+    /// made in the order given. The edits move as few plugins as possible.
+    pub fn operations(&self) -> &[LoadOrderOperation] {
+        &self.operations
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Make this diff's edits to the given load order in a single transaction,
+    /// so the load order is only validated once all the edits have been made.
+    ///
+    /// The load order may have different plugins installed than the one that
+    /// the diff was made from. A plugin that the diff removes but that is
+    /// still installed is deactivated and left where it is instead, and one
+    /// that isn't in the load order is ignored. Plugins are then moved
+    /// relative to the plugins that the diff didn't remove.
+    pub fn apply_to<T: WritableLoadOrder + ?Sized>(&self, load_order: &mut T) -> Result<(), Error> {
+        load_order.transaction(&mut |lo| {
+            let mut kept_plugins: Vec<&str> = Vec::new();
+
+            for operation in &self.operations {
+                match operation {
+                    LoadOrderOperation::Remove(name) => match lo.remove(name) {
+                        Ok(()) | Err(Error::PluginNotFound(_)) => {}
+                        Err(Error::InstalledPlugin(_)) => {
+                            kept_plugins.push(name);
+                            match lo.deactivate(name) {
+                                Ok(()) | Err(Error::ImplicitlyActivePlugin(_)) => {}
+                                Err(e) => return Err(e),
+                            }
+                        }
+                        Err(e) => return Err(e),
+                    },
+                    LoadOrderOperation::SetPluginIndex { name, index } => {
+                        let index =
+                            index_among_all_plugins(lo.plugins(), &kept_plugins, name, *index);
+                        lo.set_plugin_index(name, index)?;
+                    }
+                    LoadOrderOperation::Activate(name) => lo.activate(name)?,
+                    LoadOrderOperation::Deactivate(name) => lo.deactivate(name)?,
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+/// Convert an index into the given plugins that skips the kept plugins into
+/// an index into all of them, as expected by
+/// [WritableLoadOrder::set_plugin_index] when moving the given plugin.
+fn index_among_all_plugins(
+    plugins: &[Plugin],
+    kept_plugins: &[&str],
+    plugin_name: &str,
+    index: usize,
+) -> usize {
+    // The index is for the plugins without the one being moved.
+    let other_plugins = plugins.iter().filter(|p| !p.name_matches(plugin_name));
+
+    other_plugins
+        .enumerate()
+        .filter(|(_, p)| !kept_plugins.iter().any(|k| p.name_matches(k)))
+        .nth(index)
+        .map_or(plugins.len(), |(i, _)| i)
+}
+
+fn plugin_key(plugin: &PluginSnapshot, game_id: GameId) -> UniCase<&str> {
+    UniCase::new(trim_dot_ghost(plugin.name(), game_id))
+}
+
+fn index_plugins(plugins: &[PluginSnapshot], game_id: GameId) -> HashMap<UniCase<&str>, usize> {
+    plugins
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (plugin_key(p, game_id), i))
+        .collect()
+}
+
+/// Returns the positions in the given slice of the values that form its
+/// longest strictly increasing subsequence.
+fn longest_increasing_subsequence(values: &[usize]) -> Vec<usize> {
+    // tails[n] is the position of the smallest value that ends an increasing
+    // subsequence of length n + 1.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut predecessors = Vec::with_capacity(values.len());
+
+    for (position, value) in values.iter().enumerate() {
+        let length = tails.partition_point(|t| values.get(*t).is_some_and(|v| v < value));
+
+        predecessors.push(length.checked_sub(1).and_then(|l| tails.get(l).copied()));
+
+        if let Some(tail) = tails.get_mut(length) {
+            *tail = position;
+        } else {
+            tails.push(position);
+        }
+    }
+
+    let mut subsequence = Vec::new();
+    let mut current = tails.last().copied();
+    while let Some(position) = current {
+        subsequence.push(position);
+        current = predecessors.get(position).copied().flatten();
+    }
+    subsequence.reverse();
+
+    subsequence
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crate::plugin::ActiveState;
+    use crate::tests::copy_to_test_dir;
+    use crate::GameSettings;
+
+    fn snapshot(plugins: &[(&str, bool)]) -> LoadOrderSnapshot {
+        LoadOrderSnapshot::new(
+            GameId::Oblivion,
+            plugins
+                .iter()
+                .map(|(name, active)| {
+                    let state = if *active {
+                        ActiveState::ExplicitlyActive
+                    } else {
+                        ActiveState::Inactive
+                    };
+                    PluginSnapshot::new((*name).to_owned(), state)
+                })
+                .collect(),
+        )
+    }
+
+    /// Apply the given operations to a list of plugins, following the
+    /// behaviour of the equivalent WritableLoadOrder functions.
+    fn simulate(old: &LoadOrderSnapshot, operations: &[LoadOrderOperation]) -> Vec<(String, bool)> {
+        let mut plugins: Vec<(String, bool)> = old
+            .plugins()
+            .iter()
+            .map(|p| (p.name().to_owned(), p.is_active()))
+            .collect();
+
+        for operation in operations {
+            match operation {
+                LoadOrderOperation::Remove(name) => plugins.retain(|(n, _)| n != name),
+                LoadOrderOperation::SetPluginIndex { name, index } => {
+                    let plugin = match plugins.iter().position(|(n, _)| n == name) {
+                        Some(i) => plugins.remove(i),
+                        None => (name.clone(), false),
+                    };
+                    let index = (*index).min(plugins.len());
+                    plugins.insert(index, plugin);
+                }
+                LoadOrderOperation::Activate(name) => {
+                    plugins.iter_mut().find(|(n, _)| n == name).unwrap().1 = true;
+                }
+                LoadOrderOperation::Deactivate(name) => {
+                    plugins.iter_mut().find(|(n, _)| n == name).unwrap().1 = false;
+                }
+            }
+        }
+
+        plugins
+    }
+
+    fn to_tuples(snapshot: &LoadOrderSnapshot) -> Vec<(String, bool)> {
+        snapshot
+            .plugins()
+            .iter()
+            .map(|p| (p.name().to_owned(), p.is_active()))
+            .collect()
+    }
+
+    #[test]
+    fn diff_should_be_empty_for_identical_load_orders() {
+        let old = snapshot(&[("a.esm", true), ("b.esp", false)]);
+
+        let diff = old.diff(&old);
+
+        assert!(diff.is_empty());
+        assert_eq!(LoadOrderDiff::default(), diff);
+    }
+
+    #[test]
+    fn diff_should_match_plugins_case_insensitively_and_ignoring_ghost_extensions() {
+        let old = snapshot(&[("A.esm", true), ("b.esp.ghost", false)]);
+        let new = snapshot(&[("a.esm", true), ("B.esp", false)]);
+
+        assert!(old.diff(&new).is_empty());
+    }
+
+    #[test]
+    fn diff_should_report_added_and_removed_plugins() {
+        let old = snapshot(&[("a.esm", true), ("b.esp", false)]);
+        let new = snapshot(&[("a.esm", true), ("c.esp", true)]);
+
+        let diff = old.diff(&new);
+
+        assert_eq!(&["c.esp"], diff.added());
+        assert_eq!(&["b.esp"], diff.removed());
+        assert!(diff.moved().is_empty());
+        assert_eq!(&["c.esp"], diff.activated());
+        assert_eq!(to_tuples(&new), simulate(&old, diff.operations()));
+    }
+
+    #[test]
+    fn diff_should_only_move_plugins_that_changed_relative_position() {
+        let old = snapshot(&[("a", false), ("b", false), ("c", false), ("d", false)]);
+        let new = snapshot(&[("b", false), ("c", false), ("d", false), ("a", false)]);
+
+        let diff = old.diff(&new);
+
+        assert_eq!(
+            &[PluginMove {
+                name: "a".into(),
+                old_index: 0,
+                new_index: 3
+            }],
+            diff.moved()
+        );
+        assert_eq!(
+            &[LoadOrderOperation::SetPluginIndex {
+                name: "a".into(),
+                index: 3
+            }],
+            diff.operations()
+        );
+    }
+
+    #[test]
+    fn diff_should_report_activation_changes_after_moves() {
+        let old = snapshot(&[("a", true), ("b", false), ("c", true)]);
+        let new = snapshot(&[("a", false), ("c", true), ("b", true)]);
+
+        let diff = old.diff(&new);
+
+        assert_eq!(&["b"], diff.activated());
+        assert_eq!(&["a"], diff.deactivated());
+        assert_eq!(
+            &[
+                LoadOrderOperation::SetPluginIndex {
+                    name: "b".into(),
+                    index: 2
+                },
+                LoadOrderOperation::Deactivate("a".into()),
+                LoadOrderOperation::Activate("b".into()),
+            ],
+            diff.operations()
+        );
+    }
+
+    #[test]
+    fn diff_operations_should_produce_the_new_load_order() {
+        let old = snapshot(&[
+            ("y", false),
+            ("x", true),
+            ("f", false),
+            ("g", true),
+            ("h", false),
+            ("removed", true),
+        ]);
+        let new = snapshot(&[
+            ("f", true),
+            ("added1", false),
+            ("x", true),
+            ("h", false),
+            ("y", false),
+            ("added2", true),
+            ("g", false),
+        ]);
+
+        let diff = old.diff(&new);
+
+        assert_eq!(to_tuples(&new), simulate(&old, diff.operations()));
+        assert_eq!(&["added1", "added2"], diff.added());
+        assert_eq!(&["removed"], diff.removed());
+    }
+
+    #[test]
+    fn apply_to_should_make_the_diff_edits_to_a_load_order() {
+        let tmp_dir = tempdir().unwrap();
+        let game_dir = tmp_dir.path();
+        let settings =
+            GameSettings::with_local_path(GameId::Oblivion, game_dir, &game_dir.join("local"))
+                .unwrap();
+        copy_to_test_dir("Blank.esm", "Blank.esm", &settings);
+        copy_to_test_dir("Blank.esp", "Blank.esp", &settings);
+        copy_to_test_dir("Blank - Different.esp", "Blank - Different.esp", &settings);
+
+        let mut load_order = settings.into_load_order();
+        load_order.load().unwrap();
+
+        let old = load_order.snapshot();
+        let new = LoadOrderSnapshot::new(
+            GameId::Oblivion,
+            vec![
+                PluginSnapshot::new("Blank.esm".into(), ActiveState::ExplicitlyActive),
+                PluginSnapshot::new("Blank - Different.esp".into(), ActiveState::Inactive),
+                PluginSnapshot::new("Blank.esp".into(), ActiveState::ExplicitlyActive),
+            ],
+        );
+
+        old.diff(&new).apply_to(load_order.as_mut()).unwrap();
+
+        assert_eq!(new, load_order.snapshot());
+    }
+
+    #[test]
+    fn apply_to_should_deactivate_instead_of_removing_plugins_that_are_still_installed() {
+        let tmp_dir = tempdir().unwrap();
+        let game_dir = tmp_dir.path();
+        let settings =
+            GameSettings::with_local_path(GameId::Oblivion, game_dir, &game_dir.join("local"))
+                .unwrap();
+        copy_to_test_dir("Blank.esm", "Blank.esm", &settings);
+        copy_to_test_dir("Blank.esp", "Blank.esp", &settings);
+        copy_to_test_dir("Blank - Different.esp", "Blank - Different.esp", &settings);
+        copy_to_test_dir(
+            "Blank - Master Dependent.esp",
+            "Blank - Master Dependent.esp",
+            &settings,
+        );
+
+        let mut load_order = settings.into_load_order();
+        load_order.load().unwrap();
+        load_order
+            .set_load_order(&[
+                "Blank.esm",
+                "Blank - Different.esp",
+                "Blank.esp",
+                "Blank - Master Dependent.esp",
+            ])
+            .unwrap();
+        load_order.activate("Blank - Different.esp").unwrap();
+
+        let old = load_order.snapshot();
+        let new = LoadOrderSnapshot::new(
+            GameId::Oblivion,
+            vec![
+                PluginSnapshot::new("Blank.esm".into(), ActiveState::Inactive),
+                PluginSnapshot::new(
+                    "Blank - Master Dependent.esp".into(),
+                    ActiveState::ExplicitlyActive,
+                ),
+                PluginSnapshot::new("Blank.esp".into(), ActiveState::Inactive),
+            ],
+        );
+
+        old.diff(&new).apply_to(load_order.as_mut()).unwrap();
+
+        assert_eq!(
+            vec![
+                "Blank.esm",
+                "Blank - Different.esp",
+                "Blank - Master Dependent.esp",
+                "Blank.esp"
+            ],
+            load_order.plugin_names()
+        );
+        assert!(!load_order.is_active("Blank - Different.esp"));
+        assert!(load_order.is_active("Blank - Master Dependent.esp"));
+    }
+
+    #[test]
+    fn longest_increasing_subsequence_should_return_positions_of_the_subsequence() {
+        assert!(longest_increasing_subsequence(&[]).is_empty());
+        assert_eq!(vec![0], longest_increasing_subsequence(&[5]));
+        assert_eq!(vec![1, 2, 3], longest_increasing_subsequence(&[1, 0, 2, 3]));
+        assert_eq!(
+            vec![2, 4, 5],
+            longest_increasing_subsequence(&[3, 4, 0, 5, 1, 2])
+        );
+    }
+}
@@ -0,0 +1,418 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::fs::{metadata, read};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use super::readable::ReadableLoadOrderBase;
+use super::save_plan::SavePlan;
+use crate::enums::{Error, GameId, LoadOrderMethod};
+use crate::game_settings::GameSettings;
+use crate::ghostable_path::GhostablePath;
+use crate::plugin::{trim_dot_ghost, Plugin};
+
+/// The state of a file at a point in time, which can be compared to its state
+/// at another point in time to tell if it has changed in between.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub(crate) struct FileState {
+    modified: SystemTime,
+    size: u64,
+    hash: Option<u64>,
+}
+
+impl FileState {
+    /// Read the state of the file at the given path, or None if it doesn't
+    /// exist. The file's contents are only hashed if `hash_contents` is true,
+    /// otherwise only its size and modification time are compared.
+    pub(crate) fn read(path: &Path, hash_contents: bool) -> Result<Option<Self>, Error> {
+        let metadata = match metadata(path) {
+            Ok(m) => m,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::IoError(path.to_path_buf(), e)),
+        };
+
+        let modified = metadata
+            .modified()
+            .map_err(|e| Error::IoError(path.to_path_buf(), e))?;
+
+        let hash = if hash_contents {
+            let contents = read(path).map_err(|e| Error::IoError(path.to_path_buf(), e))?;
+            Some(hash_bytes(&contents))
+        } else {
+            None
+        };
+
+        Ok(Some(FileState {
+            modified,
+            size: metadata.len(),
+            hash,
+        }))
+    }
+
+    fn with_contents(modified: SystemTime, contents: &[u8]) -> Self {
+        FileState {
+            modified,
+            size: u64::try_from(contents.len()).unwrap_or(u64::MAX),
+            hash: Some(hash_bytes(contents)),
+        }
+    }
+}
+
+fn hash_bytes(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    bytes.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+struct FileFingerprint {
+    path: PathBuf,
+    is_plugin: bool,
+    state: Option<FileState>,
+}
+
+impl FileFingerprint {
+    fn new(path: PathBuf) -> Result<Self, Error> {
+        let state = FileState::read(&path, true)?;
+
+        Ok(FileFingerprint {
+            path,
+            is_plugin: false,
+            state,
+        })
+    }
+
+    fn has_changed(&self) -> Result<bool, Error> {
+        if self.is_plugin {
+            // Plugins can be large, and it's only their timestamps that
+            // matter. Activating or deactivating a plugin may (un)ghost it, so
+            // check the plugin's file whether or not it is ghosted.
+            let path = self
+                .path
+                .resolve_path()
+                .unwrap_or_else(|_| self.path.clone());
+
+            FileState::read(&path, false).map(|s| s != self.state)
+        } else {
+            FileState::read(&self.path, true).map(|s| s != self.state)
+        }
+    }
+}
+
+/// The states of the files that a load order was read from, so that changes
+/// made to them by other programs can be detected.
+///
+/// Files are fingerprinted from the metadata and bytes that the load order was
+/// read from, so that a change made while the load order is being read isn't
+/// missed. For games that use plugin timestamps to decide the load order, the
+/// plugins' timestamps and the names of the installed plugins are also
+/// recorded.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub(super) struct FileFingerprints {
+    files: Vec<FileFingerprint>,
+    plugin_names: Option<Vec<String>>,
+}
+
+impl FileFingerprints {
+    /// Read the file at the given path, fingerprinting it from the metadata
+    /// and bytes read. Returns None if the file doesn't exist.
+    pub(super) fn read_file(&mut self, path: &Path) -> Result<Option<Vec<u8>>, Error> {
+        // Get the modification time before reading the file, so that if the
+        // file is changed in between, the fingerprint won't match it.
+        let modified = match metadata(path).and_then(|m| m.modified()) {
+            Ok(m) => m,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.insert(path.to_path_buf(), None);
+                return Ok(None);
+            }
+            Err(e) => return Err(Error::IoError(path.to_path_buf(), e)),
+        };
+
+        let contents = read(path).map_err(|e| Error::IoError(path.to_path_buf(), e))?;
+
+        self.insert(
+            path.to_path_buf(),
+            Some(FileState::with_contents(modified, &contents)),
+        );
+
+        Ok(Some(contents))
+    }
+
+    /// Record the names of the plugins found when reading the load order, so
+    /// that installing or uninstalling plugins can be detected.
+    pub(super) fn record_plugin_paths(&mut self, paths: &[PathBuf], game_id: GameId) {
+        self.plugin_names = Some(plugin_names(paths, game_id));
+    }
+
+    /// Fingerprint the load order's other source files that haven't been read
+    /// through [FileFingerprints::read_file], which are read when the game
+    /// settings are created, and the load order's plugins' timestamps if
+    /// they decide the load order.
+    pub(super) fn finish<T: ReadableLoadOrderBase>(
+        mut self,
+        load_order: &T,
+    ) -> Result<Self, Error> {
+        for path in load_order.game_settings_base().load_order_source_files()? {
+            if !self.files.iter().any(|f| f.path == path) {
+                self.files.push(FileFingerprint::new(path)?);
+            }
+        }
+
+        self.record_plugin_timestamps(load_order.game_settings_base(), load_order.plugins());
+
+        Ok(self)
+    }
+
+    /// Update the fingerprints of the files that saving the load order wrote
+    /// to, using the written bytes, and of the plugins whose timestamps it
+    /// changed.
+    pub(super) fn record_save(
+        &mut self,
+        game_settings: &GameSettings,
+        plugins: &[Plugin],
+        plan: &SavePlan,
+    ) -> Result<(), Error> {
+        for file_write in plan.file_writes() {
+            let path = file_write.path();
+            let modified = metadata(path)
+                .and_then(|m| m.modified())
+                .map_err(|e| Error::IoError(path.to_path_buf(), e))?;
+
+            self.insert(
+                path.to_path_buf(),
+                Some(FileState::with_contents(modified, file_write.contents())),
+            );
+        }
+
+        self.record_plugin_timestamps(game_settings, plugins);
+
+        Ok(())
+    }
+
+    fn record_plugin_timestamps(&mut self, game_settings: &GameSettings, plugins: &[Plugin]) {
+        if game_settings.load_order_method() != LoadOrderMethod::Timestamp {
+            return;
+        }
+
+        self.files.retain(|f| !f.is_plugin);
+
+        for plugin in plugins {
+            self.files.push(FileFingerprint {
+                path: plugin.path().to_path_buf(),
+                is_plugin: true,
+                state: Some(FileState {
+                    modified: plugin.modification_time(),
+                    size: plugin.file_size(),
+                    hash: None,
+                }),
+            });
+        }
+    }
+
+    fn insert(&mut self, path: PathBuf, state: Option<FileState>) {
+        let fingerprint = FileFingerprint {
+            path,
+            is_plugin: false,
+            state,
+        };
+
+        match self.files.iter_mut().find(|f| f.path == fingerprint.path) {
+            Some(existing) => *existing = fingerprint,
+            None => self.files.push(fingerprint),
+        }
+    }
+
+    pub(super) fn find_changed_file(
+        &self,
+        game_settings: &GameSettings,
+    ) -> Result<Option<PathBuf>, Error> {
+        for fingerprint in &self.files {
+            if fingerprint.has_changed()? {
+                return Ok(Some(fingerprint.path.clone()));
+            }
+        }
+
+        if let Some(recorded_names) = &self.plugin_names {
+            let current_names = plugin_names(&game_settings.find_plugins(), game_settings.id());
+            if current_names != *recorded_names {
+                return Ok(Some(game_settings.plugins_directory()));
+            }
+        }
+
+        Ok(None)
+    }
+
+    pub(super) fn is_stale(&self, game_settings: &GameSettings) -> Result<bool, Error> {
+        self.find_changed_file(game_settings).map(|p| p.is_some())
+    }
+
+    /// Check that the files haven't changed, if the game settings say that
+    /// saving should fail when they have.
+    pub(super) fn check_before_save(&self, game_settings: &GameSettings) -> Result<(), Error> {
+        if game_settings.fail_save_if_stale() {
+            if let Some(path) = self.find_changed_file(game_settings)? {
+                return Err(Error::FileChangedSinceLoad(path));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Get the sorted and deduplicated names of the plugins at the given paths,
+/// ignoring whether they're ghosted.
+fn plugin_names(paths: &[PathBuf], game_id: GameId) -> Vec<String> {
+    let mut names: Vec<_> = paths
+        .iter()
+        .filter_map(|p| p.file_name().and_then(|n| n.to_str()))
+        .map(|n| trim_dot_ghost(n, game_id).to_owned())
+        .collect();
+
+    names.sort();
+    names.dedup();
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::{remove_file, write};
+
+    use tempfile::tempdir;
+
+    use crate::tests::set_file_timestamps;
+
+    #[test]
+    fn has_changed_should_be_false_if_the_file_is_unchanged() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("plugins.txt");
+        write(&path, "a.esp").unwrap();
+
+        let fingerprint = FileFingerprint::new(path).unwrap();
+
+        assert!(!fingerprint.has_changed().unwrap());
+    }
+
+    #[test]
+    fn has_changed_should_be_true_if_the_file_content_changes() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("plugins.txt");
+        write(&path, "a.esp").unwrap();
+        set_file_timestamps(&path, 1000);
+
+        let fingerprint = FileFingerprint::new(path.clone()).unwrap();
+
+        // Keep the same size and timestamp so only the hash differs.
+        write(&path, "b.esp").unwrap();
+        set_file_timestamps(&path, 1000);
+
+        assert!(fingerprint.has_changed().unwrap());
+    }
+
+    #[test]
+    fn has_changed_should_be_true_if_the_file_is_created_or_deleted() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("plugins.txt");
+
+        let fingerprint = FileFingerprint::new(path.clone()).unwrap();
+        write(&path, "a.esp").unwrap();
+        assert!(fingerprint.has_changed().unwrap());
+
+        let fingerprint = FileFingerprint::new(path.clone()).unwrap();
+        remove_file(&path).unwrap();
+        assert!(fingerprint.has_changed().unwrap());
+    }
+
+    #[test]
+    fn has_changed_should_ignore_plugin_content_and_ghosting() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("a.esp");
+        write(&path, "a").unwrap();
+        set_file_timestamps(&path, 1000);
+
+        let fingerprint = FileFingerprint {
+            path: path.clone(),
+            is_plugin: true,
+            state: FileState::read(&path, false).unwrap(),
+        };
+
+        write(&path, "b").unwrap();
+        set_file_timestamps(&path, 1000);
+        assert!(!fingerprint.has_changed().unwrap());
+
+        let ghosted_path = tmp_dir.path().join("a.esp.ghost");
+        std::fs::rename(&path, &ghosted_path).unwrap();
+        assert!(!fingerprint.has_changed().unwrap());
+
+        set_file_timestamps(&ghosted_path, 2000);
+        assert!(fingerprint.has_changed().unwrap());
+    }
+
+    #[test]
+    fn read_file_should_fingerprint_the_file_from_the_bytes_read() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("plugins.txt");
+        write(&path, "a.esp").unwrap();
+        set_file_timestamps(&path, 1000);
+
+        let mut fingerprints = FileFingerprints::default();
+        let contents = fingerprints.read_file(&path).unwrap();
+
+        assert_eq!(Some(b"a.esp".to_vec()), contents);
+        assert_eq!(1, fingerprints.files.len());
+        assert!(!fingerprints.files[0].has_changed().unwrap());
+
+        write(&path, "b.esp").unwrap();
+        set_file_timestamps(&path, 1000);
+
+        assert!(fingerprints.files[0].has_changed().unwrap());
+    }
+
+    #[test]
+    fn read_file_should_fingerprint_a_missing_file_as_absent() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("plugins.txt");
+
+        let mut fingerprints = FileFingerprints::default();
+        assert!(fingerprints.read_file(&path).unwrap().is_none());
+        assert!(!fingerprints.files[0].has_changed().unwrap());
+
+        write(&path, "a.esp").unwrap();
+
+        assert!(fingerprints.files[0].has_changed().unwrap());
+    }
+
+    #[test]
+    fn plugin_names_should_ignore_ghost_extensions_and_duplicates() {
+        let paths = vec![
+            PathBuf::from("b/B.esp.ghost"),
+            PathBuf::from("a/A.esm"),
+            PathBuf::from("c/B.esp"),
+        ];
+
+        assert_eq!(
+            vec!["A.esm".to_owned(), "B.esp".to_owned()],
+            plugin_names(&paths, GameId::Oblivion)
+        );
+    }
+}
@@ -0,0 +1,361 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::collections::VecDeque;
+use std::mem;
+
+use super::mutable::MutableLoadOrder;
+use crate::enums::Error;
+use crate::plugin::Plugin;
+
+/// The states that a load order's plugins were in before and after recent
+/// edits, so that the edits can be undone and redone. Recording is disabled
+/// until a limit is set.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub(super) struct EditHistory {
+    limit: usize,
+    undo_states: VecDeque<Vec<Plugin>>,
+    redo_states: Vec<Vec<Plugin>>,
+}
+
+impl EditHistory {
+    pub(super) fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+
+        while self.undo_states.len() > limit {
+            self.undo_states.pop_front();
+        }
+        // The next state to redo is at the end, so drop the furthest ones.
+        let excess = self.redo_states.len().saturating_sub(limit);
+        self.redo_states.drain(..excess);
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.undo_states.clear();
+        self.redo_states.clear();
+    }
+
+    pub(super) fn can_undo(&self) -> bool {
+        !self.undo_states.is_empty()
+    }
+
+    pub(super) fn can_redo(&self) -> bool {
+        !self.redo_states.is_empty()
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.limit > 0
+    }
+
+    fn push(&mut self, plugins: Vec<Plugin>) {
+        if self.undo_states.len() == self.limit {
+            self.undo_states.pop_front();
+        }
+        self.undo_states.push_back(plugins);
+
+        // A new edit invalidates anything that was undone before it.
+        self.redo_states.clear();
+    }
+}
+
+/// Make the given edit, recording the load order's previous state if the
+/// load order keeps an edit history and the edit succeeds and changes
+/// anything.
+pub(super) fn record_edit<T: MutableLoadOrder, R>(
+    load_order: &mut T,
+    edit: impl FnOnce(&mut T) -> Result<R, Error>,
+) -> Result<R, Error> {
+    // Take the history while the edit is made so that edits made as part of
+    // it (e.g. in a transaction) aren't also recorded individually.
+    let Some(mut history) = load_order.edit_history_mut().map(mem::take) else {
+        return edit(load_order);
+    };
+
+    let previous_plugins = history.is_enabled().then(|| load_order.plugins().to_vec());
+
+    let result = edit(load_order);
+
+    if let (Ok(_), Some(previous_plugins)) = (&result, previous_plugins) {
+        if previous_plugins != load_order.plugins() {
+            history.push(previous_plugins);
+        }
+    }
+
+    if let Some(edit_history) = load_order.edit_history_mut() {
+        *edit_history = history;
+    }
+
+    result
+}
+
+pub(super) fn undo<T: MutableLoadOrder>(load_order: &mut T) -> Result<bool, Error> {
+    let Some(plugins) = load_order
+        .edit_history_mut()
+        .and_then(|h| h.undo_states.pop_back())
+    else {
+        return Ok(false);
+    };
+
+    let current_plugins = load_order.plugins().to_vec();
+    let result = restore_installed_plugins(load_order, plugins);
+
+    if let Some(history) = load_order.edit_history_mut() {
+        history.redo_states.push(current_plugins);
+    }
+
+    result.map(|()| true)
+}
+
+pub(super) fn redo<T: MutableLoadOrder>(load_order: &mut T) -> Result<bool, Error> {
+    let Some(plugins) = load_order
+        .edit_history_mut()
+        .and_then(|h| h.redo_states.pop())
+    else {
+        return Ok(false);
+    };
+
+    let current_plugins = load_order.plugins().to_vec();
+    let result = restore_installed_plugins(load_order, plugins);
+
+    if let Some(history) = load_order.edit_history_mut() {
+        history.undo_states.push_back(current_plugins);
+    }
+
+    result.map(|()| true)
+}
+
+/// Restore the given plugins and their files' ghosting, leaving out any whose
+/// files have since been deleted, as a load order only holds installed
+/// plugins. This means that undoing the removal of a plugin only brings it
+/// back if it has been installed again.
+///
+/// A plugin whose file can't be renamed is kept, as its file still exists,
+/// and the first such error is returned once all the plugins are restored.
+fn restore_installed_plugins<T: MutableLoadOrder>(
+    load_order: &mut T,
+    plugins: Vec<Plugin>,
+) -> Result<(), Error> {
+    *load_order.plugins_mut() = plugins;
+
+    let mut first_error = None;
+    load_order
+        .plugins_mut()
+        .retain(|p| match p.restore_file_path() {
+            Ok(()) => p.path().exists(),
+            Err(e) => {
+                first_error.get_or_insert(e);
+                true
+            }
+        });
+
+    first_error.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use crate::enums::GameId;
+    use crate::load_order::tests::{game_settings_for_test, mock_game_files};
+    use crate::load_order::WritableLoadOrder;
+    use crate::tests::copy_to_test_dir;
+
+    fn prepare(game_dir: &Path) -> Box<dyn WritableLoadOrder + Send + Sync> {
+        let mut settings = game_settings_for_test(GameId::Oblivion, game_dir);
+        mock_game_files(&mut settings);
+
+        let mut load_order = settings.into_load_order();
+        load_order.load().unwrap();
+        load_order.set_history_limit(10);
+        load_order
+    }
+
+    #[test]
+    fn edits_should_not_be_recorded_if_the_history_limit_is_zero() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+        load_order.set_history_limit(0);
+
+        load_order.activate("Blank.esp").unwrap();
+
+        assert!(!load_order.can_undo());
+        assert!(!load_order.undo().unwrap());
+        assert!(load_order.is_active("Blank.esp"));
+    }
+
+    #[test]
+    fn undo_and_redo_should_restore_plugin_order_and_active_states() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+        let initial_snapshot = load_order.snapshot();
+
+        load_order.activate("Blank.esp").unwrap();
+        load_order.set_plugin_index("Blank.esp", 3).unwrap();
+        let edited_snapshot = load_order.snapshot();
+
+        assert!(load_order.undo().unwrap());
+        assert!(load_order.undo().unwrap());
+        assert!(!load_order.can_undo());
+        assert_eq!(initial_snapshot, load_order.snapshot());
+
+        assert!(load_order.redo().unwrap());
+        assert!(load_order.redo().unwrap());
+        assert!(!load_order.can_redo());
+        assert_eq!(edited_snapshot, load_order.snapshot());
+    }
+
+    #[test]
+    fn a_new_edit_should_discard_undone_edits() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+
+        load_order.activate("Blank.esp").unwrap();
+        load_order.undo().unwrap();
+        load_order.activate("Blank - Different.esp").unwrap();
+
+        assert!(!load_order.can_redo());
+        assert!(!load_order.redo().unwrap());
+    }
+
+    #[test]
+    fn history_should_only_keep_the_most_recent_edits_up_to_the_limit() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+        load_order.set_history_limit(1);
+
+        load_order.activate("Blank.esp").unwrap();
+        load_order.activate("Blank - Different.esp").unwrap();
+
+        assert!(load_order.undo().unwrap());
+        assert!(!load_order.undo().unwrap());
+        assert!(load_order.is_active("Blank.esp"));
+        assert!(!load_order.is_active("Blank - Different.esp"));
+    }
+
+    #[test]
+    fn lowering_the_history_limit_should_keep_the_next_edits_to_redo() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+
+        load_order.activate("Blank.esp").unwrap();
+        load_order.activate("Blank - Different.esp").unwrap();
+        load_order.activate("Blank.esm").unwrap();
+
+        assert!(load_order.undo().unwrap());
+        assert!(load_order.undo().unwrap());
+        assert!(load_order.undo().unwrap());
+        load_order.set_history_limit(1);
+
+        assert!(load_order.redo().unwrap());
+        assert!(!load_order.can_redo());
+        assert!(load_order.is_active("Blank.esp"));
+        assert!(!load_order.is_active("Blank - Different.esp"));
+        assert!(!load_order.is_active("Blank.esm"));
+    }
+
+    #[test]
+    fn a_transaction_should_be_undone_as_a_single_edit() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+
+        load_order
+            .transaction(&mut |lo| {
+                lo.activate("Blank.esp")?;
+                lo.activate("Blank - Different.esp")
+            })
+            .unwrap();
+
+        assert!(load_order.undo().unwrap());
+        assert!(!load_order.can_undo());
+        assert!(load_order.active_plugin_names().is_empty());
+    }
+
+    #[test]
+    fn undo_and_redo_should_restore_ghosting() {
+        let tmp_dir = tempdir().unwrap();
+        let mut settings = game_settings_for_test(GameId::Oblivion, tmp_dir.path());
+        mock_game_files(&mut settings);
+        copy_to_test_dir("Blank.esm", "Blank - Different.esm.ghost", &settings);
+        let plugins_dir = settings.plugins_directory();
+
+        let mut load_order = settings.into_load_order();
+        load_order.load().unwrap();
+        load_order.set_history_limit(10);
+
+        load_order.activate("Blank - Different.esm").unwrap();
+        assert!(plugins_dir.join("Blank - Different.esm").exists());
+
+        load_order.undo().unwrap();
+        assert!(plugins_dir.join("Blank - Different.esm.ghost").exists());
+        assert!(!plugins_dir.join("Blank - Different.esm").exists());
+        assert!(!load_order.is_active("Blank - Different.esm"));
+
+        load_order.redo().unwrap();
+        assert!(plugins_dir.join("Blank - Different.esm").exists());
+        assert!(!plugins_dir.join("Blank - Different.esm.ghost").exists());
+        assert!(load_order.is_active("Blank - Different.esm"));
+    }
+
+    #[test]
+    fn undoing_a_remove_should_not_restore_a_plugin_that_is_still_uninstalled() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+        let plugin_path = load_order
+            .game_settings()
+            .plugins_directory()
+            .join("Blank.esp");
+
+        std::fs::remove_file(&plugin_path).unwrap();
+        load_order.remove("Blank.esp").unwrap();
+
+        assert!(load_order.undo().unwrap());
+        assert!(load_order.index_of("Blank.esp").is_none());
+    }
+
+    #[test]
+    fn undoing_a_remove_should_restore_a_plugin_that_has_been_reinstalled() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+        let index = load_order.index_of("Blank.esp").unwrap();
+
+        let plugin_path = load_order
+            .game_settings()
+            .plugins_directory()
+            .join("Blank.esp");
+        let backup_path = tmp_dir.path().join("Blank.esp");
+        std::fs::rename(&plugin_path, &backup_path).unwrap();
+        load_order.remove("Blank.esp").unwrap();
+        std::fs::rename(&backup_path, &plugin_path).unwrap();
+
+        assert!(load_order.undo().unwrap());
+        assert_eq!(Some(index), load_order.index_of("Blank.esp"));
+    }
+
+    #[test]
+    fn loading_should_discard_the_history() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+
+        load_order.activate("Blank.esp").unwrap();
+        load_order.load().unwrap();
+
+        assert!(!load_order.can_undo());
+    }
+}
@@ -0,0 +1,244 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::collections::{HashMap, HashSet};
+
+use unicase::UniCase;
+
+use crate::enums::Error;
+use crate::plugin::Plugin;
+
+/// The masters of an active plugin that are either not installed or not
+/// active. The game is likely to crash on startup if an active plugin has any.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MasterDiagnostics {
+    plugin_name: String,
+    missing_masters: Vec<String>,
+    inactive_masters: Vec<String>,
+}
+
+impl MasterDiagnostics {
+    pub fn plugin_name(&self) -> &str {
+        &self.plugin_name
+    }
+
+    /// Masters that aren't in the load order, in the order that the plugin
+    /// lists them.
+    pub fn missing_masters(&self) -> &[String] {
+        &self.missing_masters
+    }
+
+    /// Masters that are in the load order but inactive, in the order that
+    /// the plugin lists them.
+    pub fn inactive_masters(&self) -> &[String] {
+        &self.inactive_masters
+    }
+
+    /// Check if all of the plugin's masters are installed and active.
+    pub fn is_ok(&self) -> bool {
+        self.missing_masters.is_empty() && self.inactive_masters.is_empty()
+    }
+}
+
+/// Get the master diagnostics for each active plugin, in load order.
+pub(super) fn diagnose_masters(plugins: &[Plugin]) -> Result<Vec<MasterDiagnostics>, Error> {
+    let plugins_map: HashMap<UniCase<&str>, &Plugin> = plugins
+        .iter()
+        .map(|p| (UniCase::new(p.name()), p))
+        .collect();
+
+    plugins
+        .iter()
+        .filter(|p| p.is_active())
+        .map(|plugin| {
+            let mut missing_masters = Vec::new();
+            let mut inactive_masters = Vec::new();
+
+            for master in plugin.masters()? {
+                match plugins_map.get(&UniCase::new(master.as_str())) {
+                    None => missing_masters.push(master),
+                    Some(p) if !p.is_active() => inactive_masters.push(master),
+                    Some(_) => {}
+                }
+            }
+
+            Ok(MasterDiagnostics {
+                plugin_name: plugin.name().to_owned(),
+                missing_masters,
+                inactive_masters,
+            })
+        })
+        .collect()
+}
+
+/// Get the names of the given plugin and all the masters that it depends on,
+/// directly or indirectly, with each plugin's masters listed before it.
+/// Fails if the plugin or any of those masters aren't in the load order.
+pub(super) fn plugin_and_masters<'a>(
+    plugins: &'a [Plugin],
+    plugin_name: &str,
+) -> Result<Vec<&'a str>, Error> {
+    let mut visited = HashSet::new();
+    visited.insert(UniCase::new(plugin_name.to_owned()));
+
+    let mut plugin_names = Vec::new();
+    add_plugin_and_masters(plugins, plugin_name, &mut visited, &mut plugin_names)?;
+
+    Ok(plugin_names)
+}
+
+fn add_plugin_and_masters<'a>(
+    plugins: &'a [Plugin],
+    plugin_name: &str,
+    visited: &mut HashSet<UniCase<String>>,
+    plugin_names: &mut Vec<&'a str>,
+) -> Result<(), Error> {
+    let Some(plugin) = plugins.iter().find(|p| p.name_matches(plugin_name)) else {
+        return Err(Error::PluginNotFound(plugin_name.to_owned()));
+    };
+
+    for master in plugin.masters()? {
+        // Masters can't really be cyclic, but guard against it anyway.
+        if visited.insert(UniCase::new(master.clone())) {
+            add_plugin_and_masters(plugins, &master, visited, plugin_names)?;
+        }
+    }
+
+    plugin_names.push(plugin.name());
+
+    Ok(())
+}
+
+/// Get the names of the active plugins that have the given plugin as a
+/// master, directly or indirectly, in load order.
+pub(super) fn active_dependents<'a>(plugins: &'a [Plugin], plugin_name: &str) -> Vec<&'a str> {
+    let mut is_dependent = vec![false; plugins.len()];
+    let mut masters = vec![plugin_name];
+
+    while let Some(master) = masters.pop() {
+        for (plugin, is_dependent) in plugins.iter().zip(is_dependent.iter_mut()) {
+            if !*is_dependent && plugin.is_active() && plugin.has_master(master) {
+                *is_dependent = true;
+                masters.push(plugin.name());
+            }
+        }
+    }
+
+    plugins
+        .iter()
+        .zip(is_dependent)
+        .filter(|(_, is_dependent)| *is_dependent)
+        .map(|(p, _)| p.name())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crate::enums::GameId;
+    use crate::load_order::tests::{game_settings_for_test, mock_game_files};
+    use crate::plugin::ActiveState;
+    use crate::tests::copy_to_test_dir;
+
+    #[test]
+    fn diagnose_masters_should_report_missing_and_inactive_masters_of_active_plugins() {
+        let tmp_dir = tempdir().unwrap();
+        let mut settings = game_settings_for_test(GameId::SkyrimSE, tmp_dir.path());
+        mock_game_files(&mut settings);
+        copy_to_test_dir(
+            "Blank - Master Dependent.esm",
+            "Blank - Master Dependent.esm",
+            &settings,
+        );
+
+        let active = ActiveState::ExplicitlyActive;
+        let plugins = vec![
+            Plugin::new("Blank.esm", &settings).unwrap(),
+            Plugin::with_active("Blank - Master Dependent.esm", &settings, active).unwrap(),
+            Plugin::with_active("Blank.esp", &settings, active).unwrap(),
+            Plugin::new("Blank - Master Dependent.esp", &settings).unwrap(),
+        ];
+
+        let diagnostics = diagnose_masters(&plugins).unwrap();
+
+        assert_eq!(2, diagnostics.len());
+        assert_eq!("Blank - Master Dependent.esm", diagnostics[0].plugin_name());
+        assert!(diagnostics[0].missing_masters().is_empty());
+        assert_eq!(&["Blank.esm"], diagnostics[0].inactive_masters());
+        assert!(!diagnostics[0].is_ok());
+        assert_eq!("Blank.esp", diagnostics[1].plugin_name());
+        assert!(diagnostics[1].is_ok());
+
+        let plugins = &plugins[1..];
+        let diagnostics = diagnose_masters(plugins).unwrap();
+
+        assert_eq!(&["Blank.esm"], diagnostics[0].missing_masters());
+        assert!(diagnostics[0].inactive_masters().is_empty());
+    }
+
+    #[test]
+    fn plugin_and_masters_should_list_masters_before_their_dependents() {
+        let tmp_dir = tempdir().unwrap();
+        let mut settings = game_settings_for_test(GameId::SkyrimSE, tmp_dir.path());
+        mock_game_files(&mut settings);
+        let plugin_dependent_master = "Blank - Plugin Dependent.esm";
+        copy_to_test_dir(
+            "Blank - Plugin Dependent.esp",
+            plugin_dependent_master,
+            &settings,
+        );
+
+        let plugins = vec![
+            Plugin::new(plugin_dependent_master, &settings).unwrap(),
+            Plugin::new("Blank.esm", &settings).unwrap(),
+            Plugin::new("Blank.esp", &settings).unwrap(),
+        ];
+
+        assert_eq!(
+            vec!["Blank.esp", plugin_dependent_master],
+            plugin_and_masters(&plugins, plugin_dependent_master).unwrap()
+        );
+    }
+
+    #[test]
+    fn active_dependents_should_only_include_active_plugins_that_depend_on_the_plugin() {
+        let tmp_dir = tempdir().unwrap();
+        let mut settings = game_settings_for_test(GameId::Oblivion, tmp_dir.path());
+        mock_game_files(&mut settings);
+        let master_dependent_master = "Blank - Master Dependent.esm";
+        copy_to_test_dir(master_dependent_master, master_dependent_master, &settings);
+
+        let active = ActiveState::ExplicitlyActive;
+        let plugins = vec![
+            Plugin::with_active("Blank.esm", &settings, active).unwrap(),
+            Plugin::with_active(master_dependent_master, &settings, active).unwrap(),
+            Plugin::new("Blank - Master Dependent.esp", &settings).unwrap(),
+            Plugin::with_active("Blank.esp", &settings, active).unwrap(),
+        ];
+
+        assert_eq!(
+            vec![master_dependent_master],
+            active_dependents(&plugins, "blank.esm")
+        );
+        assert!(active_dependents(&plugins, "Blank.esp").is_empty());
+    }
+}
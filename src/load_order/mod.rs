@@ -18,13 +18,26 @@
  */
 
 mod asterisk_based;
+mod dependency_graph;
+mod diff;
+mod fingerprint;
+mod history;
+mod master_diagnostics;
+mod mod_index;
 mod mutable;
 mod openmw;
+mod plugin_info;
 mod readable;
+mod repair;
+mod save_plan;
+mod slot_usage;
+mod snapshot;
 #[cfg(test)]
 mod tests;
 mod textfile_based;
 mod timestamp_based;
+mod transaction;
+mod validation;
 mod writable;
 
 use std::borrow::Cow;
@@ -34,11 +47,24 @@ use encoding_rs::WINDOWS_1252;
 use super::enums::Error;
 
 pub(crate) use self::asterisk_based::AsteriskBasedLoadOrder;
+pub use self::dependency_graph::DependencyGraph;
+pub use self::diff::{LoadOrderDiff, LoadOrderOperation, PluginMove};
+#[cfg(feature = "watcher")]
+pub(crate) use self::fingerprint::FileState;
+pub use self::master_diagnostics::MasterDiagnostics;
+pub use self::mod_index::ModIndex;
 pub(crate) use self::openmw::OpenMWLoadOrder;
+pub use self::plugin_info::PluginInfo;
 pub use self::readable::ReadableLoadOrder;
+pub use self::repair::{LoadOrderRepair, PluginRepair, RepairReason};
+pub use self::save_plan::{FileWrite, SavePlan, TimestampChange};
+pub use self::slot_usage::{PluginSlotUsage, SlotUsage};
+pub use self::snapshot::{LoadOrderSnapshot, PluginSnapshot};
 pub(crate) use self::textfile_based::TextfileBasedLoadOrder;
 pub(crate) use self::timestamp_based::TimestampBasedLoadOrder;
-pub use self::writable::WritableLoadOrder;
+pub use self::transaction::LoadOrderTransaction;
+pub use self::validation::ValidationIssue;
+pub use self::writable::{ActivationPolicy, WritableLoadOrder};
 
 fn strict_encode(string: &str) -> Result<Cow<'_, [u8]>, Error> {
     let (output, _, had_unmappable_chars) = WINDOWS_1252.encode(string);
@@ -0,0 +1,143 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::fmt;
+
+use super::writable::PluginCounts;
+use crate::plugin::Plugin;
+
+/// The index that the game gives an active plugin at runtime, which forms
+/// the prefix of the FormIDs of the records that the plugin adds.
+///
+/// Full plugins are indexed from `00`, medium plugins (Starfield only) share
+/// the `FD` index and light plugins share the `FE` index, with each medium
+/// and light plugin also getting its own index within that shared index.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[non_exhaustive]
+pub enum ModIndex {
+    Full(usize),
+    Medium(usize),
+    Light(usize),
+}
+
+impl fmt::Display for ModIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ModIndex::Full(index) => write!(f, "{index:02X}"),
+            ModIndex::Medium(index) => write!(f, "FD:{index:02X}"),
+            ModIndex::Light(index) => write!(f, "FE:{index:03X}"),
+        }
+    }
+}
+
+/// Get the mod indexes of the active plugins, in load order. Plugins for games
+/// that don't give plugins mod indexes are omitted.
+pub(super) fn active_plugin_mod_indexes(plugins: &[Plugin]) -> Vec<(&str, ModIndex)> {
+    let mut counts = PluginCounts::default();
+
+    plugins
+        .iter()
+        .filter(|p| p.is_active())
+        .filter_map(|p| counts.count_plugin(p).map(|i| (p.name(), i)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crate::enums::GameId;
+    use crate::tests::copy_to_test_dir;
+    use crate::GameSettings;
+
+    #[test]
+    fn mod_index_should_display_as_hex_with_the_game_prefix() {
+        assert_eq!("0A", ModIndex::Full(10).to_string());
+        assert_eq!("FD:0A", ModIndex::Medium(10).to_string());
+        assert_eq!("FE:00A", ModIndex::Light(10).to_string());
+        assert_eq!("FE:FFF", ModIndex::Light(4095).to_string());
+    }
+
+    #[test]
+    fn mod_indexes_should_count_full_medium_and_light_active_plugins_separately() {
+        let tmp_dir = tempdir().unwrap();
+        let game_dir = tmp_dir.path();
+        let settings =
+            GameSettings::with_local_path(GameId::Starfield, game_dir, &game_dir.join("local"))
+                .unwrap();
+
+        copy_to_test_dir("Blank.full.esm", "Blank.full.esm", &settings);
+        copy_to_test_dir("Blank.medium.esm", "Blank.medium.esm", &settings);
+        copy_to_test_dir("Blank.small.esm", "Blank.small.esm", &settings);
+        copy_to_test_dir("Blank.full.esm", "Blank - Inactive.esm", &settings);
+        copy_to_test_dir("Blank.esp", "Blank.esp", &settings);
+
+        let mut load_order = settings.into_load_order();
+        load_order.load().unwrap();
+        load_order
+            .set_load_order(&[
+                "Blank.full.esm",
+                "Blank.medium.esm",
+                "Blank.small.esm",
+                "Blank - Inactive.esm",
+                "Blank.esp",
+            ])
+            .unwrap();
+        load_order
+            .set_active_plugins(&[
+                "Blank.full.esm",
+                "Blank.medium.esm",
+                "Blank.small.esm",
+                "Blank.esp",
+            ])
+            .unwrap();
+
+        assert_eq!(
+            vec![
+                ("Blank.full.esm", ModIndex::Full(0)),
+                ("Blank.medium.esm", ModIndex::Medium(0)),
+                ("Blank.small.esm", ModIndex::Light(0)),
+                ("Blank.esp", ModIndex::Full(1)),
+            ],
+            load_order.mod_indexes()
+        );
+        assert_eq!(Some(ModIndex::Full(1)), load_order.mod_index("blank.esp"));
+        assert_eq!(None, load_order.mod_index("Blank - Inactive.esm"));
+    }
+
+    #[test]
+    fn mod_indexes_should_be_empty_for_games_without_formid_mod_indexes() {
+        let tmp_dir = tempdir().unwrap();
+        let game_dir = tmp_dir.path();
+        let settings =
+            GameSettings::with_local_path(GameId::Morrowind, game_dir, &game_dir.join("local"))
+                .unwrap();
+
+        copy_to_test_dir("Blank.esm", "Blank.esm", &settings);
+
+        let mut load_order = settings.into_load_order();
+        load_order.load().unwrap();
+        load_order.activate("Blank.esm").unwrap();
+
+        assert!(load_order.is_active("Blank.esm"));
+        assert!(load_order.mod_indexes().is_empty());
+        assert_eq!(None, load_order.mod_index("Blank.esm"));
+    }
+}
@@ -17,7 +17,7 @@
  * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
  */
 
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, HashSet};
 use std::mem;
 use std::path::{Path, PathBuf};
 
@@ -25,45 +25,41 @@ use encoding_rs::WINDOWS_1252;
 use rayon::prelude::*;
 use unicase::{eq, UniCase};
 
+use super::fingerprint::FileFingerprints;
+use super::history::EditHistory;
 use super::readable::{ReadableLoadOrder, ReadableLoadOrderBase};
+use super::validation::{find_load_order_issues, to_load_order_error, PluginEntry};
 use crate::enums::Error;
 use crate::game_settings::GameSettings;
-use crate::plugin::{trim_dot_ghost, ActiveState, Plugin};
+use crate::plugin::{trim_dot_ghost, ActiveState, Plugin, PluginCache};
 use crate::GameId;
 
 pub(super) trait MutableLoadOrder: ReadableLoadOrder + ReadableLoadOrderBase + Sync {
     fn plugins_mut(&mut self) -> &mut Vec<Plugin>;
 
-    fn find_plugin_mut(&mut self, plugin_name: &str) -> Option<&mut Plugin> {
-        self.plugins_mut()
-            .iter_mut()
-            .find(|p| p.name_matches(plugin_name))
+    /// Whether edits should skip checking that they give a valid load order
+    /// and don't exceed the active plugin limits, because the load order will
+    /// be validated once a batch of edits has been made.
+    fn defers_validation(&self) -> bool {
+        false
     }
 
-    fn max_active_full_plugins(&self) -> usize {
-        let has_active_light_plugin = if self.game_settings().id().supports_light_plugins() {
-            self.plugins()
-                .iter()
-                .any(|p| p.is_active() && p.is_light_plugin())
-        } else {
-            false
-        };
+    /// The history that edits made through the shared helpers are recorded
+    /// in, if this load order keeps one.
+    fn edit_history_mut(&mut self) -> Option<&mut EditHistory> {
+        None
+    }
 
-        let has_active_medium_plugin = if self.game_settings().id().supports_medium_plugins() {
-            self.plugins()
-                .iter()
-                .any(|p| p.is_active() && p.is_medium_plugin())
-        } else {
-            false
-        };
+    /// The fingerprints of the files that this load order was read from, if
+    /// it keeps them.
+    fn file_fingerprints_mut(&mut self) -> Option<&mut FileFingerprints> {
+        None
+    }
 
-        if has_active_light_plugin && has_active_medium_plugin {
-            253
-        } else if has_active_light_plugin || has_active_medium_plugin {
-            254
-        } else {
-            255
-        }
+    fn find_plugin_mut(&mut self, plugin_name: &str) -> Option<&mut Plugin> {
+        self.plugins_mut()
+            .iter_mut()
+            .find(|p| p.name_matches(plugin_name))
     }
 
     fn insert_position(&self, plugin: &Plugin) -> Option<usize> {
@@ -149,19 +145,13 @@ pub(super) trait MutableLoadOrder: ReadableLoadOrder + ReadableLoadOrderBase + S
     }
 
     fn replace_plugins(&mut self, plugin_names: &[&str]) -> Result<(), Error> {
-        let mut unique_plugin_names = HashSet::new();
-
-        let non_unique_plugin = plugin_names
-            .iter()
-            .find(|n| !unique_plugin_names.insert(UniCase::new(*n)));
-
-        if let Some(n) = non_unique_plugin {
-            return Err(Error::DuplicatePlugin((*n).to_owned()));
-        }
+        validate_plugin_names_are_unique(plugin_names)?;
 
         let mut plugins = map_to_plugins(self, plugin_names)?;
 
-        validate_load_order(&plugins, self.game_settings().early_loading_plugins())?;
+        if !self.defers_validation() {
+            validate_load_order(&plugins, self.game_settings().early_loading_plugins())?;
+        }
 
         mem::swap(&mut plugins, self.plugins_mut());
 
@@ -172,6 +162,7 @@ pub(super) trait MutableLoadOrder: ReadableLoadOrder + ReadableLoadOrderBase + S
         &mut self,
         defined_load_order: &[(String, bool)],
         installed_files: &[PathBuf],
+        cache: &PluginCache,
     ) {
         let plugins: Vec<_> = Self::total_insertion_order(
             defined_load_order,
@@ -188,7 +179,9 @@ pub(super) trait MutableLoadOrder: ReadableLoadOrder + ReadableLoadOrderBase + S
                 // once they've been loaded.
                 ActiveState::Inactive
             };
-            Plugin::with_active(&filename, self.game_settings(), active).ok()
+            cache
+                .get_or_load(&filename, self.game_settings(), active)
+                .ok()
         })
         .collect();
 
@@ -290,17 +283,22 @@ pub(super) fn filename_str(file_path: &Path) -> Option<&str> {
     file_path.file_name().and_then(|n| n.to_str())
 }
 
-pub(super) fn load_active_plugins<T, F>(load_order: &mut T, line_mapper: F) -> Result<(), Error>
+pub(super) fn load_active_plugins<T, F>(
+    load_order: &mut T,
+    file_fingerprints: &mut FileFingerprints,
+    line_mapper: F,
+) -> Result<(), Error>
 where
     T: MutableLoadOrder,
     F: Fn(&str) -> Option<String> + Send + Sync,
 {
     load_order.deactivate_all();
 
-    let plugin_names = read_plugin_names(
-        load_order.game_settings().active_plugins_file(),
-        line_mapper,
-    )?;
+    let plugin_names =
+        match file_fingerprints.read_file(load_order.game_settings().active_plugins_file())? {
+            Some(content) => decode_plugin_names(&content, line_mapper)?,
+            None => Vec::new(),
+        };
 
     for plugin_name in plugin_names {
         if let Some(plugin) = load_order.find_plugin_mut(&plugin_name) {
@@ -323,11 +321,21 @@ where
     let content =
         std::fs::read(file_path).map_err(|e| Error::IoError(file_path.to_path_buf(), e))?;
 
+    decode_plugin_names(&content, line_mapper)
+}
+
+/// Equivalent to [read_plugin_names], but for file content that has already
+/// been read.
+pub(super) fn decode_plugin_names<F, T>(content: &[u8], line_mapper: F) -> Result<Vec<T>, Error>
+where
+    F: FnMut(&str) -> Option<T> + Send + Sync,
+    T: Send,
+{
     // This should never fail, as although Windows-1252 has a few unused bytes
     // they get mapped to C1 control characters.
     let decoded_content = WINDOWS_1252
-        .decode_without_bom_handling_and_without_replacement(&content)
-        .ok_or_else(|| Error::DecodeError(content.clone()))?;
+        .decode_without_bom_handling_and_without_replacement(content)
+        .ok_or_else(|| Error::DecodeError(content.to_vec()))?;
 
     Ok(decoded_content.lines().filter_map(line_mapper).collect())
 }
@@ -345,6 +353,16 @@ pub(super) fn plugin_line_mapper(line: &str) -> Option<String> {
 /// function "hoists" such masters further up the load order to match that
 /// behaviour.
 pub(super) fn hoist_masters(plugins: &mut Vec<Plugin>) -> Result<(), Error> {
+    let from_to_map = find_masters_to_hoist(plugins)?;
+
+    move_elements(plugins, from_to_map);
+
+    Ok(())
+}
+
+/// Get the current position of each plugin that [hoist_masters] would move,
+/// mapped to the position it would be moved to.
+pub(super) fn find_masters_to_hoist(plugins: &[Plugin]) -> Result<BTreeMap<usize, usize>, Error> {
     // Store plugins' current positions and where they need to move to.
     // Use a BTreeMap so that if a plugin needs to move for more than one ESM,
     // it will move for the earlier one and so also satisfy the later one, and
@@ -372,40 +390,21 @@ pub(super) fn hoist_masters(plugins: &mut Vec<Plugin>) -> Result<(), Error> {
         }
     }
 
-    move_elements(plugins, from_to_map);
-
-    Ok(())
+    Ok(from_to_map)
 }
 
-fn validate_early_loader_positions(
-    plugins: &[Plugin],
-    early_loading_plugins: &[String],
-) -> Result<(), Error> {
-    // Check that all early loading plugins that are present load in
-    // their hardcoded order.
-    let mut missing_plugins_count = 0;
-    for (i, plugin_name) in early_loading_plugins.iter().enumerate() {
-        // Blueprint masters never actually load early, so it's as
-        // if they're missing.
-        match plugins
-            .iter()
-            .position(|p| !p.is_blueprint_master() && eq(p.name(), plugin_name))
-        {
-            Some(pos) => {
-                let expected_pos = i - missing_plugins_count;
-                if pos != expected_pos {
-                    return Err(Error::InvalidEarlyLoadingPluginPosition {
-                        name: plugin_name.clone(),
-                        pos,
-                        expected_pos,
-                    });
-                }
-            }
-            None => missing_plugins_count += 1,
-        }
-    }
+pub(super) fn validate_plugin_names_are_unique(plugin_names: &[&str]) -> Result<(), Error> {
+    let mut unique_plugin_names = HashSet::new();
 
-    Ok(())
+    let non_unique_plugin = plugin_names
+        .iter()
+        .find(|n| !unique_plugin_names.insert(UniCase::new(*n)));
+
+    if let Some(n) = non_unique_plugin {
+        Err(Error::DuplicatePlugin((*n).to_owned()))
+    } else {
+        Ok(())
+    }
 }
 
 fn generic_insert_position(plugins: &[Plugin], plugin: &Plugin) -> Option<usize> {
@@ -436,7 +435,7 @@ fn generic_insert_position(plugins: &[Plugin], plugin: &Plugin) -> Option<usize>
     })
 }
 
-fn to_plugin(
+pub(super) fn to_plugin(
     plugin_name: &str,
     existing_plugins: &[Plugin],
     game_settings: &GameSettings,
@@ -615,7 +614,7 @@ fn insert<T: MutableLoadOrder + ?Sized>(load_order: &mut T, plugin: Plugin) -> u
     }
 }
 
-fn move_elements<T>(vec: &mut Vec<T>, mut from_to_indices: BTreeMap<usize, usize>) {
+pub(super) fn move_elements<T>(vec: &mut Vec<T>, mut from_to_indices: BTreeMap<usize, usize>) {
     // Move elements around. Moving elements doesn't change from_index values,
     // as we're iterating from earliest index to latest, but to_index values can
     // become incorrect, e.g. (5, 2), (6, 3), (7, 1) will insert an element
@@ -641,126 +640,41 @@ fn get_plugin_to_insert_at<T: MutableLoadOrder + ?Sized>(
     plugin_name: &str,
     insert_position: usize,
 ) -> Result<Plugin, Error> {
+    let defers_validation = load_order.defers_validation();
+
     if let Some((index, plugin)) = load_order.find_plugin_and_index(plugin_name) {
-        load_order.validate_index(plugin, insert_position)?;
+        if !defers_validation {
+            load_order.validate_index(plugin, insert_position)?;
+        }
 
         Ok(load_order.plugins_mut().remove(index))
     } else {
         let plugin = Plugin::new(plugin_name, load_order.game_settings())?;
 
-        load_order.validate_index(&plugin, insert_position)?;
+        if !defers_validation {
+            load_order.validate_index(&plugin, insert_position)?;
+        }
 
         Ok(plugin)
     }
 }
 
-fn validate_load_order(plugins: &[Plugin], early_loading_plugins: &[String]) -> Result<(), Error> {
-    validate_early_loader_positions(plugins, early_loading_plugins)?;
-
-    validate_no_unhoisted_non_masters_before_masters(plugins)?;
-
-    validate_no_non_blueprint_plugins_after_blueprint_plugins(plugins)?;
-
-    validate_plugins_load_before_their_masters(plugins)?;
-
-    Ok(())
-}
-
-fn validate_no_unhoisted_non_masters_before_masters(plugins: &[Plugin]) -> Result<(), Error> {
-    let first_non_master_pos = match find_first_non_master_position(plugins) {
-        None => plugins.len(),
-        Some(x) => x,
-    };
-
-    // Ignore blueprint plugins because they load after non-masters.
-    let Some(last_master_pos) = plugins
-        .iter()
-        .rposition(|p| p.is_master_file() && !p.is_blueprint_master())
-    else {
-        return Ok(());
-    };
-
-    let mut plugin_names: HashSet<UniCase<String>> = HashSet::new();
-
-    // Add each plugin that isn't a master file to the hashset.
-    // When a master file is encountered, remove its masters from the hashset.
-    // If there are any plugins left in the hashset, they weren't hoisted there,
-    // so fail the check.
-    if first_non_master_pos < last_master_pos {
-        for plugin in plugins
-            .iter()
-            .skip(first_non_master_pos)
-            .take(last_master_pos - first_non_master_pos + 1)
-        {
-            if plugin.is_master_file() {
-                for master in plugin.masters()? {
-                    plugin_names.remove(&UniCase::new(master.clone()));
-                }
-
-                if let Some(n) = plugin_names.iter().next() {
-                    return Err(Error::NonMasterBeforeMaster {
-                        master: plugin.name().to_owned(),
-                        non_master: n.to_string(),
-                    });
-                }
-            } else {
-                plugin_names.insert(UniCase::new(plugin.name().to_owned()));
-            }
-        }
-    }
-
-    Ok(())
-}
-
-fn validate_no_non_blueprint_plugins_after_blueprint_plugins(
+pub(super) fn validate_load_order(
     plugins: &[Plugin],
+    early_loading_plugins: &[String],
 ) -> Result<(), Error> {
-    let first_blueprint_plugin = plugins
+    let plugins = plugins
         .iter()
         .enumerate()
-        .find(|(_, p)| p.is_blueprint_master());
-
-    if let Some((first_blueprint_pos, first_blueprint_plugin)) = first_blueprint_plugin {
-        let last_non_blueprint_pos = plugins.iter().rposition(|p| !p.is_blueprint_master());
-
-        if let Some(last_non_blueprint_pos) = last_non_blueprint_pos {
-            if last_non_blueprint_pos > first_blueprint_pos {
-                return Err(Error::InvalidBlueprintPluginPosition {
-                    name: first_blueprint_plugin.name().to_owned(),
-                    pos: first_blueprint_pos,
-                    expected_pos: last_non_blueprint_pos,
-                });
-            }
-        }
-    }
-
-    Ok(())
-}
-
-fn validate_plugins_load_before_their_masters(plugins: &[Plugin]) -> Result<(), Error> {
-    let mut plugins_map: HashMap<UniCase<String>, &Plugin> = HashMap::new();
-
-    for plugin in plugins.iter().rev() {
-        if plugin.is_master_file() {
-            if let Some(m) = plugin
-                .masters()?
-                .iter()
-                .find_map(|m| plugins_map.get(&UniCase::new(m.to_owned())))
-            {
-                // Don't error if a non-blueprint plugin depends on a blueprint plugin.
-                if plugin.is_blueprint_master() || !m.is_blueprint_master() {
-                    return Err(Error::UnrepresentedHoist {
-                        plugin: m.name().to_owned(),
-                        master: plugin.name().to_owned(),
-                    });
-                }
-            }
-        }
-
-        plugins_map.insert(UniCase::new(plugin.name().to_owned()), plugin);
-    }
-
-    Ok(())
+        .map(|(pos, plugin)| PluginEntry::new(pos, plugin))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut issues = Vec::new();
+    find_load_order_issues(&plugins, early_loading_plugins, &mut issues);
+
+    issues
+        .into_iter()
+        .find_map(to_load_order_error)
+        .map_or(Ok(()), Err)
 }
 
 fn implicitly_activate<T: MutableLoadOrder + ?Sized>(
@@ -784,10 +698,6 @@ fn implicitly_activate<T: MutableLoadOrder + ?Sized>(
     }
 }
 
-fn find_first_non_master_position(plugins: &[Plugin]) -> Option<usize> {
-    plugins.iter().position(|p| !p.is_master_file())
-}
-
 fn find_first_blueprint_master_position(plugins: &[Plugin]) -> Option<usize> {
     plugins.iter().position(Plugin::is_blueprint_master)
 }
@@ -803,7 +713,6 @@ mod tests {
     use super::*;
 
     use crate::load_order::tests::*;
-    use crate::load_order::writable::create_parent_dirs;
     use crate::tests::{copy_to_test_dir, NON_ASCII};
 
     use tempfile::tempdir;
@@ -2278,6 +2187,59 @@ mod tests {
         assert!(validate_load_order(&plugins, &[]).is_err());
     }
 
+    #[test]
+    fn validate_load_order_should_report_the_last_loading_master_that_loads_before_one_of_its_masters(
+    ) {
+        let tmp_dir = tempdir().unwrap();
+        let settings = prepare(GameId::SkyrimSE, tmp_dir.path()).game_settings;
+
+        copy_to_test_dir(
+            "Blank - Master Dependent.esm",
+            "Blank - Master Dependent.esm",
+            &settings,
+        );
+        copy_to_test_dir(
+            "Blank - Plugin Dependent.esp",
+            "Blank - Plugin Dependent.esm",
+            &settings,
+        );
+
+        let plugins = vec![
+            Plugin::new("Blank - Master Dependent.esm", &settings).unwrap(),
+            Plugin::new("Blank.esm", &settings).unwrap(),
+            Plugin::new("Blank - Plugin Dependent.esm", &settings).unwrap(),
+            Plugin::new("Blank.esp", &settings).unwrap(),
+        ];
+
+        match validate_load_order(&plugins, &[]).unwrap_err() {
+            Error::UnrepresentedHoist { plugin, master } => {
+                assert_eq!("Blank.esp", plugin);
+                assert_eq!("Blank - Plugin Dependent.esm", master);
+            }
+            e => panic!("Unexpected error type: {e:?}"),
+        }
+    }
+
+    #[test]
+    fn validate_load_order_should_succeed_if_a_non_master_loads_before_its_non_master_master() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = prepare(GameId::SkyrimSE, tmp_dir.path()).game_settings;
+
+        copy_to_test_dir(
+            "Blank - Plugin Dependent.esp",
+            "Blank - Plugin Dependent.esp",
+            &settings,
+        );
+
+        let plugins = vec![
+            Plugin::new("Blank.esm", &settings).unwrap(),
+            Plugin::new("Blank - Plugin Dependent.esp", &settings).unwrap(),
+            Plugin::new("Blank.esp", &settings).unwrap(),
+        ];
+
+        assert!(validate_load_order(&plugins, &[]).is_ok());
+    }
+
     #[test]
     fn validate_load_order_should_succeed_if_a_blueprint_plugin_loads_after_all_non_blueprint_plugins(
     ) {
@@ -2425,20 +2387,32 @@ mod tests {
     }
 
     #[test]
-    fn find_first_non_master_should_find_a_full_esp() {
+    fn validate_load_order_should_treat_a_full_esp_as_a_non_master() {
         let tmp_dir = tempdir().unwrap();
-        let plugins = prepare_plugins(tmp_dir.path(), "Blank.esp");
+        let mut plugins = prepare_plugins(tmp_dir.path(), "Blank.esp");
+        plugins.reverse();
 
-        let first_non_master = super::find_first_non_master_position(&plugins);
-        assert_eq!(1, first_non_master.unwrap());
+        match validate_load_order(&plugins, &[]).unwrap_err() {
+            Error::NonMasterBeforeMaster { master, non_master } => {
+                assert_eq!("Skyrim.esm", master);
+                assert_eq!("Blank.esp", non_master);
+            }
+            e => panic!("Unexpected error type: {e:?}"),
+        }
     }
 
     #[test]
-    fn find_first_non_master_should_find_a_light_flagged_esp() {
+    fn validate_load_order_should_treat_a_light_flagged_esp_as_a_non_master() {
         let tmp_dir = tempdir().unwrap();
-        let plugins = prepare_plugins(tmp_dir.path(), "Blank.esl");
+        let mut plugins = prepare_plugins(tmp_dir.path(), "Blank.esl");
+        plugins.reverse();
 
-        let first_non_master = super::find_first_non_master_position(&plugins);
-        assert_eq!(1, first_non_master.unwrap());
+        match validate_load_order(&plugins, &[]).unwrap_err() {
+            Error::NonMasterBeforeMaster { master, non_master } => {
+                assert_eq!("Skyrim.esm", master);
+                assert_eq!("Blank.esp", non_master);
+            }
+            e => panic!("Unexpected error type: {e:?}"),
+        }
     }
 }
@@ -1,25 +1,37 @@
-use std::{collections::HashSet, path::PathBuf};
+use std::{collections::HashSet, mem, path::PathBuf};
 
 use unicase::UniCase;
 
 use crate::{
     load_order::mutable::filename_str,
-    openmw_config::{non_user_additional_data_paths, read_active_plugin_names, write_openmw_cfg},
-    plugin::{iends_with_ascii, Plugin},
+    openmw_config::{
+        non_user_additional_data_paths, openmw_cfg_contents, parse_active_plugin_names,
+    },
+    plugin::{iends_with_ascii, Plugin, PluginCache},
     Error, GameId, GameSettings,
 };
 
 use super::{
+    fingerprint::FileFingerprints,
+    history::{redo, undo, EditHistory},
     mutable::MutableLoadOrder,
     readable::{ReadableLoadOrder, ReadableLoadOrderBase},
-    writable::{activate, add, deactivate, remove, set_active_plugins},
-    WritableLoadOrder,
+    save_plan::{FileWrite, SavePlan},
+    transaction::LoadOrderTransaction,
+    writable::{
+        activate, activate_many, activate_with_masters, add, deactivate,
+        deactivate_with_dependents, remove, restore_backup, save, set_active_plugins,
+        set_load_order, set_plugin_index, transaction,
+    },
+    ActivationPolicy, WritableLoadOrder,
 };
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub(crate) struct OpenMWLoadOrder {
     game_settings: GameSettings,
     plugins: Vec<Plugin>,
+    file_fingerprints: FileFingerprints,
+    history: EditHistory,
 }
 
 impl OpenMWLoadOrder {
@@ -27,13 +39,21 @@ impl OpenMWLoadOrder {
         Self {
             game_settings,
             plugins: Vec::new(),
+            file_fingerprints: FileFingerprints::default(),
+            history: EditHistory::default(),
         }
     }
 
-    fn read_from_active_plugins_file(&self) -> Result<Vec<(String, bool)>, Error> {
+    fn read_from_active_plugins_file(
+        &self,
+        file_fingerprints: &mut FileFingerprints,
+    ) -> Result<Vec<(String, bool)>, Error> {
         let path = self.game_settings().active_plugins_file();
+        let Some(content) = file_fingerprints.read_file(path)? else {
+            return Ok(Vec::new());
+        };
 
-        let active_plugin_tuples: Vec<_> = read_active_plugin_names(path)?
+        let active_plugin_tuples: Vec<_> = parse_active_plugin_names(path, &content)?
             .into_iter()
             .map(|v| (v, true))
             .collect();
@@ -162,12 +182,6 @@ impl ReadableLoadOrderBase for OpenMWLoadOrder {
     fn game_settings_base(&self) -> &GameSettings {
         &self.game_settings
     }
-}
-
-impl MutableLoadOrder for OpenMWLoadOrder {
-    fn plugins_mut(&mut self) -> &mut Vec<Plugin> {
-        &mut self.plugins
-    }
 
     fn max_active_full_plugins(&self) -> usize {
         // Stated as the limit in the FAQs here:
@@ -178,6 +192,20 @@ impl MutableLoadOrder for OpenMWLoadOrder {
         // <https://gitlab.com/OpenMW/openmw/-/blob/openmw-49-rc3/components/esm/formid.hpp?ref_type=tags#L16>
         0x7FFF_FFFE
     }
+}
+
+impl MutableLoadOrder for OpenMWLoadOrder {
+    fn plugins_mut(&mut self) -> &mut Vec<Plugin> {
+        &mut self.plugins
+    }
+
+    fn edit_history_mut(&mut self) -> Option<&mut EditHistory> {
+        Some(&mut self.history)
+    }
+
+    fn file_fingerprints_mut(&mut self) -> Option<&mut FileFingerprints> {
+        Some(&mut self.file_fingerprints)
+    }
 
     fn total_insertion_order(
         defined_load_order: &[(String, bool)],
@@ -232,19 +260,34 @@ impl WritableLoadOrder for OpenMWLoadOrder {
     fn load(&mut self) -> Result<(), Error> {
         self.plugins_mut().clear();
 
-        let plugin_tuples = self.read_from_active_plugins_file()?;
+        self.reload()
+    }
+
+    fn reload(&mut self) -> Result<(), Error> {
+        self.history.clear();
+
+        let cache = PluginCache::new(mem::take(self.plugins_mut()));
+
+        let mut file_fingerprints = FileFingerprints::default();
+        let plugin_tuples = self.read_from_active_plugins_file(&mut file_fingerprints)?;
         let paths = self.game_settings.find_plugins();
 
-        self.load_unique_plugins(&plugin_tuples, &paths);
+        self.load_unique_plugins(&plugin_tuples, &paths, &cache);
 
         self.add_implicitly_active_plugins()?;
 
         self.apply_load_order(&plugin_tuples);
 
+        self.file_fingerprints = file_fingerprints.finish(self)?;
+
         Ok(())
     }
 
     fn save(&mut self) -> Result<(), Error> {
+        save(self)
+    }
+
+    fn plan_save(&self) -> Result<SavePlan, Error> {
         let read_only_data_paths: HashSet<_> =
             non_user_additional_data_paths(self.game_settings.game_path())?
                 .into_iter()
@@ -261,9 +304,12 @@ impl WritableLoadOrder for OpenMWLoadOrder {
             .collect();
 
         let cfg_path = self.game_settings.active_plugins_file();
-        write_openmw_cfg(cfg_path, &data_paths, &self.active_plugin_names())?;
+        let contents = openmw_cfg_contents(cfg_path, &data_paths, &self.active_plugin_names())?;
 
-        Ok(())
+        Ok(SavePlan::new(
+            vec![FileWrite::new(cfg_path.clone(), contents)],
+            Vec::new(),
+        ))
     }
 
     fn add(&mut self, plugin_name: &str) -> Result<usize, Error> {
@@ -275,11 +321,11 @@ impl WritableLoadOrder for OpenMWLoadOrder {
     }
 
     fn set_load_order(&mut self, plugin_names: &[&str]) -> Result<(), Error> {
-        self.replace_plugins(plugin_names)
+        set_load_order(self, plugin_names)
     }
 
     fn set_plugin_index(&mut self, plugin_name: &str, position: usize) -> Result<usize, Error> {
-        MutableLoadOrder::set_plugin_index(self, plugin_name, position)
+        set_plugin_index(self, plugin_name, position)
     }
 
     fn is_self_consistent(&self) -> Result<bool, Error> {
@@ -294,13 +340,64 @@ impl WritableLoadOrder for OpenMWLoadOrder {
         activate(self, plugin_name)
     }
 
+    fn activate_with_masters(&mut self, plugin_name: &str) -> Result<(), Error> {
+        activate_with_masters(self, plugin_name)
+    }
+
     fn deactivate(&mut self, plugin_name: &str) -> Result<(), Error> {
         deactivate(self, plugin_name)
     }
 
+    fn deactivate_with_dependents(&mut self, plugin_name: &str) -> Result<(), Error> {
+        deactivate_with_dependents(self, plugin_name)
+    }
+
     fn set_active_plugins(&mut self, active_plugin_names: &[&str]) -> Result<(), Error> {
         set_active_plugins(self, active_plugin_names)
     }
+
+    fn activate_many(
+        &mut self,
+        plugin_names: &[&str],
+        policy: ActivationPolicy,
+    ) -> Result<Vec<String>, Error> {
+        activate_many(self, plugin_names, policy)
+    }
+
+    fn transaction(
+        &mut self,
+        edits: &mut dyn FnMut(&mut LoadOrderTransaction<'_>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        transaction(self, edits)
+    }
+
+    fn restore_backup(&mut self, backup_id: &str) -> Result<(), Error> {
+        restore_backup(self, backup_id)
+    }
+
+    fn is_stale(&self) -> Result<bool, Error> {
+        self.file_fingerprints.is_stale(self.game_settings())
+    }
+
+    fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    fn undo(&mut self) -> Result<bool, Error> {
+        undo(self)
+    }
+
+    fn redo(&mut self) -> Result<bool, Error> {
+        redo(self)
+    }
 }
 
 #[cfg(test)]
@@ -331,6 +428,8 @@ mod tests {
         OpenMWLoadOrder {
             game_settings,
             plugins: Vec::new(),
+            file_fingerprints: FileFingerprints::default(),
+            history: EditHistory::default(),
         }
     }
 
@@ -364,6 +463,8 @@ mod tests {
         let mut load_order = OpenMWLoadOrder {
             game_settings: game_settings_for_test(GameId::OpenMW, tmp_dir.path()),
             plugins: Vec::new(),
+            file_fingerprints: FileFingerprints::default(),
+            history: EditHistory::default(),
         };
 
         load_order.load().unwrap();
@@ -602,6 +703,36 @@ mod tests {
         assert_eq!(active_plugin_names, load_order.plugin_names().as_slice());
     }
 
+    #[test]
+    fn plan_save_should_return_the_openmw_cfg_that_save_writes() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+        let cfg_path = cfg_path(tmp_dir.path());
+        let plugin = Plugin::with_active(
+            "Blank.esp",
+            load_order.game_settings(),
+            ActiveState::ExplicitlyActive,
+        )
+        .unwrap();
+        load_order.plugins.push(plugin);
+        let old_contents = std::fs::read(&cfg_path).ok();
+
+        let plan = load_order.plan_save().unwrap();
+
+        assert_eq!(1, plan.file_writes().len());
+        assert_eq!(cfg_path, plan.file_writes()[0].path());
+        assert!(plan.timestamp_changes().is_empty());
+        assert_eq!(old_contents, std::fs::read(&cfg_path).ok());
+
+        load_order.save().unwrap();
+
+        assert_eq!(
+            plan.file_writes()[0].contents(),
+            std::fs::read(&cfg_path).unwrap()
+        );
+        assert_eq!(vec!["content=Blank.esp"], read_lines(&cfg_path));
+    }
+
     #[test]
     fn save_should_write_active_openmw_plugin_positions() {
         let tmp_dir = tempdir().unwrap();
@@ -711,4 +842,24 @@ mod tests {
             assert!(load_order.is_active(plugin));
         }
     }
+
+    #[test]
+    fn transaction_should_allow_more_than_255_plugins_to_be_active() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+
+        let plugins = prepare_bulk_full_plugins(&mut load_order);
+        load_order
+            .transaction(&mut |transaction| {
+                for plugin in &plugins[..260] {
+                    transaction.activate(plugin)?;
+                }
+                Ok(())
+            })
+            .unwrap();
+
+        for plugin in &plugins[..260] {
+            assert!(load_order.is_active(plugin));
+        }
+    }
 }
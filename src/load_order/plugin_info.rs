@@ -0,0 +1,182 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::enums::Error;
+use crate::game_settings::GameSettings;
+use crate::plugin::{ActiveState, Plugin};
+
+/// A read-only view of a plugin in a load order, giving access to the data
+/// that was read from its file when the load order was loaded.
+#[derive(Clone, Copy, Debug)]
+pub struct PluginInfo<'a> {
+    plugin: &'a Plugin,
+    game_settings: &'a GameSettings,
+}
+
+impl<'a> PluginInfo<'a> {
+    pub(super) fn new(plugin: &'a Plugin, game_settings: &'a GameSettings) -> Self {
+        Self {
+            plugin,
+            game_settings,
+        }
+    }
+
+    pub fn name(&self) -> &'a str {
+        self.plugin.name()
+    }
+
+    /// The path that the plugin was loaded from, which may have a `.ghost`
+    /// file extension.
+    pub fn path(&self) -> &'a Path {
+        self.plugin.path()
+    }
+
+    /// The directory that the plugin was loaded from, which is either the
+    /// game's plugins directory or one of its additional plugins directories.
+    pub fn directory(&self) -> Option<&'a Path> {
+        self.plugin.path().parent()
+    }
+
+    pub fn modification_time(&self) -> SystemTime {
+        self.plugin.modification_time()
+    }
+
+    pub fn active_state(&self) -> ActiveState {
+        self.plugin.active_state()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.plugin.is_active()
+    }
+
+    /// Whether the game always activates the plugin, whether or not it's
+    /// listed as active in the active plugins file.
+    pub fn is_implicitly_active(&self) -> bool {
+        self.game_settings.is_implicitly_active(self.plugin.name())
+    }
+
+    pub fn loads_early(&self) -> bool {
+        self.game_settings.loads_early(self.plugin.name())
+    }
+
+    pub fn is_ghosted(&self) -> bool {
+        self.plugin.is_ghosted()
+    }
+
+    pub fn is_master_file(&self) -> bool {
+        self.plugin.is_master_file()
+    }
+
+    pub fn is_light_plugin(&self) -> bool {
+        self.plugin.is_light_plugin()
+    }
+
+    pub fn is_medium_plugin(&self) -> bool {
+        self.plugin.is_medium_plugin()
+    }
+
+    pub fn is_blueprint_plugin(&self) -> bool {
+        self.plugin.is_blueprint_plugin()
+    }
+
+    pub fn masters(&self) -> Result<Vec<String>, Error> {
+        self.plugin.masters()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crate::enums::GameId;
+    use crate::tests::copy_to_test_dir;
+
+    #[test]
+    fn plugin_info_should_expose_plugin_and_game_data() {
+        let tmp_dir = tempdir().unwrap();
+        let game_dir = tmp_dir.path();
+        let settings =
+            GameSettings::with_local_path(GameId::Oblivion, game_dir, &game_dir.join("local"))
+                .unwrap();
+
+        copy_to_test_dir("Blank.esm", "Blank.esm", &settings);
+        copy_to_test_dir(
+            "Blank - Master Dependent.esp",
+            "Blank - Master Dependent.esp.ghost",
+            &settings,
+        );
+
+        let mut load_order = settings.into_load_order();
+        load_order.load().unwrap();
+
+        let plugins_dir = load_order.game_settings().plugins_directory();
+
+        let info = load_order.plugin_info("Blank.esm").unwrap();
+        assert_eq!("Blank.esm", info.name());
+        assert_eq!(plugins_dir.join("Blank.esm"), info.path());
+        assert_eq!(Some(plugins_dir.as_path()), info.directory());
+        assert!(info.is_master_file());
+        assert!(!info.is_ghosted());
+        assert!(!info.is_active());
+        assert!(!info.is_implicitly_active());
+        assert!(!info.loads_early());
+        assert!(info.masters().unwrap().is_empty());
+
+        let info = load_order
+            .plugin_info("Blank - Master Dependent.esp")
+            .unwrap();
+        assert_eq!("Blank - Master Dependent.esp", info.name());
+        assert!(info.is_ghosted());
+        assert!(!info.is_master_file());
+        assert_eq!(vec!["Blank.esm"], info.masters().unwrap());
+    }
+
+    #[test]
+    fn plugin_info_should_report_implicitly_active_and_early_loading_plugins() {
+        let tmp_dir = tempdir().unwrap();
+        let game_dir = tmp_dir.path();
+        let settings =
+            GameSettings::with_local_path(GameId::SkyrimSE, game_dir, &game_dir.join("local"))
+                .unwrap();
+
+        copy_to_test_dir("Blank.esm", "Skyrim.esm", &settings);
+        copy_to_test_dir("Blank.esp", "Blank.esp", &settings);
+
+        let mut load_order = settings.into_load_order();
+        load_order.load().unwrap();
+
+        let info = load_order.plugin_info("skyrim.esm").unwrap();
+        assert!(info.is_implicitly_active());
+        assert!(info.loads_early());
+        assert!(info.is_active());
+
+        assert!(load_order.plugin_info("missing.esp").is_none());
+
+        let names: Vec<_> = load_order
+            .plugins_info()
+            .iter()
+            .map(PluginInfo::name)
+            .collect();
+        assert_eq!(load_order.plugin_names(), names);
+    }
+}
@@ -16,6 +16,17 @@
  * You should have received a copy of the GNU General Public License
  * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
  */
+use super::dependency_graph::DependencyGraph;
+use super::master_diagnostics::{active_dependents, diagnose_masters, MasterDiagnostics};
+use super::mod_index::{active_plugin_mod_indexes, ModIndex};
+use super::mutable::{to_plugin, validate_plugin_names_are_unique};
+use super::plugin_info::PluginInfo;
+use super::repair::{repair_load_order, LoadOrderRepair};
+use super::slot_usage::PluginSlotUsage;
+use super::snapshot::{LoadOrderSnapshot, PluginSnapshot};
+use super::validation::ValidationIssue;
+use super::writable::{plugin_slot_usage, validate};
+use crate::enums::Error;
 use crate::game_settings::GameSettings;
 use crate::plugin::Plugin;
 
@@ -34,6 +45,12 @@ pub trait ReadableLoadOrderBase {
             .enumerate()
             .find(|(_, p)| p.name_matches(plugin_name))
     }
+
+    /// The maximum number of active plugins that aren't light or medium
+    /// plugins, if no light or medium plugins are active.
+    fn max_active_full_plugins(&self) -> usize {
+        255
+    }
 }
 
 pub trait ReadableLoadOrder {
@@ -48,6 +65,61 @@ pub trait ReadableLoadOrder {
     fn active_plugin_names(&self) -> Vec<&str>;
 
     fn is_active(&self, plugin_name: &str) -> bool;
+
+    /// Capture the load order and the active state of each plugin in it.
+    fn snapshot(&self) -> LoadOrderSnapshot;
+
+    /// Get information about the given plugin, if it's in the load order.
+    fn plugin_info(&self, plugin_name: &str) -> Option<PluginInfo<'_>>;
+
+    /// Get information about all the plugins in the load order, in load
+    /// order.
+    fn plugins_info(&self) -> Vec<PluginInfo<'_>>;
+
+    /// Get the mod index that the game gives each active plugin, in load
+    /// order. Morrowind and OpenMW don't give plugins mod indexes, so this is
+    /// empty for them.
+    fn mod_indexes(&self) -> Vec<(&str, ModIndex)>;
+
+    /// Get the mod index that the game gives the given plugin, or `None` if
+    /// the plugin isn't active or the game doesn't give plugins mod indexes.
+    fn mod_index(&self, plugin_name: &str) -> Option<ModIndex>;
+
+    /// Get the masters of each active plugin that aren't installed or aren't
+    /// active, in load order.
+    fn master_diagnostics(&self) -> Result<Vec<MasterDiagnostics>, Error>;
+
+    /// Get the active plugins that have the given plugin as a master,
+    /// directly or indirectly, in load order. These are the plugins that
+    /// [WritableLoadOrder::deactivate_with_dependents](super::WritableLoadOrder::deactivate_with_dependents)
+    /// would also deactivate.
+    fn active_dependents(&self, plugin_name: &str) -> Vec<&str>;
+
+    /// Build a graph of the master dependencies between the plugins in the
+    /// load order.
+    fn dependency_graph(&self) -> Result<DependencyGraph, Error>;
+
+    /// Get the closest valid load order to the given load order, as would be
+    /// accepted by [WritableLoadOrder::set_load_order](super::WritableLoadOrder::set_load_order),
+    /// along with what was moved to get there and why. Masters are moved
+    /// before non-masters and hoisted as the game would hoist them, early
+    /// loading plugins are moved to their hardcoded positions and blueprint
+    /// masters are moved to the end.
+    ///
+    /// Fails if the given plugin names contain duplicates or any of the
+    /// plugins can't be loaded.
+    fn repair_load_order(&self, plugin_names: &[&str]) -> Result<LoadOrderRepair, Error>;
+
+    /// Check if the given load order would be accepted by
+    /// [WritableLoadOrder::set_load_order](super::WritableLoadOrder::set_load_order),
+    /// reporting every problem found instead of stopping at the first.
+    /// Plugins that are in the current load order keep their active states
+    /// when checking the active plugin limits. The load order is not changed.
+    fn validate(&self, plugin_names: &[&str]) -> Vec<ValidationIssue>;
+
+    /// Get how many active plugin slots of each scale are used and how many
+    /// remain, given the plugins that are currently active.
+    fn plugin_slot_usage(&self) -> PluginSlotUsage;
 }
 
 impl<T: ReadableLoadOrderBase> ReadableLoadOrder for T {
@@ -80,6 +152,72 @@ impl<T: ReadableLoadOrderBase> ReadableLoadOrder for T {
     fn is_active(&self, plugin_name: &str) -> bool {
         self.find_plugin(plugin_name).is_some_and(Plugin::is_active)
     }
+
+    fn snapshot(&self) -> LoadOrderSnapshot {
+        let plugins = self
+            .plugins()
+            .iter()
+            .map(|p| PluginSnapshot::new(p.name().to_owned(), p.active_state()))
+            .collect();
+
+        LoadOrderSnapshot::new(self.game_settings().id(), plugins)
+    }
+
+    fn plugin_info(&self, plugin_name: &str) -> Option<PluginInfo<'_>> {
+        self.find_plugin(plugin_name)
+            .map(|p| PluginInfo::new(p, self.game_settings()))
+    }
+
+    fn plugins_info(&self) -> Vec<PluginInfo<'_>> {
+        self.plugins()
+            .iter()
+            .map(|p| PluginInfo::new(p, self.game_settings()))
+            .collect()
+    }
+
+    fn mod_indexes(&self) -> Vec<(&str, ModIndex)> {
+        active_plugin_mod_indexes(self.plugins())
+    }
+
+    fn mod_index(&self, plugin_name: &str) -> Option<ModIndex> {
+        let plugin = self.find_plugin(plugin_name)?;
+
+        self.mod_indexes()
+            .into_iter()
+            .find(|(name, _)| *name == plugin.name())
+            .map(|(_, index)| index)
+    }
+
+    fn master_diagnostics(&self) -> Result<Vec<MasterDiagnostics>, Error> {
+        diagnose_masters(self.plugins())
+    }
+
+    fn active_dependents(&self, plugin_name: &str) -> Vec<&str> {
+        active_dependents(self.plugins(), plugin_name)
+    }
+
+    fn dependency_graph(&self) -> Result<DependencyGraph, Error> {
+        DependencyGraph::new(self.plugins())
+    }
+
+    fn repair_load_order(&self, plugin_names: &[&str]) -> Result<LoadOrderRepair, Error> {
+        validate_plugin_names_are_unique(plugin_names)?;
+
+        let plugins = plugin_names
+            .iter()
+            .map(|n| to_plugin(n, self.plugins(), self.game_settings()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        repair_load_order(plugins, self.game_settings().early_loading_plugins())
+    }
+
+    fn validate(&self, plugin_names: &[&str]) -> Vec<ValidationIssue> {
+        validate(self, plugin_names)
+    }
+
+    fn plugin_slot_usage(&self) -> PluginSlotUsage {
+        plugin_slot_usage(self)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,403 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::collections::{HashMap, HashSet};
+
+use unicase::{eq, UniCase};
+
+use super::mutable::{find_masters_to_hoist, move_elements, validate_load_order};
+use crate::enums::Error;
+use crate::plugin::Plugin;
+
+/// The reason that a plugin was moved when repairing a load order.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum RepairReason {
+    /// The plugin is an early loader, so was moved to its hardcoded position.
+    EarlyLoader,
+    /// The plugin is a master file, so was moved before non-master files.
+    MasterFile,
+    /// The plugin is a blueprint master, so was moved after all other plugins.
+    BlueprintMaster,
+    /// The plugin is a master of the named master file, so was hoisted to load
+    /// before it.
+    HoistedByMaster(String),
+}
+
+/// A plugin that was moved when repairing a load order, and why.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PluginRepair {
+    name: String,
+    old_index: usize,
+    new_index: usize,
+    reason: RepairReason,
+}
+
+impl PluginRepair {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The plugin's index in the load order that was repaired.
+    pub fn old_index(&self) -> usize {
+        self.old_index
+    }
+
+    /// The plugin's index in the repaired load order.
+    pub fn new_index(&self) -> usize {
+        self.new_index
+    }
+
+    pub fn reason(&self) -> &RepairReason {
+        &self.reason
+    }
+}
+
+/// A valid load order derived from a requested load order, and the moves
+/// that were needed to get from one to the other.
+///
+/// A plugin may be moved more than once for different reasons, in which case
+/// it appears in [LoadOrderRepair::repairs] once for each reason, in the order
+/// that the moves were made.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoadOrderRepair {
+    plugin_names: Vec<String>,
+    repairs: Vec<PluginRepair>,
+}
+
+impl LoadOrderRepair {
+    pub fn plugin_names(&self) -> Vec<&str> {
+        self.plugin_names.iter().map(String::as_str).collect()
+    }
+
+    pub fn repairs(&self) -> &[PluginRepair] {
+        &self.repairs
+    }
+
+    /// Check if the requested load order was already valid.
+    pub fn is_empty(&self) -> bool {
+        self.repairs.is_empty()
+    }
+}
+
+/// Reorder the given plugins so that they pass [validate_load_order], moving
+/// as few plugins as the load order rules allow. If the plugins are already
+/// in a valid order, they're left as they are.
+pub(super) fn repair_load_order(
+    plugins: Vec<Plugin>,
+    early_loading_plugins: &[String],
+) -> Result<LoadOrderRepair, Error> {
+    let requested_names: Vec<String> = plugins.iter().map(|p| p.name().to_owned()).collect();
+
+    if validate_load_order(&plugins, early_loading_plugins).is_ok() {
+        return Ok(LoadOrderRepair {
+            plugin_names: requested_names,
+            repairs: Vec::new(),
+        });
+    }
+
+    let mut moves = Vec::new();
+
+    let plugins = move_masters_before_non_masters(plugins, &mut moves)?;
+    let plugins = move_early_loaders_to_start(plugins, early_loading_plugins, &mut moves);
+    let mut plugins = move_blueprint_masters_to_end(plugins, &mut moves);
+
+    let from_to_map = find_masters_to_hoist(&plugins)?;
+    for (from, to) in &from_to_map {
+        if let (Some(plugin), Some(master)) = (plugins.get(*from), plugins.get(*to)) {
+            moves.push((
+                plugin.name().to_owned(),
+                RepairReason::HoistedByMaster(master.name().to_owned()),
+            ));
+        }
+    }
+    move_elements(&mut plugins, from_to_map);
+
+    // The rules above should always produce a valid load order, but check
+    // in case they don't so that an invalid load order is never returned.
+    validate_load_order(&plugins, early_loading_plugins)?;
+
+    let plugin_names: Vec<String> = plugins.iter().map(|p| p.name().to_owned()).collect();
+
+    // A plugin may be moved for the same reason in more than one step, e.g.
+    // a non-master kept with the masters and then hoisted above its
+    // dependent, so only report each reason once.
+    let mut unique_moves = HashSet::new();
+    let repairs = moves
+        .into_iter()
+        .filter(|m| unique_moves.insert(m.clone()))
+        .filter_map(|(name, reason)| {
+            let old_index = requested_names.iter().position(|n| *n == name)?;
+            let new_index = plugin_names.iter().position(|n| *n == name)?;
+            Some(PluginRepair {
+                name,
+                old_index,
+                new_index,
+                reason,
+            })
+        })
+        .collect();
+
+    Ok(LoadOrderRepair {
+        plugin_names,
+        repairs,
+    })
+}
+
+fn move_masters_before_non_masters(
+    plugins: Vec<Plugin>,
+    moves: &mut Vec<(String, RepairReason)>,
+) -> Result<Vec<Plugin>, Error> {
+    // Non-masters that are masters of a master file will get hoisted above
+    // that master anyway, so leave them with the masters instead of moving
+    // them down only to move them back up again.
+    let mut hoisted_plugins: HashMap<UniCase<String>, String> = HashMap::new();
+    for plugin in &plugins {
+        if plugin.is_master_file() && !plugin.is_blueprint_master() {
+            for master in plugin.masters()? {
+                hoisted_plugins
+                    .entry(UniCase::new(master))
+                    .or_insert_with(|| plugin.name().to_owned());
+            }
+        }
+    }
+
+    let mut masters = Vec::new();
+    let mut non_masters = Vec::new();
+    for plugin in plugins {
+        let reason = if plugin.is_blueprint_master() {
+            None
+        } else if plugin.is_master_file() {
+            Some(RepairReason::MasterFile)
+        } else {
+            hoisted_plugins
+                .get(&UniCase::new(plugin.name().to_owned()))
+                .map(|m| RepairReason::HoistedByMaster(m.clone()))
+        };
+
+        match reason {
+            Some(reason) => {
+                if !non_masters.is_empty() {
+                    moves.push((plugin.name().to_owned(), reason));
+                }
+                masters.push(plugin);
+            }
+            None => non_masters.push(plugin),
+        }
+    }
+
+    masters.append(&mut non_masters);
+
+    Ok(masters)
+}
+
+fn move_early_loaders_to_start(
+    mut plugins: Vec<Plugin>,
+    early_loading_plugins: &[String],
+    moves: &mut Vec<(String, RepairReason)>,
+) -> Vec<Plugin> {
+    let mut early_loaders = Vec::new();
+    for early_loader in early_loading_plugins {
+        // Blueprint masters never actually load early.
+        if let Some(pos) = plugins
+            .iter()
+            .position(|p| !p.is_blueprint_master() && eq(p.name(), early_loader.as_str()))
+        {
+            let plugin = plugins.remove(pos);
+            if pos != 0 {
+                moves.push((plugin.name().to_owned(), RepairReason::EarlyLoader));
+            }
+            early_loaders.push(plugin);
+        }
+    }
+
+    early_loaders.append(&mut plugins);
+
+    early_loaders
+}
+
+fn move_blueprint_masters_to_end(
+    plugins: Vec<Plugin>,
+    moves: &mut Vec<(String, RepairReason)>,
+) -> Vec<Plugin> {
+    let last_non_blueprint_pos = plugins.iter().rposition(|p| !p.is_blueprint_master());
+
+    let (mut non_blueprint_plugins, mut blueprint_masters): (Vec<_>, Vec<_>) = plugins
+        .into_iter()
+        .enumerate()
+        .partition(|(_, p)| !p.is_blueprint_master());
+
+    for (index, plugin) in &blueprint_masters {
+        if last_non_blueprint_pos.is_some_and(|pos| *index < pos) {
+            moves.push((plugin.name().to_owned(), RepairReason::BlueprintMaster));
+        }
+    }
+
+    non_blueprint_plugins.append(&mut blueprint_masters);
+
+    non_blueprint_plugins.into_iter().map(|(_, p)| p).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use crate::enums::GameId;
+    use crate::game_settings::GameSettings;
+    use crate::load_order::tests::{game_settings_for_test, mock_game_files, set_blueprint_flag};
+    use crate::tests::copy_to_test_dir;
+
+    fn prepare(game_id: GameId, game_dir: &Path) -> GameSettings {
+        let mut settings = game_settings_for_test(game_id, game_dir);
+        mock_game_files(&mut settings);
+        settings
+    }
+
+    fn reasons(repair: &LoadOrderRepair) -> Vec<(&str, usize, usize, RepairReason)> {
+        repair
+            .repairs()
+            .iter()
+            .map(|r| (r.name(), r.old_index(), r.new_index(), r.reason().clone()))
+            .collect()
+    }
+
+    #[test]
+    fn repair_load_order_should_not_change_a_valid_load_order() {
+        let tmp_dir = tempdir().unwrap();
+        let load_order = prepare(GameId::Oblivion, tmp_dir.path()).into_load_order();
+
+        let plugin_names = ["Blank.esm", "Blank - Different.esp", "Blank.esp"];
+        let repair = load_order.repair_load_order(&plugin_names).unwrap();
+
+        assert_eq!(plugin_names.to_vec(), repair.plugin_names());
+        assert!(repair.is_empty());
+    }
+
+    #[test]
+    fn repair_load_order_should_move_masters_before_non_masters() {
+        let tmp_dir = tempdir().unwrap();
+        let load_order = prepare(GameId::Oblivion, tmp_dir.path()).into_load_order();
+
+        let repair = load_order
+            .repair_load_order(&["Blank.esp", "Blank.esm", "Blank - Different.esp"])
+            .unwrap();
+
+        assert_eq!(
+            vec!["Blank.esm", "Blank.esp", "Blank - Different.esp"],
+            repair.plugin_names()
+        );
+        assert_eq!(
+            vec![("Blank.esm", 1, 0, RepairReason::MasterFile)],
+            reasons(&repair)
+        );
+    }
+
+    #[test]
+    fn repair_load_order_should_move_early_loaders_to_their_hardcoded_positions() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = prepare(GameId::SkyrimSE, tmp_dir.path());
+        copy_to_test_dir("Blank.esm", "Skyrim.esm", &settings);
+        copy_to_test_dir("Blank.esm", "Update.esm", &settings);
+        let load_order = settings.into_load_order();
+
+        let repair = load_order
+            .repair_load_order(&["Update.esm", "Blank.esm", "Skyrim.esm", "Blank.esp"])
+            .unwrap();
+
+        assert_eq!(
+            vec!["Skyrim.esm", "Update.esm", "Blank.esm", "Blank.esp"],
+            repair.plugin_names()
+        );
+        assert_eq!(
+            vec![("Skyrim.esm", 2, 0, RepairReason::EarlyLoader)],
+            reasons(&repair)
+        );
+    }
+
+    #[test]
+    fn repair_load_order_should_hoist_non_masters_that_masters_depend_on() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = prepare(GameId::SkyrimSE, tmp_dir.path());
+        let master = "Blank - Plugin Dependent.esm";
+        copy_to_test_dir("Blank - Plugin Dependent.esp", master, &settings);
+        let load_order = settings.into_load_order();
+
+        let repair = load_order
+            .repair_load_order(&["Blank.esm", master, "Blank - Different.esp", "Blank.esp"])
+            .unwrap();
+
+        assert_eq!(
+            vec!["Blank.esm", "Blank.esp", master, "Blank - Different.esp"],
+            repair.plugin_names()
+        );
+        assert_eq!(
+            vec![(
+                "Blank.esp",
+                3,
+                1,
+                RepairReason::HoistedByMaster(master.to_owned())
+            )],
+            reasons(&repair)
+        );
+    }
+
+    #[test]
+    fn repair_load_order_should_move_blueprint_masters_to_the_end() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = prepare(GameId::Starfield, tmp_dir.path());
+        let blueprint_master = "Blank.medium.esm";
+        set_blueprint_flag(
+            GameId::Starfield,
+            &settings.plugins_directory().join(blueprint_master),
+            true,
+        )
+        .unwrap();
+        let load_order = settings.into_load_order();
+
+        let repair = load_order
+            .repair_load_order(&["Blank.full.esm", blueprint_master, "Blank.esp"])
+            .unwrap();
+
+        assert_eq!(
+            vec!["Blank.full.esm", "Blank.esp", blueprint_master],
+            repair.plugin_names()
+        );
+        assert_eq!(
+            vec![(blueprint_master, 1, 2, RepairReason::BlueprintMaster)],
+            reasons(&repair)
+        );
+    }
+
+    #[test]
+    fn repair_load_order_should_error_if_given_duplicate_plugins() {
+        let tmp_dir = tempdir().unwrap();
+        let load_order = prepare(GameId::Oblivion, tmp_dir.path()).into_load_order();
+
+        match load_order.repair_load_order(&["Blank.esm", "blank.esm"]) {
+            Err(Error::DuplicatePlugin(name)) => assert_eq!("blank.esm", name),
+            other => panic!("Expected a duplicate plugin error, got {other:?}"),
+        }
+    }
+}
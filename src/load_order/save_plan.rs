@@ -0,0 +1,179 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::fs::metadata;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::mutable::MutableLoadOrder;
+use crate::atomic_write::write_files_atomically;
+use crate::enums::Error;
+use crate::plugin::Plugin;
+
+/// A file that saving a load order would write, and the contents it would be
+/// given.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct FileWrite {
+    path: PathBuf,
+    contents: Vec<u8>,
+}
+
+impl FileWrite {
+    pub(super) fn new(path: PathBuf, contents: Vec<u8>) -> Self {
+        Self { path, contents }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The bytes that would be written, in the file's encoding.
+    pub fn contents(&self) -> &[u8] {
+        &self.contents
+    }
+}
+
+/// A plugin file modification time that saving a load order would change.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct TimestampChange {
+    plugin_name: String,
+    path: PathBuf,
+    old_time: SystemTime,
+    new_time: SystemTime,
+}
+
+impl TimestampChange {
+    pub fn plugin_name(&self) -> &str {
+        &self.plugin_name
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The plugin file's current modification time.
+    pub fn old_time(&self) -> SystemTime {
+        self.old_time
+    }
+
+    pub fn new_time(&self) -> SystemTime {
+        self.new_time
+    }
+}
+
+/// The changes that [WritableLoadOrder::save](super::WritableLoadOrder::save)
+/// would make to files on disk.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct SavePlan {
+    file_writes: Vec<FileWrite>,
+    timestamp_changes: Vec<TimestampChange>,
+}
+
+impl SavePlan {
+    pub(super) fn new(
+        file_writes: Vec<FileWrite>,
+        timestamp_changes: Vec<TimestampChange>,
+    ) -> Self {
+        Self {
+            file_writes,
+            timestamp_changes,
+        }
+    }
+
+    /// The files that would be written, in the order they would be written.
+    pub fn file_writes(&self) -> &[FileWrite] {
+        &self.file_writes
+    }
+
+    pub fn timestamp_changes(&self) -> &[TimestampChange] {
+        &self.timestamp_changes
+    }
+}
+
+/// Get the timestamp changes needed for the given plugins to load in the
+/// order they're given in when sorted by timestamp. The timestamps already
+/// used by the plugins are reused, with duplicates replaced by new timestamps
+/// after the last one.
+///
+/// The old times are read from the plugin files, so plugins whose files
+/// already have the right timestamps are omitted even if they've been changed
+/// since the load order was loaded.
+pub(super) fn plan_plugin_timestamps<'a>(
+    plugins: impl Iterator<Item = &'a Plugin> + Clone,
+) -> Result<Vec<TimestampChange>, Error> {
+    let timestamps = padded_unique_timestamps(plugins.clone());
+
+    let mut changes = Vec::new();
+    for (plugin, new_time) in plugins.zip(timestamps) {
+        let path = plugin.path();
+        let old_time = metadata(path)
+            .and_then(|m| m.modified())
+            .map_err(|e| Error::IoError(path.to_path_buf(), e))?;
+
+        if old_time != new_time {
+            changes.push(TimestampChange {
+                plugin_name: plugin.name().to_owned(),
+                path: path.to_path_buf(),
+                old_time,
+                new_time,
+            });
+        }
+    }
+
+    Ok(changes)
+}
+
+fn padded_unique_timestamps<'a>(plugins: impl Iterator<Item = &'a Plugin>) -> Vec<SystemTime> {
+    let mut timestamps: Vec<SystemTime> = plugins.map(Plugin::modification_time).collect();
+
+    timestamps.sort();
+
+    let old_len = timestamps.len();
+    timestamps.dedup();
+
+    while timestamps.len() < old_len {
+        let timestamp = *timestamps.last().unwrap_or(&UNIX_EPOCH) + Duration::from_secs(60);
+        timestamps.push(timestamp);
+    }
+
+    timestamps
+}
+
+/// Write the plan's files, then set its plugin timestamps.
+pub(super) fn apply_save_plan<T: MutableLoadOrder + ?Sized>(
+    load_order: &mut T,
+    plan: &SavePlan,
+) -> Result<(), Error> {
+    let files: Vec<_> = plan
+        .file_writes
+        .iter()
+        .map(|w| (w.path.as_path(), w.contents.as_slice()))
+        .collect();
+
+    if !files.is_empty() {
+        write_files_atomically(&files)?;
+    }
+
+    for change in &plan.timestamp_changes {
+        if let Some(plugin) = load_order.find_plugin_mut(&change.plugin_name) {
+            plugin.set_modification_time(change.new_time)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+/// How many of the slots for active plugins of one scale are in use.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SlotUsage {
+    used: usize,
+    max: usize,
+    remaining: usize,
+}
+
+impl SlotUsage {
+    pub(super) fn new(used: usize, max: usize) -> Self {
+        Self {
+            used,
+            max,
+            remaining: max.saturating_sub(used),
+        }
+    }
+
+    /// The same usage, but with no slots remaining, for when using one would
+    /// take away a full plugin slot that's already in use.
+    pub(super) fn without_remaining(self) -> Self {
+        Self {
+            remaining: 0,
+            ..self
+        }
+    }
+
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    /// The number of slots that are free, or zero if the limit has already
+    /// been exceeded. For light and medium plugins, this is also zero if none
+    /// are active and activating one would reduce the full plugin limit below
+    /// the number of active full plugins.
+    pub fn remaining(&self) -> usize {
+        self.remaining
+    }
+}
+
+/// How many of the slots for active full, medium and light plugins are in
+/// use.
+///
+/// Light and medium plugins share the game's full plugin slots, so the
+/// maximum number of active full plugins is 255, reduced by one if any light
+/// plugins are active and by one if any medium plugins are active. The
+/// maximums here reflect the plugins that are currently active, so
+/// activating the first light or medium plugin also takes away a full plugin
+/// slot. Games that don't support light or medium plugins have no slots for
+/// them.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PluginSlotUsage {
+    full: SlotUsage,
+    medium: SlotUsage,
+    light: SlotUsage,
+}
+
+impl PluginSlotUsage {
+    pub(super) fn new(full: SlotUsage, medium: SlotUsage, light: SlotUsage) -> Self {
+        Self {
+            full,
+            medium,
+            light,
+        }
+    }
+
+    pub fn full(&self) -> SlotUsage {
+        self.full
+    }
+
+    pub fn medium(&self) -> SlotUsage {
+        self.medium
+    }
+
+    pub fn light(&self) -> SlotUsage {
+        self.light
+    }
+}
@@ -0,0 +1,210 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use super::diff::LoadOrderDiff;
+use super::writable::WritableLoadOrder;
+use crate::enums::{Error, GameId};
+use crate::plugin::ActiveState;
+
+/// A plugin's name and active state, as recorded in a [LoadOrderSnapshot].
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PluginSnapshot {
+    name: String,
+    active: ActiveState,
+}
+
+impl PluginSnapshot {
+    pub fn new(name: String, active: ActiveState) -> Self {
+        Self { name, active }
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn active_state(&self) -> ActiveState {
+        self.active
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.is_active()
+    }
+}
+
+/// A copy of a load order's plugins and their active states that is
+/// independent of the load order it was captured from. If the `serde` feature
+/// is enabled, snapshots can be serialised to and deserialised from formats
+/// like JSON and TOML.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoadOrderSnapshot {
+    game_id: GameId,
+    plugins: Vec<PluginSnapshot>,
+}
+
+impl LoadOrderSnapshot {
+    pub fn new(game_id: GameId, plugins: Vec<PluginSnapshot>) -> Self {
+        Self { game_id, plugins }
+    }
+
+    pub fn game_id(&self) -> GameId {
+        self.game_id
+    }
+
+    pub fn plugins(&self) -> &[PluginSnapshot] {
+        &self.plugins
+    }
+
+    pub fn plugin_names(&self) -> Vec<&str> {
+        self.plugins.iter().map(PluginSnapshot::name).collect()
+    }
+
+    pub fn active_plugin_names(&self) -> Vec<&str> {
+        self.plugins
+            .iter()
+            .filter(|p| p.is_active())
+            .map(PluginSnapshot::name)
+            .collect()
+    }
+
+    /// Get the changes that would turn this snapshot's load order into the
+    /// given snapshot's load order. To compare against a loaded load order,
+    /// pass its [ReadableLoadOrder::snapshot](super::ReadableLoadOrder::snapshot).
+    pub fn diff(&self, other: &LoadOrderSnapshot) -> LoadOrderDiff {
+        LoadOrderDiff::new(self, other)
+    }
+
+    /// Set the given load order's plugin order and active plugins to match
+    /// this snapshot. The changes are made in a single transaction, so if the
+    /// snapshot can't be applied the load order is left unchanged.
+    ///
+    /// Plugins that are implicitly active in the snapshot are activated
+    /// explicitly, as [WritableLoadOrder::set_active_plugins] does.
+    pub fn apply_to<T: WritableLoadOrder + ?Sized>(&self, load_order: &mut T) -> Result<(), Error> {
+        let plugin_names = self.plugin_names();
+        let active_plugin_names = self.active_plugin_names();
+
+        load_order.transaction(&mut |lo| {
+            lo.set_load_order(&plugin_names)?;
+            lo.set_active_plugins(&active_plugin_names)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crate::tests::copy_to_test_dir;
+    use crate::GameSettings;
+
+    fn load_order(game_dir: &std::path::Path) -> Box<dyn WritableLoadOrder + Send + Sync> {
+        let settings =
+            GameSettings::with_local_path(GameId::Oblivion, game_dir, &game_dir.join("local"))
+                .unwrap();
+
+        copy_to_test_dir("Blank.esm", "Blank.esm", &settings);
+        copy_to_test_dir("Blank.esp", "Blank.esp", &settings);
+        copy_to_test_dir("Blank - Different.esp", "Blank - Different.esp", &settings);
+
+        let mut load_order = settings.into_load_order();
+        load_order.load().unwrap();
+        load_order
+    }
+
+    #[test]
+    fn snapshot_should_capture_plugin_order_and_active_states() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = load_order(tmp_dir.path());
+        load_order.activate("Blank.esp").unwrap();
+
+        let snapshot = load_order.snapshot();
+
+        assert_eq!(GameId::Oblivion, snapshot.game_id());
+        assert_eq!(load_order.plugin_names(), snapshot.plugin_names());
+        assert_eq!(vec!["Blank.esp"], snapshot.active_plugin_names());
+        assert_eq!(
+            ActiveState::ExplicitlyActive,
+            snapshot.plugins()[1].active_state()
+        );
+    }
+
+    #[test]
+    fn apply_to_should_set_the_load_order_and_active_plugins() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = load_order(tmp_dir.path());
+
+        let snapshot = LoadOrderSnapshot::new(
+            GameId::Oblivion,
+            vec![
+                PluginSnapshot::new("Blank.esm".into(), ActiveState::ExplicitlyActive),
+                PluginSnapshot::new("Blank - Different.esp".into(), ActiveState::Inactive),
+                PluginSnapshot::new("Blank.esp".into(), ActiveState::ExplicitlyActive),
+            ],
+        );
+
+        snapshot.apply_to(load_order.as_mut()).unwrap();
+
+        assert_eq!(snapshot, load_order.snapshot());
+    }
+
+    #[test]
+    fn apply_to_should_leave_the_load_order_unchanged_if_the_snapshot_is_invalid() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = load_order(tmp_dir.path());
+        let previous_snapshot = load_order.snapshot();
+
+        let snapshot = LoadOrderSnapshot::new(
+            GameId::Oblivion,
+            vec![
+                PluginSnapshot::new("Blank.esm".into(), ActiveState::ExplicitlyActive),
+                PluginSnapshot::new("Blank.esp".into(), ActiveState::ExplicitlyActive),
+                PluginSnapshot::new("missing.esp".into(), ActiveState::ExplicitlyActive),
+            ],
+        );
+
+        assert!(snapshot.apply_to(load_order.as_mut()).is_err());
+        assert_eq!(previous_snapshot, load_order.snapshot());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn snapshot_should_round_trip_through_json() {
+        let snapshot = LoadOrderSnapshot::new(
+            GameId::SkyrimSE,
+            vec![
+                PluginSnapshot::new("Skyrim.esm".into(), ActiveState::ImplicitlyActive),
+                PluginSnapshot::new("Blank.esp".into(), ActiveState::Inactive),
+            ],
+        );
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+
+        assert_eq!(
+            r#"{"game_id":"SkyrimSE","plugins":[{"name":"Skyrim.esm","active":"ImplicitlyActive"},{"name":"Blank.esp","active":"Inactive"}]}"#,
+            json
+        );
+        assert_eq!(
+            snapshot,
+            serde_json::from_str::<LoadOrderSnapshot>(&json).unwrap()
+        );
+    }
+}
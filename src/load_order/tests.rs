@@ -33,6 +33,13 @@ use crate::tests::{copy_to_test_dir, set_timestamps, NON_ASCII};
 
 use super::mutable::MutableLoadOrder;
 
+pub(super) fn create_parent_dirs(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) => create_dir_all(parent),
+        None => Ok(()),
+    }
+}
+
 pub(super) fn write_load_order_file<T: AsRef<str> + Display>(
     game_settings: &GameSettings,
     filenames: &[T],
@@ -17,29 +17,38 @@
  * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
  */
 use std::collections::HashSet;
-use std::fs::File;
-use std::io::{BufWriter, Write};
+use std::mem;
 use std::path::{Path, PathBuf};
 
 use unicase::{eq, UniCase};
 
+use super::fingerprint::FileFingerprints;
+use super::history::{redo, undo, EditHistory};
 use super::mutable::{
-    hoist_masters, load_active_plugins, plugin_line_mapper, read_plugin_names, MutableLoadOrder,
+    decode_plugin_names, hoist_masters, load_active_plugins, plugin_line_mapper, read_plugin_names,
+    MutableLoadOrder,
 };
 use super::readable::{ReadableLoadOrder, ReadableLoadOrderBase};
+use super::save_plan::{FileWrite, SavePlan};
 use super::strict_encode;
+use super::transaction::LoadOrderTransaction;
 use super::writable::{
-    activate, add, create_parent_dirs, deactivate, remove, set_active_plugins, WritableLoadOrder,
+    activate, activate_many, activate_with_masters, add, deactivate, deactivate_with_dependents,
+    remove, restore_backup, save, set_active_plugins, set_load_order, set_plugin_index,
+    transaction, ActivationPolicy, WritableLoadOrder,
 };
+use crate::atomic_write::recover_interrupted_writes;
 use crate::enums::Error;
 use crate::game_settings::GameSettings;
-use crate::plugin::{trim_dot_ghost, trim_dot_ghost_unchecked, Plugin};
+use crate::plugin::{trim_dot_ghost, trim_dot_ghost_unchecked, Plugin, PluginCache};
 use crate::GameId;
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub(crate) struct TextfileBasedLoadOrder {
     game_settings: GameSettings,
     plugins: Vec<Plugin>,
+    file_fingerprints: FileFingerprints,
+    history: EditHistory,
 }
 
 impl TextfileBasedLoadOrder {
@@ -47,53 +56,56 @@ impl TextfileBasedLoadOrder {
         Self {
             game_settings,
             plugins: Vec::new(),
+            file_fingerprints: FileFingerprints::default(),
+            history: EditHistory::default(),
         }
     }
 
-    fn read_from_load_order_file(&self) -> Result<Vec<(String, bool)>, Error> {
-        match self.game_settings().load_order_file() {
-            Some(file_path) => read_utf8_plugin_names(file_path, load_order_line_mapper)
-                .or_else(|_| read_plugin_names(file_path, load_order_line_mapper)),
-            None => Ok(Vec::new()),
+    fn read_from_load_order_file(
+        &self,
+        file_fingerprints: &mut FileFingerprints,
+    ) -> Result<Option<Vec<(String, bool)>>, Error> {
+        let Some(file_path) = self.game_settings().load_order_file() else {
+            return Ok(None);
+        };
+
+        match file_fingerprints.read_file(file_path)? {
+            Some(content) => match std::str::from_utf8(&content) {
+                Ok(content) => Ok(Some(
+                    content.lines().filter_map(load_order_line_mapper).collect(),
+                )),
+                Err(_) => decode_plugin_names(&content, load_order_line_mapper).map(Some),
+            },
+            None => Ok(None),
         }
     }
 
-    fn read_from_active_plugins_file(&self) -> Result<Vec<(String, bool)>, Error> {
-        read_plugin_names(
-            self.game_settings().active_plugins_file(),
-            active_plugin_line_mapper,
-        )
+    fn read_from_active_plugins_file(
+        &self,
+        file_fingerprints: &mut FileFingerprints,
+    ) -> Result<Vec<(String, bool)>, Error> {
+        match file_fingerprints.read_file(self.game_settings().active_plugins_file())? {
+            Some(content) => decode_plugin_names(&content, active_plugin_line_mapper),
+            None => Ok(Vec::new()),
+        }
     }
 
-    fn save_load_order(&self) -> Result<(), Error> {
-        if let Some(file_path) = self.game_settings().load_order_file() {
-            create_parent_dirs(file_path)?;
-
-            let file = File::create(file_path).map_err(|e| Error::IoError(file_path.clone(), e))?;
-            let mut writer = BufWriter::new(file);
-            for plugin_name in self.plugin_names() {
-                writeln!(writer, "{plugin_name}")
-                    .map_err(|e| Error::IoError(file_path.clone(), e))?;
-            }
+    fn load_order_file_contents(&self) -> Vec<u8> {
+        let mut contents = Vec::new();
+        for plugin_name in self.plugin_names() {
+            contents.extend_from_slice(plugin_name.as_bytes());
+            contents.push(b'\n');
         }
-        Ok(())
+        contents
     }
 
-    fn save_active_plugins(&self) -> Result<(), Error> {
-        let path = self.game_settings().active_plugins_file();
-        create_parent_dirs(path)?;
-
-        let file = File::create(path).map_err(|e| Error::IoError(path.clone(), e))?;
-        let mut writer = BufWriter::new(file);
-
+    fn active_plugins_file_contents(&self) -> Result<Vec<u8>, Error> {
+        let mut contents = Vec::new();
         for plugin_name in self.active_plugin_names() {
-            writer
-                .write_all(&strict_encode(plugin_name)?)
-                .map_err(|e| Error::IoError(path.clone(), e))?;
-            writeln!(writer).map_err(|e| Error::IoError(path.clone(), e))?;
+            contents.extend_from_slice(&strict_encode(plugin_name)?);
+            contents.push(b'\n');
         }
-
-        Ok(())
+        Ok(contents)
     }
 }
 
@@ -111,6 +123,14 @@ impl MutableLoadOrder for TextfileBasedLoadOrder {
     fn plugins_mut(&mut self) -> &mut Vec<Plugin> {
         &mut self.plugins
     }
+
+    fn edit_history_mut(&mut self) -> Option<&mut EditHistory> {
+        Some(&mut self.history)
+    }
+
+    fn file_fingerprints_mut(&mut self) -> Option<&mut FileFingerprints> {
+        Some(&mut self.file_fingerprints)
+    }
 }
 
 impl WritableLoadOrder for TextfileBasedLoadOrder {
@@ -121,22 +141,39 @@ impl WritableLoadOrder for TextfileBasedLoadOrder {
     fn load(&mut self) -> Result<(), Error> {
         self.plugins_mut().clear();
 
-        let load_order_file_exists = self
-            .game_settings()
-            .load_order_file()
-            .is_some_and(|p| p.exists());
+        self.reload()
+    }
+
+    fn reload(&mut self) -> Result<(), Error> {
+        if let Some(file_path) = self.game_settings().load_order_file() {
+            // If the last save was interrupted, either finish or undo it so
+            // that the two files are consistent.
+            recover_interrupted_writes(&[
+                file_path.as_path(),
+                self.game_settings().active_plugins_file().as_path(),
+            ])?;
+        }
+
+        self.history.clear();
 
-        let plugin_tuples = if load_order_file_exists {
-            self.read_from_load_order_file()?
-        } else {
-            self.read_from_active_plugins_file()?
+        let cache = PluginCache::new(mem::take(self.plugins_mut()));
+
+        let mut file_fingerprints = FileFingerprints::default();
+
+        let load_order_file_plugin_tuples =
+            self.read_from_load_order_file(&mut file_fingerprints)?;
+        let load_order_file_exists = load_order_file_plugin_tuples.is_some();
+
+        let plugin_tuples = match load_order_file_plugin_tuples {
+            Some(plugin_tuples) => plugin_tuples,
+            None => self.read_from_active_plugins_file(&mut file_fingerprints)?,
         };
 
         let paths = self.game_settings.find_plugins();
-        self.load_unique_plugins(&plugin_tuples, &paths);
+        self.load_unique_plugins(&plugin_tuples, &paths, &cache);
 
         if load_order_file_exists {
-            load_active_plugins(self, plugin_line_mapper)?;
+            load_active_plugins(self, &mut file_fingerprints, plugin_line_mapper)?;
         }
 
         self.add_implicitly_active_plugins()?;
@@ -145,12 +182,31 @@ impl WritableLoadOrder for TextfileBasedLoadOrder {
             hoist_masters(&mut self.plugins)?;
         }
 
+        self.file_fingerprints = file_fingerprints.finish(self)?;
+
         Ok(())
     }
 
     fn save(&mut self) -> Result<(), Error> {
-        self.save_load_order()?;
-        self.save_active_plugins()
+        save(self)
+    }
+
+    fn plan_save(&self) -> Result<SavePlan, Error> {
+        let active_plugins_file_contents = self.active_plugins_file_contents()?;
+        let load_order_file_contents = self.load_order_file_contents();
+
+        // The load order file is written first, so that the manifest that
+        // load() uses to finish an interrupted save is written next to it.
+        let mut file_writes = Vec::new();
+        if let Some(file_path) = self.game_settings().load_order_file() {
+            file_writes.push(FileWrite::new(file_path.clone(), load_order_file_contents));
+        }
+        file_writes.push(FileWrite::new(
+            self.game_settings().active_plugins_file().clone(),
+            active_plugins_file_contents,
+        ));
+
+        Ok(SavePlan::new(file_writes, Vec::new()))
     }
 
     fn add(&mut self, plugin_name: &str) -> Result<usize, Error> {
@@ -162,11 +218,11 @@ impl WritableLoadOrder for TextfileBasedLoadOrder {
     }
 
     fn set_load_order(&mut self, plugin_names: &[&str]) -> Result<(), Error> {
-        self.replace_plugins(plugin_names)
+        set_load_order(self, plugin_names)
     }
 
     fn set_plugin_index(&mut self, plugin_name: &str, position: usize) -> Result<usize, Error> {
-        MutableLoadOrder::set_plugin_index(self, plugin_name, position)
+        set_plugin_index(self, plugin_name, position)
     }
 
     fn is_self_consistent(&self) -> Result<bool, Error> {
@@ -213,13 +269,64 @@ impl WritableLoadOrder for TextfileBasedLoadOrder {
         activate(self, plugin_name)
     }
 
+    fn activate_with_masters(&mut self, plugin_name: &str) -> Result<(), Error> {
+        activate_with_masters(self, plugin_name)
+    }
+
     fn deactivate(&mut self, plugin_name: &str) -> Result<(), Error> {
         deactivate(self, plugin_name)
     }
 
+    fn deactivate_with_dependents(&mut self, plugin_name: &str) -> Result<(), Error> {
+        deactivate_with_dependents(self, plugin_name)
+    }
+
     fn set_active_plugins(&mut self, active_plugin_names: &[&str]) -> Result<(), Error> {
         set_active_plugins(self, active_plugin_names)
     }
+
+    fn activate_many(
+        &mut self,
+        plugin_names: &[&str],
+        policy: ActivationPolicy,
+    ) -> Result<Vec<String>, Error> {
+        activate_many(self, plugin_names, policy)
+    }
+
+    fn transaction(
+        &mut self,
+        edits: &mut dyn FnMut(&mut LoadOrderTransaction<'_>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        transaction(self, edits)
+    }
+
+    fn restore_backup(&mut self, backup_id: &str) -> Result<(), Error> {
+        restore_backup(self, backup_id)
+    }
+
+    fn is_stale(&self) -> Result<bool, Error> {
+        self.file_fingerprints.is_stale(self.game_settings())
+    }
+
+    fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    fn undo(&mut self) -> Result<bool, Error> {
+        undo(self)
+    }
+
+    fn redo(&mut self) -> Result<bool, Error> {
+        redo(self)
+    }
 }
 
 pub(super) fn read_utf8_plugin_names<F, T>(
@@ -317,9 +424,16 @@ mod tests {
     use crate::load_order::tests::*;
     use crate::plugin::ActiveState;
     use crate::tests::{copy_to_test_dir, set_file_timestamps, NON_ASCII};
-    use std::fs::remove_dir_all;
+    use std::fs::{remove_dir_all, File};
+    use std::io::Write;
     use tempfile::tempdir;
 
+    fn staged_path(path: &Path) -> PathBuf {
+        let mut staged_path = path.as_os_str().to_os_string();
+        staged_path.push(".tmp");
+        PathBuf::from(staged_path)
+    }
+
     fn prepare(game_dir: &Path) -> TextfileBasedLoadOrder {
         prepare_game(GameId::Skyrim, game_dir)
     }
@@ -341,6 +455,8 @@ mod tests {
         TextfileBasedLoadOrder {
             game_settings,
             plugins,
+            file_fingerprints: FileFingerprints::default(),
+            history: EditHistory::default(),
         }
     }
 
@@ -394,6 +510,51 @@ mod tests {
         assert!(load_order.index_of("Blank - Different.esp").is_none());
     }
 
+    #[test]
+    fn reload_should_remove_plugins_that_fail_to_load() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+
+        load_order.load().unwrap();
+        assert!(load_order.index_of("Blank.esp").is_some());
+
+        let plugin_path = load_order
+            .game_settings()
+            .plugins_directory()
+            .join("Blank.esp");
+        write_file(&plugin_path);
+        set_file_timestamps(&plugin_path, 0);
+
+        load_order.reload().unwrap();
+        assert!(load_order.index_of("Blank.esp").is_none());
+    }
+
+    #[test]
+    fn reload_should_give_the_same_load_order_as_load() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+
+        load_order.load().unwrap();
+
+        let filenames = vec![
+            "Blank.esm",
+            NON_ASCII,
+            "Blank - Master Dependent.esp",
+            "Blank - Different.esp",
+            "Blank.esp",
+        ];
+        write_load_order_file(load_order.game_settings(), &filenames);
+        write_active_plugins_file(load_order.game_settings(), &["Blank.esm", "Blank.esp"]);
+
+        load_order.reload().unwrap();
+        let reloaded = load_order.snapshot();
+
+        load_order.load().unwrap();
+
+        assert_eq!(load_order.snapshot(), reloaded);
+        assert_eq!(&filenames, &reloaded.plugin_names()[..5]);
+    }
+
     #[test]
     fn load_should_get_load_order_from_load_order_file() {
         let tmp_dir = tempdir().unwrap();
@@ -686,6 +847,119 @@ mod tests {
         assert_eq!(expected_filenames, load_order.plugin_names());
     }
 
+    #[test]
+    fn load_should_finish_an_interrupted_save_if_the_load_order_file_was_replaced() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+
+        let filenames = vec!["Blank.esm", "Blank.esp", "Blank - Different.esp"];
+        write_load_order_file(load_order.game_settings(), &filenames);
+        write_active_plugins_file(load_order.game_settings(), &["Blank.esm"]);
+
+        let load_order_file = load_order.game_settings().load_order_file().unwrap();
+        let active_plugins_file = load_order.game_settings().active_plugins_file();
+        let mut manifest_path = load_order_file.as_os_str().to_os_string();
+        manifest_path.push(".commit");
+        std::fs::write(
+            &manifest_path,
+            format!(
+                "{}\n{}\n",
+                load_order_file.display(),
+                active_plugins_file.display()
+            ),
+        )
+        .unwrap();
+
+        let staged_path = staged_path(active_plugins_file);
+        std::fs::write(&staged_path, "Blank.esm\nBlank.esp\n").unwrap();
+
+        load_order.load().unwrap();
+
+        assert_eq!(
+            vec!["Blank.esm", "Blank.esp"],
+            load_order.active_plugin_names()
+        );
+        assert!(!staged_path.exists());
+    }
+
+    #[test]
+    fn load_should_ignore_a_stale_staged_active_plugins_file_if_no_save_was_interrupted() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+
+        let filenames = vec!["Blank.esm", "Blank.esp", "Blank - Different.esp"];
+        write_load_order_file(load_order.game_settings(), &filenames);
+        write_active_plugins_file(load_order.game_settings(), &["Blank.esm"]);
+
+        let staged_path = staged_path(load_order.game_settings().active_plugins_file());
+        std::fs::write(&staged_path, "Blank.esm\nBlank.esp\n").unwrap();
+
+        load_order.load().unwrap();
+
+        assert_eq!(vec!["Blank.esm"], load_order.active_plugin_names());
+        assert!(!staged_path.exists());
+    }
+
+    #[test]
+    fn load_should_undo_an_interrupted_save_if_the_load_order_file_was_not_replaced() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+
+        let filenames = vec!["Blank.esm", "Blank.esp", "Blank - Different.esp"];
+        write_load_order_file(load_order.game_settings(), &filenames);
+        write_active_plugins_file(load_order.game_settings(), &["Blank.esm"]);
+
+        let staged_load_order_path =
+            staged_path(load_order.game_settings().load_order_file().unwrap());
+        std::fs::write(
+            &staged_load_order_path,
+            "Blank.esm\nBlank - Different.esp\n",
+        )
+        .unwrap();
+
+        let staged_active_plugins_path =
+            staged_path(load_order.game_settings().active_plugins_file());
+        std::fs::write(&staged_active_plugins_path, "Blank.esm\nBlank.esp\n").unwrap();
+
+        load_order.load().unwrap();
+
+        assert_eq!(filenames, load_order.plugin_names()[..3]);
+        assert_eq!(vec!["Blank.esm"], load_order.active_plugin_names());
+        assert!(!staged_load_order_path.exists());
+        assert!(!staged_active_plugins_path.exists());
+    }
+
+    #[test]
+    fn plan_save_should_return_the_files_that_save_writes() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+        let load_order_file = load_order
+            .game_settings()
+            .load_order_file()
+            .unwrap()
+            .clone();
+        let active_plugins_file = load_order.game_settings().active_plugins_file().clone();
+
+        let plan = load_order.plan_save().unwrap();
+
+        let paths: Vec<_> = plan.file_writes().iter().map(FileWrite::path).collect();
+        assert_eq!(
+            vec![load_order_file.as_path(), active_plugins_file.as_path()],
+            paths
+        );
+        assert!(plan.timestamp_changes().is_empty());
+        assert!(!load_order_file.exists());
+
+        load_order.save().unwrap();
+
+        for file_write in plan.file_writes() {
+            assert_eq!(
+                file_write.contents(),
+                std::fs::read(file_write.path()).unwrap()
+            );
+        }
+    }
+
     #[test]
     fn save_should_write_all_plugins_to_load_order_file() {
         let tmp_dir = tempdir().unwrap();
@@ -737,6 +1011,17 @@ mod tests {
         assert_eq!(vec!["Blank.esp"], load_order.active_plugin_names());
     }
 
+    #[test]
+    fn save_should_not_leave_staged_files_behind() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(tmp_dir.path());
+
+        load_order.save().unwrap();
+
+        assert!(!staged_path(load_order.game_settings().load_order_file().unwrap()).exists());
+        assert!(!staged_path(load_order.game_settings().active_plugins_file()).exists());
+    }
+
     #[test]
     fn save_should_error_if_an_active_plugin_filename_cannot_be_encoded_in_windows_1252() {
         let tmp_dir = tempdir().unwrap();
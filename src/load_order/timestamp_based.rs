@@ -19,30 +19,36 @@
 use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::fs::File;
-use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::io::{BufRead, BufReader};
+use std::mem;
 use std::path::PathBuf;
-use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use rayon::prelude::*;
 use unicase::UniCase;
 
+use super::fingerprint::FileFingerprints;
+use super::history::{redo, undo, EditHistory};
 use super::mutable::{hoist_masters, load_active_plugins, MutableLoadOrder};
 use super::readable::{ReadableLoadOrder, ReadableLoadOrderBase};
+use super::save_plan::{plan_plugin_timestamps, FileWrite, SavePlan};
 use super::strict_encode;
+use super::transaction::LoadOrderTransaction;
 use super::writable::{
-    activate, add, create_parent_dirs, deactivate, remove, set_active_plugins, WritableLoadOrder,
+    activate, activate_many, activate_with_masters, add, deactivate, deactivate_with_dependents,
+    remove, restore_backup, save, set_active_plugins, set_load_order, set_plugin_index,
+    transaction, ActivationPolicy, WritableLoadOrder,
 };
 use crate::enums::{Error, GameId};
 use crate::game_settings::GameSettings;
-use crate::ini::read_morrowind_active_plugins;
-use crate::plugin::{trim_dot_ghost, Plugin};
-
-const GAME_FILES_HEADER: &[u8] = b"[Game Files]";
+use crate::ini::{parse_morrowind_active_plugins, GAME_FILES_HEADER};
+use crate::plugin::{trim_dot_ghost, ActiveState, Plugin, PluginCache};
 
 #[derive(Clone, Debug, Eq, PartialEq, Hash)]
 pub(crate) struct TimestampBasedLoadOrder {
     game_settings: GameSettings,
     plugins: Vec<Plugin>,
+    file_fingerprints: FileFingerprints,
+    history: EditHistory,
 }
 
 /// Retains the first occurrence for each unique filename that is valid Unicode.
@@ -62,55 +68,59 @@ impl TimestampBasedLoadOrder {
         Self {
             game_settings,
             plugins: Vec::new(),
+            file_fingerprints: FileFingerprints::default(),
+            history: EditHistory::default(),
         }
     }
 
-    fn load_plugins_from_dir(&self) -> Vec<Plugin> {
+    fn load_plugins_from_dir(
+        &self,
+        cache: &PluginCache,
+        file_fingerprints: &mut FileFingerprints,
+    ) -> Vec<Plugin> {
         let paths = self.game_settings.find_plugins();
 
+        file_fingerprints.record_plugin_paths(&paths, self.game_settings.id());
+
         let filenames = get_unique_filenames(&paths, self.game_settings.id());
 
         filenames
             .par_iter()
-            .filter_map(|f| Plugin::new(f, &self.game_settings).ok())
+            .filter_map(|f| {
+                cache
+                    .get_or_load(f, &self.game_settings, ActiveState::Inactive)
+                    .ok()
+            })
             .collect()
     }
 
-    fn save_active_plugins(&mut self) -> Result<(), Error> {
-        let path = self.game_settings().active_plugins_file();
-        create_parent_dirs(path)?;
-
-        let prelude = get_file_prelude(self.game_settings())?;
-
-        let file = File::create(path).map_err(|e| Error::IoError(path.clone(), e))?;
-        let mut writer = BufWriter::new(file);
-        writer
-            .write_all(&prelude)
-            .map_err(|e| Error::IoError(path.clone(), e))?;
+    fn active_plugins_file_contents(&self) -> Result<Vec<u8>, Error> {
+        let mut contents = get_file_prelude(self.game_settings())?;
 
         for (index, plugin_name) in self.active_plugin_names().iter().enumerate() {
             if self.game_settings().id() == GameId::Morrowind {
-                write!(writer, "GameFile{index}=").map_err(|e| Error::IoError(path.clone(), e))?;
+                contents.extend_from_slice(format!("GameFile{index}=").as_bytes());
             }
 
-            writer
-                .write_all(&strict_encode(plugin_name)?)
-                .map_err(|e| Error::IoError(path.clone(), e))?;
-            writeln!(writer).map_err(|e| Error::IoError(path.clone(), e))?;
+            contents.extend_from_slice(&strict_encode(plugin_name)?);
+            contents.push(b'\n');
         }
 
-        Ok(())
+        Ok(contents)
     }
 
-    fn load_active_morrowind_plugins(&mut self) -> Result<(), Error> {
+    fn load_active_morrowind_plugins(
+        &mut self,
+        file_fingerprints: &mut FileFingerprints,
+    ) -> Result<(), Error> {
         self.deactivate_all();
 
         let file_path = self.game_settings().active_plugins_file();
-        if !file_path.exists() {
+        let Some(content) = file_fingerprints.read_file(file_path)? else {
             return Ok(());
-        }
+        };
 
-        let plugin_names = read_morrowind_active_plugins(file_path)?;
+        let plugin_names = parse_morrowind_active_plugins(file_path, &content)?;
 
         for plugin_name in plugin_names {
             if let Some(plugin) = self.find_plugin_mut(&plugin_name) {
@@ -136,6 +146,14 @@ impl MutableLoadOrder for TimestampBasedLoadOrder {
     fn plugins_mut(&mut self) -> &mut Vec<Plugin> {
         &mut self.plugins
     }
+
+    fn edit_history_mut(&mut self) -> Option<&mut EditHistory> {
+        Some(&mut self.history)
+    }
+
+    fn file_fingerprints_mut(&mut self) -> Option<&mut FileFingerprints> {
+        Some(&mut self.file_fingerprints)
+    }
 }
 
 impl WritableLoadOrder for TimestampBasedLoadOrder {
@@ -146,27 +164,47 @@ impl WritableLoadOrder for TimestampBasedLoadOrder {
     fn load(&mut self) -> Result<(), Error> {
         self.plugins_mut().clear();
 
-        self.plugins = self.load_plugins_from_dir();
+        self.reload()
+    }
+
+    fn reload(&mut self) -> Result<(), Error> {
+        self.history.clear();
+
+        let cache = PluginCache::new(mem::take(self.plugins_mut()));
+
+        let mut file_fingerprints = FileFingerprints::default();
+
+        self.plugins = self.load_plugins_from_dir(&cache, &mut file_fingerprints);
         self.plugins.sort_by(plugin_sorter);
 
         let game_id = self.game_settings().id();
         if game_id == GameId::Morrowind {
-            self.load_active_morrowind_plugins()?;
+            self.load_active_morrowind_plugins(&mut file_fingerprints)?;
         } else {
-            load_active_plugins(self, plugin_line_mapper)?;
+            load_active_plugins(self, &mut file_fingerprints, plugin_line_mapper)?;
         }
 
         self.add_implicitly_active_plugins()?;
 
         hoist_masters(&mut self.plugins)?;
 
+        self.file_fingerprints = file_fingerprints.finish(self)?;
+
         Ok(())
     }
 
     fn save(&mut self) -> Result<(), Error> {
-        save_load_order_using_timestamps(&mut self.plugins)?;
+        save(self)
+    }
 
-        self.save_active_plugins()
+    fn plan_save(&self) -> Result<SavePlan, Error> {
+        let file_writes = vec![FileWrite::new(
+            self.game_settings().active_plugins_file().clone(),
+            self.active_plugins_file_contents()?,
+        )];
+        let timestamp_changes = plan_plugin_timestamps(self.plugins.iter())?;
+
+        Ok(SavePlan::new(file_writes, timestamp_changes))
     }
 
     fn add(&mut self, plugin_name: &str) -> Result<usize, Error> {
@@ -178,11 +216,11 @@ impl WritableLoadOrder for TimestampBasedLoadOrder {
     }
 
     fn set_load_order(&mut self, plugin_names: &[&str]) -> Result<(), Error> {
-        self.replace_plugins(plugin_names)
+        set_load_order(self, plugin_names)
     }
 
     fn set_plugin_index(&mut self, plugin_name: &str, position: usize) -> Result<usize, Error> {
-        MutableLoadOrder::set_plugin_index(self, plugin_name, position)
+        set_plugin_index(self, plugin_name, position)
     }
 
     fn is_self_consistent(&self) -> Result<bool, Error> {
@@ -199,39 +237,64 @@ impl WritableLoadOrder for TimestampBasedLoadOrder {
         activate(self, plugin_name)
     }
 
+    fn activate_with_masters(&mut self, plugin_name: &str) -> Result<(), Error> {
+        activate_with_masters(self, plugin_name)
+    }
+
     fn deactivate(&mut self, plugin_name: &str) -> Result<(), Error> {
         deactivate(self, plugin_name)
     }
 
+    fn deactivate_with_dependents(&mut self, plugin_name: &str) -> Result<(), Error> {
+        deactivate_with_dependents(self, plugin_name)
+    }
+
     fn set_active_plugins(&mut self, active_plugin_names: &[&str]) -> Result<(), Error> {
         set_active_plugins(self, active_plugin_names)
     }
-}
 
-pub(super) fn save_load_order_using_timestamps(plugins: &mut [Plugin]) -> Result<(), Error> {
-    let timestamps = padded_unique_timestamps(plugins.iter());
+    fn activate_many(
+        &mut self,
+        plugin_names: &[&str],
+        policy: ActivationPolicy,
+    ) -> Result<Vec<String>, Error> {
+        activate_many(self, plugin_names, policy)
+    }
 
-    set_plugin_timestamps(plugins.iter_mut(), timestamps)
-}
+    fn transaction(
+        &mut self,
+        edits: &mut dyn FnMut(&mut LoadOrderTransaction<'_>) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        transaction(self, edits)
+    }
 
-pub(super) fn save_partial_load_order_using_timestamps<'a>(
-    plugins_iter: impl Iterator<Item = &'a mut Plugin>,
-) -> Result<(), Error> {
-    let plugins: Vec<&mut Plugin> = plugins_iter.collect();
-    let timestamps = padded_unique_timestamps(plugins.iter().map(|p| &**p));
+    fn restore_backup(&mut self, backup_id: &str) -> Result<(), Error> {
+        restore_backup(self, backup_id)
+    }
 
-    set_plugin_timestamps(plugins.into_iter(), timestamps)
-}
+    fn is_stale(&self) -> Result<bool, Error> {
+        self.file_fingerprints.is_stale(self.game_settings())
+    }
 
-fn set_plugin_timestamps<'a>(
-    plugins: impl Iterator<Item = &'a mut Plugin>,
-    timestamps: Vec<SystemTime>,
-) -> Result<(), Error> {
-    plugins
-        .zip(timestamps)
-        .map(|(ref mut plugin, timestamp)| plugin.set_modification_time(timestamp))
-        .collect::<Result<Vec<_>, Error>>()
-        .map(|_| ())
+    fn set_history_limit(&mut self, limit: usize) {
+        self.history.set_limit(limit);
+    }
+
+    fn can_undo(&self) -> bool {
+        self.history.can_undo()
+    }
+
+    fn can_redo(&self) -> bool {
+        self.history.can_redo()
+    }
+
+    fn undo(&mut self) -> Result<bool, Error> {
+        undo(self)
+    }
+
+    fn redo(&mut self) -> Result<bool, Error> {
+        redo(self)
+    }
 }
 
 fn plugin_sorter(a: &Plugin, b: &Plugin) -> Ordering {
@@ -259,22 +322,6 @@ fn plugin_line_mapper(line: &str) -> Option<String> {
     }
 }
 
-fn padded_unique_timestamps<'a>(plugins: impl Iterator<Item = &'a Plugin>) -> Vec<SystemTime> {
-    let mut timestamps: Vec<SystemTime> = plugins.map(Plugin::modification_time).collect();
-
-    timestamps.sort();
-
-    let old_len = timestamps.len();
-    timestamps.dedup();
-
-    while timestamps.len() < old_len {
-        let timestamp = *timestamps.last().unwrap_or(&UNIX_EPOCH) + Duration::from_secs(60);
-        timestamps.push(timestamp);
-    }
-
-    timestamps
-}
-
 fn get_file_prelude(game_settings: &GameSettings) -> Result<Vec<u8>, Error> {
     let mut prelude: Vec<u8> = Vec::new();
 
@@ -303,11 +350,11 @@ mod tests {
     use super::*;
 
     use crate::load_order::tests::*;
-    use crate::plugin::ActiveState;
     use crate::tests::{copy_to_test_dir, set_file_timestamps, set_timestamps, NON_ASCII};
     use std::fs::remove_dir_all;
-    use std::io::Read;
+    use std::io::{Read, Write};
     use std::path::Path;
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
     use tempfile::tempdir;
 
     fn prepare(game_id: GameId, game_dir: &Path) -> TimestampBasedLoadOrder {
@@ -323,6 +370,8 @@ mod tests {
         TimestampBasedLoadOrder {
             game_settings,
             plugins,
+            file_fingerprints: FileFingerprints::default(),
+            history: EditHistory::default(),
         }
     }
 
@@ -376,6 +425,54 @@ mod tests {
         assert!(load_order.index_of("Blank - Different.esp").is_none());
     }
 
+    #[test]
+    fn reload_should_parse_plugins_that_have_changed() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+
+        load_order.load().unwrap();
+        let index = load_order.index_of("Blank.esp").unwrap();
+        assert!(!load_order.plugins()[index].is_master_file());
+
+        copy_to_test_dir("Blank.esm", "Blank.esp", load_order.game_settings());
+        let plugin_path = load_order
+            .game_settings()
+            .plugins_directory()
+            .join("Blank.esp");
+        set_file_timestamps(&plugin_path, 0);
+
+        load_order.reload().unwrap();
+
+        let index = load_order.index_of("Blank.esp").unwrap();
+        assert!(load_order.plugins()[index].is_master_file());
+    }
+
+    #[test]
+    fn reload_should_give_the_same_load_order_as_load() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+
+        load_order.load().unwrap();
+
+        set_timestamps(
+            &load_order.game_settings().plugins_directory(),
+            &[
+                "Blank - Master Dependent.esp",
+                "Blank.esm",
+                "Blank - Different.esp",
+                "Blank.esp",
+            ],
+        );
+        write_active_plugins_file(load_order.game_settings(), &["Blank.esp"]);
+
+        load_order.reload().unwrap();
+        let reloaded = load_order.snapshot();
+
+        load_order.load().unwrap();
+
+        assert_eq!(load_order.snapshot(), reloaded);
+    }
+
     #[test]
     fn load_should_sort_installed_plugins_into_their_timestamp_order_with_master_files_first() {
         let tmp_dir = tempdir().unwrap();
@@ -715,6 +812,71 @@ mod tests {
         assert!(content.contains("isrealmorrowindini=false\n[Game Files]\n"));
     }
 
+    #[test]
+    fn plan_save_should_return_the_active_plugins_file_contents_without_writing_it() {
+        let tmp_dir = tempdir().unwrap();
+        let load_order = prepare(GameId::Oblivion, tmp_dir.path());
+        let path = load_order.game_settings().active_plugins_file();
+
+        let plan = load_order.plan_save().unwrap();
+
+        assert_eq!(
+            &[FileWrite::new(path.clone(), b"Blank.esp\n".to_vec())],
+            plan.file_writes()
+        );
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn plan_save_should_return_changed_timestamps_without_setting_them() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+        load_order.load().unwrap();
+
+        load_order
+            .set_load_order(&[
+                "Blank.esm",
+                "Blank - Different.esp",
+                "Blank.esp",
+                "Blank - Master Dependent.esp",
+                NON_ASCII,
+            ])
+            .unwrap();
+
+        let plan = load_order.plan_save().unwrap();
+
+        let to_secs = |t: SystemTime| t.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let changes: Vec<_> = plan
+            .timestamp_changes()
+            .iter()
+            .map(|c| {
+                (
+                    c.plugin_name(),
+                    to_secs(c.old_time()),
+                    to_secs(c.new_time()),
+                )
+            })
+            .collect();
+        assert_eq!(
+            vec![
+                ("Blank - Different.esp", 1_321_009_991, 1_321_009_931),
+                ("Blank.esp", 1_321_009_931, 1_321_009_991),
+            ],
+            changes
+        );
+
+        let plugin_path = load_order.plugins()[2].path();
+        let modified = plugin_path.metadata().unwrap().modified().unwrap();
+        assert_eq!(1_321_009_931, to_secs(modified));
+
+        load_order.save().unwrap();
+
+        for change in plan.timestamp_changes() {
+            let modified = change.path().metadata().unwrap().modified().unwrap();
+            assert_eq!(change.new_time(), modified);
+        }
+    }
+
     #[test]
     fn save_should_error_if_an_active_plugin_filename_cannot_be_encoded_in_windows_1252() {
         let tmp_dir = tempdir().unwrap();
@@ -814,4 +976,76 @@ mod tests {
 
         assert_eq!(Ordering::Greater, ordering);
     }
+
+    #[test]
+    fn is_stale_should_be_true_if_a_plugin_timestamp_changed_since_load() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+
+        load_order.load().unwrap();
+        assert!(!load_order.is_stale().unwrap());
+
+        let plugin_path = load_order.game_settings().plugin_path("Blank.esp");
+        set_file_timestamps(&plugin_path, 1);
+
+        assert!(load_order.is_stale().unwrap());
+    }
+
+    #[test]
+    fn is_stale_should_be_true_if_a_plugin_is_installed_since_load() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+
+        load_order.load().unwrap();
+
+        copy_to_test_dir("Blank.esp", "Blank - Copy.esp", load_order.game_settings());
+
+        assert!(load_order.is_stale().unwrap());
+    }
+
+    #[test]
+    fn is_stale_should_be_false_after_saving_a_plugin_activation_that_unghosts_it() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+
+        copy_to_test_dir(
+            "Blank - Different.esm",
+            "Blank - Different.esm.ghost",
+            load_order.game_settings(),
+        );
+        load_order.load().unwrap();
+
+        load_order.activate("Blank - Different.esm").unwrap();
+        load_order.save().unwrap();
+
+        assert!(!load_order.is_stale().unwrap());
+    }
+
+    #[test]
+    fn transaction_should_reghost_plugins_that_were_unghosted_by_a_failed_transaction() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+
+        copy_to_test_dir(
+            "Blank - Different.esm",
+            "Blank - Different.esm.ghost",
+            load_order.game_settings(),
+        );
+        load_order.add("Blank - Different.esm").unwrap();
+
+        let result = load_order.transaction(&mut |lo| {
+            lo.activate("Blank - Different.esm")?;
+            lo.activate("missing.esp")
+        });
+
+        assert!(result.is_err());
+        assert!(!load_order.is_active("Blank - Different.esm"));
+
+        let plugins_dir = load_order.game_settings().plugins_directory();
+        assert!(plugins_dir.join("Blank - Different.esm.ghost").exists());
+        assert!(!plugins_dir.join("Blank - Different.esm").exists());
+
+        load_order.activate("Blank - Different.esm").unwrap();
+        assert!(plugins_dir.join("Blank - Different.esm").exists());
+    }
 }
@@ -0,0 +1,107 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+
+use super::mutable::MutableLoadOrder;
+use super::readable::ReadableLoadOrderBase;
+use super::writable::{
+    activate, add, deactivate, remove, set_active_plugins, set_load_order, set_plugin_index,
+};
+use crate::enums::Error;
+use crate::game_settings::GameSettings;
+use crate::plugin::Plugin;
+
+/// A batch of edits being made to a load order by
+/// [WritableLoadOrder::transaction](super::WritableLoadOrder::transaction).
+///
+/// Edits that would give an invalid load order or exceed the active plugin
+/// limits are allowed, as the load order is only validated once all the edits
+/// have been made. Other errors (e.g. a plugin not being found) still cause
+/// the edit to fail.
+#[derive(Debug)]
+pub struct LoadOrderTransaction<'a> {
+    game_settings: &'a GameSettings,
+    plugins: &'a mut Vec<Plugin>,
+    max_active_full_plugins: usize,
+}
+
+impl<'a> LoadOrderTransaction<'a> {
+    pub(super) fn new(
+        game_settings: &'a GameSettings,
+        plugins: &'a mut Vec<Plugin>,
+        max_active_full_plugins: usize,
+    ) -> Self {
+        Self {
+            game_settings,
+            plugins,
+            max_active_full_plugins,
+        }
+    }
+
+    pub fn add(&mut self, plugin_name: &str) -> Result<usize, Error> {
+        add(self, plugin_name)
+    }
+
+    pub fn remove(&mut self, plugin_name: &str) -> Result<(), Error> {
+        remove(self, plugin_name)
+    }
+
+    pub fn set_load_order(&mut self, plugin_names: &[&str]) -> Result<(), Error> {
+        set_load_order(self, plugin_names)
+    }
+
+    pub fn set_plugin_index(&mut self, plugin_name: &str, position: usize) -> Result<usize, Error> {
+        set_plugin_index(self, plugin_name, position)
+    }
+
+    pub fn activate(&mut self, plugin_name: &str) -> Result<(), Error> {
+        activate(self, plugin_name)
+    }
+
+    pub fn deactivate(&mut self, plugin_name: &str) -> Result<(), Error> {
+        deactivate(self, plugin_name)
+    }
+
+    pub fn set_active_plugins(&mut self, active_plugin_names: &[&str]) -> Result<(), Error> {
+        set_active_plugins(self, active_plugin_names)
+    }
+}
+
+impl ReadableLoadOrderBase for LoadOrderTransaction<'_> {
+    fn game_settings_base(&self) -> &GameSettings {
+        self.game_settings
+    }
+
+    fn plugins(&self) -> &[Plugin] {
+        self.plugins
+    }
+
+    fn max_active_full_plugins(&self) -> usize {
+        self.max_active_full_plugins
+    }
+}
+
+impl MutableLoadOrder for LoadOrderTransaction<'_> {
+    fn plugins_mut(&mut self) -> &mut Vec<Plugin> {
+        self.plugins
+    }
+
+    fn defers_validation(&self) -> bool {
+        true
+    }
+}
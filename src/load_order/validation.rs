@@ -0,0 +1,566 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use unicase::{eq, UniCase};
+
+use super::mutable::to_plugin;
+use crate::enums::Error;
+use crate::game_settings::GameSettings;
+use crate::ghostable_path::GhostablePath;
+use crate::plugin::Plugin;
+
+/// A problem found by [ReadableLoadOrder::validate](super::ReadableLoadOrder::validate).
+/// Indexes are positions in the list of plugin names that was validated.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
+pub enum ValidationIssue {
+    /// The plugin appears more than once: `first_pos` is where it first
+    /// appears.
+    DuplicatePlugin {
+        name: String,
+        pos: usize,
+        first_pos: usize,
+    },
+    /// The plugin isn't installed.
+    MissingPlugin { name: String, pos: usize },
+    /// The plugin is installed but couldn't be loaded.
+    InvalidPlugin { name: String, pos: usize },
+    InvalidEarlyLoadingPluginPosition {
+        name: String,
+        pos: usize,
+        expected_pos: usize,
+    },
+    NonMasterBeforeMaster {
+        master: String,
+        master_pos: usize,
+        non_master: String,
+        non_master_pos: usize,
+    },
+    /// The plugin loads after a master file that has it as a master, which
+    /// the game would hoist it above.
+    UnrepresentedHoist {
+        plugin: String,
+        plugin_pos: usize,
+        master: String,
+        master_pos: usize,
+    },
+    /// The blueprint plugin loads before the non-blueprint plugin at
+    /// `expected_pos`.
+    InvalidBlueprintPluginPosition {
+        name: String,
+        pos: usize,
+        expected_pos: usize,
+    },
+    /// The non-master plugin loads before one of its masters, which is also a
+    /// non-master plugin and so isn't hoisted by the game.
+    PluginBeforeNonMasterMaster {
+        plugin: String,
+        plugin_pos: usize,
+        master: String,
+        master_pos: usize,
+    },
+    TooManyActivePlugins {
+        light_count: usize,
+        medium_count: usize,
+        full_count: usize,
+    },
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationIssue::DuplicatePlugin { name, pos, first_pos } =>
+                write!(f, "The plugin \"{name}\" at position {pos} is a duplicate of the plugin at position {first_pos}"),
+            ValidationIssue::MissingPlugin { name, pos } =>
+                write!(f, "The plugin \"{name}\" at position {pos} is not installed"),
+            ValidationIssue::InvalidPlugin { name, pos } =>
+                write!(f, "The plugin \"{name}\" at position {pos} could not be loaded"),
+            ValidationIssue::InvalidEarlyLoadingPluginPosition { name, pos, expected_pos } =>
+                write!(f, "The early-loading plugin \"{name}\" is at position {pos}, its expected position is {expected_pos}"),
+            ValidationIssue::NonMasterBeforeMaster { master, master_pos, non_master, non_master_pos } =>
+                write!(f, "The non-master plugin \"{non_master}\" at position {non_master_pos} loads before the master plugin \"{master}\" at position {master_pos}"),
+            ValidationIssue::UnrepresentedHoist { plugin, plugin_pos, master, master_pos } =>
+                write!(f, "The plugin \"{plugin}\" at position {plugin_pos} is a master of \"{master}\" at position {master_pos}, which will hoist it"),
+            ValidationIssue::InvalidBlueprintPluginPosition { name, pos, expected_pos } =>
+                write!(f, "The blueprint plugin \"{name}\" is at position {pos}, but must load after position {expected_pos}"),
+            ValidationIssue::PluginBeforeNonMasterMaster { plugin, plugin_pos, master, master_pos } =>
+                write!(f, "The plugin \"{plugin}\" at position {plugin_pos} loads before its master \"{master}\" at position {master_pos}"),
+            ValidationIssue::TooManyActivePlugins { light_count, medium_count, full_count } =>
+                write!(f, "Maximum number of active plugins exceeded: there are {full_count} active full plugins, {medium_count} active medium plugins and {light_count} active light plugins"),
+        }
+    }
+}
+
+/// Load the named plugins, reusing any in `existing_plugins`, and pair each
+/// one with its position in `plugin_names`. Names that are duplicates or
+/// that can't be loaded are reported as issues and skipped.
+pub(super) fn load_plugins(
+    plugin_names: &[&str],
+    existing_plugins: &[Plugin],
+    game_settings: &GameSettings,
+    issues: &mut Vec<ValidationIssue>,
+) -> Vec<(usize, Plugin)> {
+    let mut first_positions: HashMap<UniCase<&str>, usize> = HashMap::new();
+    let mut plugins = Vec::new();
+
+    for (pos, name) in plugin_names.iter().enumerate() {
+        if let Some(first_pos) = first_positions.get(&UniCase::new(*name)) {
+            issues.push(ValidationIssue::DuplicatePlugin {
+                name: (*name).to_owned(),
+                pos,
+                first_pos: *first_pos,
+            });
+            continue;
+        }
+        first_positions.insert(UniCase::new(*name), pos);
+
+        match to_plugin(name, existing_plugins, game_settings) {
+            Ok(plugin) => plugins.push((pos, plugin)),
+            Err(_) if !is_installed(name, game_settings) => {
+                issues.push(ValidationIssue::MissingPlugin {
+                    name: (*name).to_owned(),
+                    pos,
+                });
+            }
+            Err(_) => issues.push(ValidationIssue::InvalidPlugin {
+                name: (*name).to_owned(),
+                pos,
+            }),
+        }
+    }
+
+    plugins
+}
+
+fn is_installed(plugin_name: &str, game_settings: &GameSettings) -> bool {
+    let path = game_settings.plugin_path(plugin_name);
+
+    if game_settings.id().allow_plugin_ghosting() {
+        path.resolve_path().is_ok()
+    } else {
+        path.exists()
+    }
+}
+
+/// A plugin being checked by [find_load_order_issues], with its position in
+/// the list being validated. Its masters are read up front so that callers
+/// can decide how to handle a plugin whose masters can't be read.
+pub(super) struct PluginEntry<'a> {
+    pos: usize,
+    plugin: &'a Plugin,
+    masters: Vec<String>,
+}
+
+impl<'a> PluginEntry<'a> {
+    pub(super) fn new(pos: usize, plugin: &'a Plugin) -> Result<Self, Error> {
+        plugin.masters().map(|masters| Self {
+            pos,
+            plugin,
+            masters,
+        })
+    }
+}
+
+/// Find every problem with the order of the given plugins.
+/// [validate_load_order](super::mutable::validate_load_order) uses the first
+/// of these that [to_load_order_error] converts to an error.
+pub(super) fn find_load_order_issues(
+    plugins: &[PluginEntry<'_>],
+    early_loading_plugins: &[String],
+    issues: &mut Vec<ValidationIssue>,
+) {
+    find_early_loader_position_issues(plugins, early_loading_plugins, issues);
+
+    find_unhoisted_non_masters_before_masters(plugins, issues);
+
+    find_non_blueprint_plugins_after_blueprint_plugins(plugins, issues);
+
+    find_plugins_loading_after_their_masters(plugins, issues);
+
+    find_plugins_loading_before_their_non_master_masters(plugins, issues);
+}
+
+fn find_early_loader_position_issues(
+    plugins: &[PluginEntry<'_>],
+    early_loading_plugins: &[String],
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let mut missing_plugins_count = 0;
+    for (i, plugin_name) in early_loading_plugins.iter().enumerate() {
+        // Blueprint masters never actually load early, so it's as
+        // if they're missing.
+        match plugins
+            .iter()
+            .enumerate()
+            .find(|(_, e)| !e.plugin.is_blueprint_master() && eq(e.plugin.name(), plugin_name))
+        {
+            Some((index, entry)) => {
+                let expected_index = i - missing_plugins_count;
+                if index != expected_index {
+                    // Report the expected position as the position of the
+                    // plugin that's currently where this one should be.
+                    let expected_pos = plugins
+                        .get(expected_index)
+                        .map_or(expected_index, |e| e.pos);
+
+                    issues.push(ValidationIssue::InvalidEarlyLoadingPluginPosition {
+                        name: plugin_name.clone(),
+                        pos: entry.pos,
+                        expected_pos,
+                    });
+                }
+            }
+            None => missing_plugins_count += 1,
+        }
+    }
+}
+
+fn find_unhoisted_non_masters_before_masters(
+    plugins: &[PluginEntry<'_>],
+    issues: &mut Vec<ValidationIssue>,
+) {
+    // Ignore blueprint plugins because they load after non-masters.
+    let Some(last_master_index) = plugins
+        .iter()
+        .rposition(|e| e.plugin.is_master_file() && !e.plugin.is_blueprint_master())
+    else {
+        return;
+    };
+
+    // Track the non-masters seen so far, and when a master file is reached,
+    // forget any of its masters, since it hoists them. Any non-masters left
+    // weren't hoisted, so are reported once, against the first master that
+    // they load before.
+    let mut non_masters: Vec<(UniCase<&str>, usize)> = Vec::new();
+    for entry in plugins.iter().take(last_master_index + 1) {
+        if entry.plugin.is_master_file() {
+            let masters: HashSet<UniCase<&str>> = entry
+                .masters
+                .iter()
+                .map(|m| UniCase::new(m.as_str()))
+                .collect();
+
+            for (non_master, non_master_pos) in non_masters.drain(..) {
+                if !masters.contains(&non_master) {
+                    issues.push(ValidationIssue::NonMasterBeforeMaster {
+                        master: entry.plugin.name().to_owned(),
+                        master_pos: entry.pos,
+                        non_master: non_master.to_string(),
+                        non_master_pos,
+                    });
+                }
+            }
+        } else {
+            non_masters.push((UniCase::new(entry.plugin.name()), entry.pos));
+        }
+    }
+}
+
+fn find_non_blueprint_plugins_after_blueprint_plugins(
+    plugins: &[PluginEntry<'_>],
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let Some(last_non_blueprint) = plugins.iter().rfind(|e| !e.plugin.is_blueprint_master()) else {
+        return;
+    };
+
+    for entry in plugins {
+        if entry.plugin.is_blueprint_master() && entry.pos < last_non_blueprint.pos {
+            issues.push(ValidationIssue::InvalidBlueprintPluginPosition {
+                name: entry.plugin.name().to_owned(),
+                pos: entry.pos,
+                expected_pos: last_non_blueprint.pos,
+            });
+        }
+    }
+}
+
+fn find_plugins_loading_after_their_masters(
+    plugins: &[PluginEntry<'_>],
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let indexes = index_plugins_by_name(plugins);
+
+    // Check the last-loading masters first, so that the first issue found
+    // is the one that validating a load order has always reported.
+    for (index, master) in plugins.iter().enumerate().rev() {
+        if !master.plugin.is_master_file() {
+            continue;
+        }
+
+        for master_name in &master.masters {
+            let later_plugin = indexes
+                .get(&UniCase::new(master_name.as_str()))
+                .filter(|i| **i > index)
+                .and_then(|i| plugins.get(*i))
+                // Don't report a non-blueprint plugin depending on a
+                // blueprint plugin.
+                .filter(|e| master.plugin.is_blueprint_master() || !e.plugin.is_blueprint_master());
+
+            if let Some(plugin) = later_plugin {
+                issues.push(ValidationIssue::UnrepresentedHoist {
+                    plugin: plugin.plugin.name().to_owned(),
+                    plugin_pos: plugin.pos,
+                    master: master.plugin.name().to_owned(),
+                    master_pos: master.pos,
+                });
+            }
+        }
+    }
+}
+
+fn find_plugins_loading_before_their_non_master_masters(
+    plugins: &[PluginEntry<'_>],
+    issues: &mut Vec<ValidationIssue>,
+) {
+    let indexes = index_plugins_by_name(plugins);
+
+    for (index, plugin) in plugins.iter().enumerate() {
+        // Master files that load before their masters are reported as
+        // unrepresented hoists instead.
+        if plugin.plugin.is_master_file() {
+            continue;
+        }
+
+        for master_name in &plugin.masters {
+            let later_master = indexes
+                .get(&UniCase::new(master_name.as_str()))
+                .filter(|i| **i > index)
+                .and_then(|i| plugins.get(*i))
+                .filter(|e| !e.plugin.is_master_file());
+
+            if let Some(master) = later_master {
+                issues.push(ValidationIssue::PluginBeforeNonMasterMaster {
+                    plugin: plugin.plugin.name().to_owned(),
+                    plugin_pos: plugin.pos,
+                    master: master.plugin.name().to_owned(),
+                    master_pos: master.pos,
+                });
+            }
+        }
+    }
+}
+
+fn index_plugins_by_name<'a>(plugins: &[PluginEntry<'a>]) -> HashMap<UniCase<&'a str>, usize> {
+    plugins
+        .iter()
+        .enumerate()
+        .map(|(index, e)| (UniCase::new(e.plugin.name()), index))
+        .collect()
+}
+
+/// Convert an issue into the error that
+/// [validate_load_order](super::mutable::validate_load_order) would give for
+/// it, if it's one that load orders are required to be free of.
+pub(super) fn to_load_order_error(issue: ValidationIssue) -> Option<Error> {
+    match issue {
+        ValidationIssue::InvalidEarlyLoadingPluginPosition {
+            name,
+            pos,
+            expected_pos,
+        } => Some(Error::InvalidEarlyLoadingPluginPosition {
+            name,
+            pos,
+            expected_pos,
+        }),
+        ValidationIssue::NonMasterBeforeMaster {
+            master, non_master, ..
+        } => Some(Error::NonMasterBeforeMaster { master, non_master }),
+        ValidationIssue::UnrepresentedHoist { plugin, master, .. } => {
+            Some(Error::UnrepresentedHoist { plugin, master })
+        }
+        ValidationIssue::InvalidBlueprintPluginPosition {
+            name,
+            pos,
+            expected_pos,
+        } => Some(Error::InvalidBlueprintPluginPosition {
+            name,
+            pos,
+            expected_pos,
+        }),
+        // The game loads a plugin before its non-master masters without
+        // complaint, even though the plugin won't work as intended, and the
+        // other issues aren't about the load order.
+        ValidationIssue::PluginBeforeNonMasterMaster { .. }
+        | ValidationIssue::DuplicatePlugin { .. }
+        | ValidationIssue::MissingPlugin { .. }
+        | ValidationIssue::InvalidPlugin { .. }
+        | ValidationIssue::TooManyActivePlugins { .. } => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crate::enums::GameId;
+    use crate::load_order::tests::{game_settings_for_test, mock_game_files, set_blueprint_flag};
+    use crate::tests::copy_to_test_dir;
+
+    fn prepare(game_id: GameId, game_dir: &std::path::Path) -> GameSettings {
+        let mut settings = game_settings_for_test(game_id, game_dir);
+        mock_game_files(&mut settings);
+        settings
+    }
+
+    #[test]
+    fn validate_should_return_no_issues_for_a_valid_load_order() {
+        let tmp_dir = tempdir().unwrap();
+        let load_order = prepare(GameId::SkyrimSE, tmp_dir.path()).into_load_order();
+
+        let issues = load_order.validate(&["Blank.esm", "Blank.esp", "Blank - Different.esp"]);
+
+        assert!(issues.is_empty());
+    }
+
+    #[test]
+    fn validate_should_report_every_issue_instead_of_only_the_first() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = prepare(GameId::SkyrimSE, tmp_dir.path());
+        copy_to_test_dir("Blank.esm", "Skyrim.esm", &settings);
+        let mut load_order = settings.into_load_order();
+        load_order.load().unwrap();
+        let plugin_names: Vec<String> = load_order
+            .plugin_names()
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        let issues = load_order.validate(&[
+            "Blank.esp",
+            "Skyrim.esm",
+            "Blank.esm",
+            "missing.esp",
+            "blank.esp",
+            "Blank - Different.esp",
+        ]);
+
+        assert_eq!(
+            vec![
+                ValidationIssue::MissingPlugin {
+                    name: "missing.esp".into(),
+                    pos: 3,
+                },
+                ValidationIssue::DuplicatePlugin {
+                    name: "blank.esp".into(),
+                    pos: 4,
+                    first_pos: 0,
+                },
+                ValidationIssue::InvalidEarlyLoadingPluginPosition {
+                    name: "Skyrim.esm".into(),
+                    pos: 1,
+                    expected_pos: 0,
+                },
+                ValidationIssue::NonMasterBeforeMaster {
+                    master: "Skyrim.esm".into(),
+                    master_pos: 1,
+                    non_master: "Blank.esp".into(),
+                    non_master_pos: 0,
+                },
+            ],
+            issues
+        );
+        assert_eq!(plugin_names, load_order.plugin_names());
+    }
+
+    #[test]
+    fn validate_should_report_plugins_loading_after_masters_that_hoist_them() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = prepare(GameId::SkyrimSE, tmp_dir.path());
+        let master = "Blank - Master Dependent.esm";
+        copy_to_test_dir(master, master, &settings);
+        let load_order = settings.into_load_order();
+
+        let issues = load_order.validate(&[master, "Blank.esm", "Blank.esp"]);
+
+        assert_eq!(
+            vec![ValidationIssue::UnrepresentedHoist {
+                plugin: "Blank.esm".into(),
+                plugin_pos: 1,
+                master: master.into(),
+                master_pos: 0,
+            }],
+            issues
+        );
+    }
+
+    #[test]
+    fn validate_should_report_plugins_loading_before_their_non_master_masters() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = prepare(GameId::SkyrimSE, tmp_dir.path());
+        let plugin = "Blank - Plugin Dependent.esp";
+        copy_to_test_dir(plugin, plugin, &settings);
+        let load_order = settings.into_load_order();
+
+        let issues = load_order.validate(&["Blank.esm", plugin, "Blank.esp"]);
+
+        assert_eq!(
+            vec![ValidationIssue::PluginBeforeNonMasterMaster {
+                plugin: plugin.into(),
+                plugin_pos: 1,
+                master: "Blank.esp".into(),
+                master_pos: 2,
+            }],
+            issues
+        );
+    }
+
+    #[test]
+    fn validate_should_report_each_blueprint_master_that_loads_before_a_non_blueprint_plugin() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = prepare(GameId::Starfield, tmp_dir.path());
+        for plugin in ["Blank.medium.esm", "Blank.small.esm"] {
+            set_blueprint_flag(
+                GameId::Starfield,
+                &settings.plugins_directory().join(plugin),
+                true,
+            )
+            .unwrap();
+        }
+        let load_order = settings.into_load_order();
+
+        let issues = load_order.validate(&[
+            "Blank.full.esm",
+            "Blank.medium.esm",
+            "Blank.small.esm",
+            "Blank.esp",
+        ]);
+
+        assert_eq!(
+            vec![
+                ValidationIssue::InvalidBlueprintPluginPosition {
+                    name: "Blank.medium.esm".into(),
+                    pos: 1,
+                    expected_pos: 3,
+                },
+                ValidationIssue::InvalidBlueprintPluginPosition {
+                    name: "Blank.small.esm".into(),
+                    pos: 2,
+                    expected_pos: 3,
+                },
+            ],
+            issues
+        );
+    }
+}
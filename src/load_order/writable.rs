@@ -17,13 +17,19 @@
  * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
  */
 use std::collections::HashSet;
-use std::fs::create_dir_all;
-use std::path::Path;
 
 use unicase::{eq, UniCase};
 
-use super::mutable::MutableLoadOrder;
+use super::history::record_edit;
+use super::master_diagnostics::{active_dependents, plugin_and_masters};
+use super::mod_index::ModIndex;
+use super::mutable::{validate_load_order, MutableLoadOrder};
 use super::readable::{ReadableLoadOrder, ReadableLoadOrderBase};
+use super::save_plan::{apply_save_plan, SavePlan};
+use super::slot_usage::{PluginSlotUsage, SlotUsage};
+use super::transaction::LoadOrderTransaction;
+use super::validation::{find_load_order_issues, load_plugins, PluginEntry, ValidationIssue};
+use crate::backup;
 use crate::enums::Error;
 use crate::plugin::Plugin;
 use crate::GameSettings;
@@ -31,13 +37,36 @@ use crate::GameSettings;
 const MAX_ACTIVE_LIGHT_PLUGINS: usize = 4096;
 const MAX_ACTIVE_MEDIUM_PLUGINS: usize = 256;
 
+/// How [WritableLoadOrder::activate_many] should handle plugins that can't
+/// be activated without exceeding the active plugin limits.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum ActivationPolicy {
+    /// Activate none of the plugins.
+    #[default]
+    AllOrNothing,
+    /// Skip the plugins that don't fit and carry on activating the rest, so
+    /// that plugins earlier in the list take priority.
+    AsManyAsFit,
+}
+
 pub trait WritableLoadOrder: ReadableLoadOrder + std::fmt::Debug {
     fn game_settings_mut(&mut self) -> &mut GameSettings;
 
     fn load(&mut self) -> Result<(), Error>;
 
+    /// Load the load order again, like [WritableLoadOrder::load], but reuse
+    /// the currently-loaded plugins whose files have the same path, size and
+    /// modification time as when they were loaded, instead of parsing them
+    /// again. The resulting load order is the same as `load()` would give.
+    fn reload(&mut self) -> Result<(), Error>;
+
     fn save(&mut self) -> Result<(), Error>;
 
+    /// Get the file writes and plugin timestamp changes that
+    /// [WritableLoadOrder::save] would make, without making them.
+    fn plan_save(&self) -> Result<SavePlan, Error>;
+
     fn add(&mut self, plugin_name: &str) -> Result<usize, Error>;
 
     fn remove(&mut self, plugin_name: &str) -> Result<(), Error>;
@@ -52,104 +81,198 @@ pub trait WritableLoadOrder: ReadableLoadOrder + std::fmt::Debug {
 
     fn activate(&mut self, plugin_name: &str) -> Result<(), Error>;
 
+    /// Activate the given plugin and all the masters that it depends on,
+    /// directly or indirectly. If any of them can't be activated, e.g.
+    /// because a master isn't installed or activating it would exceed the
+    /// active plugin limits, no plugins are activated.
+    fn activate_with_masters(&mut self, plugin_name: &str) -> Result<(), Error>;
+
     fn deactivate(&mut self, plugin_name: &str) -> Result<(), Error>;
 
+    /// Deactivate the given plugin and all the active plugins that have it as
+    /// a master, directly or indirectly (see
+    /// [ReadableLoadOrder::active_dependents]). If any of them are implicitly
+    /// active, no plugins are deactivated.
+    fn deactivate_with_dependents(&mut self, plugin_name: &str) -> Result<(), Error>;
+
     fn set_active_plugins(&mut self, active_plugin_names: &[&str]) -> Result<(), Error>;
+
+    /// Activate the given plugins in the given order, leaving any other
+    /// active plugins active, and return the plugins that were skipped
+    /// because activating them would have exceeded the active plugin limits.
+    /// If the policy is [ActivationPolicy::AllOrNothing], no plugins are
+    /// skipped and the limits being exceeded is an error.
+    ///
+    /// If a plugin can't be activated for any other reason, no plugins are
+    /// activated.
+    fn activate_many(
+        &mut self,
+        plugin_names: &[&str],
+        policy: ActivationPolicy,
+    ) -> Result<Vec<String>, Error>;
+
+    /// Apply a batch of edits as a single unit. The edits aren't validated
+    /// individually, so the load order may be invalid part-way through the
+    /// batch: instead it's validated once all the edits have been made. If
+    /// any edit or the validation fails, the load order is left unchanged.
+    fn transaction(
+        &mut self,
+        edits: &mut dyn FnMut(&mut LoadOrderTransaction<'_>) -> Result<(), Error>,
+    ) -> Result<(), Error>;
+
+    /// Replace the active plugins and load order files with those from the
+    /// given backup (see [GameSettings::backups]), then reload the load order.
+    fn restore_backup(&mut self, backup_id: &str) -> Result<(), Error>;
+
+    /// Check if any of the files that the load order was read from (or last
+    /// saved to) have since been changed, e.g. by the game's launcher or
+    /// another mod manager. Always false if the load order hasn't been loaded.
+    fn is_stale(&self) -> Result<bool, Error>;
+
+    /// Keep a history of up to `limit` edits made through this trait, so that
+    /// they can be undone and redone. A limit of zero (the default) disables
+    /// the history and discards any edits that were recorded. Loading the
+    /// load order also discards the history.
+    fn set_history_limit(&mut self, limit: usize);
+
+    fn can_undo(&self) -> bool;
+
+    fn can_redo(&self) -> bool;
+
+    /// Restore the plugins' order and active states to how they were before
+    /// the last edit in the history, returning false if there was no edit to
+    /// undo. A transaction is undone as a single edit. Plugins whose files
+    /// have been deleted since the edit aren't restored, so undoing
+    /// [WritableLoadOrder::remove] only brings the plugin back if it has been
+    /// installed again. Errors if a plugin's file can't be ghosted or
+    /// unghosted to match the restored state, though the rest of the state is
+    /// still restored.
+    fn undo(&mut self) -> Result<bool, Error>;
+
+    /// Make the last undone edit again, returning false if there was no edit
+    /// to redo. Making a new edit discards any edits that could be redone.
+    /// Errors in the same way as [WritableLoadOrder::undo].
+    fn redo(&mut self) -> Result<bool, Error>;
 }
 
 pub(super) fn add<T: MutableLoadOrder>(
     load_order: &mut T,
     plugin_name: &str,
 ) -> Result<usize, Error> {
-    if load_order.index_of(plugin_name).is_some() {
-        Err(Error::DuplicatePlugin(plugin_name.to_owned()))
-    } else {
-        let plugin = Plugin::new(plugin_name, load_order.game_settings())?;
+    record_edit(load_order, |load_order| {
+        if load_order.index_of(plugin_name).is_some() {
+            Err(Error::DuplicatePlugin(plugin_name.to_owned()))
+        } else {
+            let plugin = Plugin::new(plugin_name, load_order.game_settings())?;
+            let position = load_order
+                .insert_position(&plugin)
+                .unwrap_or(load_order.plugins().len());
+
+            if !load_order.defers_validation() {
+                load_order.validate_index(&plugin, position)?;
+            }
 
-        if let Some(position) = load_order.insert_position(&plugin) {
-            load_order.validate_index(&plugin, position)?;
             load_order.plugins_mut().insert(position, plugin);
             Ok(position)
-        } else {
-            load_order.validate_index(&plugin, load_order.plugins().len())?;
-            load_order.plugins_mut().push(plugin);
-            Ok(load_order.plugins().len() - 1)
         }
-    }
+    })
 }
 
 pub(super) fn remove<T: MutableLoadOrder>(
     load_order: &mut T,
     plugin_name: &str,
 ) -> Result<(), Error> {
-    match load_order.find_plugin_and_index(plugin_name) {
-        Some((index, plugin)) => {
-            let plugin_path = load_order.game_settings().plugin_path(plugin_name);
-            if plugin_path.exists() {
-                return Err(Error::InstalledPlugin(plugin_name.to_owned()));
-            }
+    record_edit(load_order, |load_order| {
+        match load_order.find_plugin_and_index(plugin_name) {
+            Some((index, plugin)) => {
+                let plugin_path = load_order.game_settings().plugin_path(plugin_name);
+                if plugin_path.exists() {
+                    return Err(Error::InstalledPlugin(plugin_name.to_owned()));
+                }
 
-            // If this is a master file that depends on a non-master file, it shouldn't be removed
-            // without first moving the non-master file later in the load order, unless the next
-            // master file also depends on that same non-master file. The non-master file also
-            // doesn't need to be moved if this is the last master file in the load order.
-            if plugin.is_master_file() {
-                let next_master = &load_order
-                    .plugins()
-                    .iter()
-                    .skip(index + 1)
-                    .find(|p| p.is_master_file());
-
-                if let Some(next_master) = next_master {
-                    let next_master_masters = next_master.masters()?;
-                    let next_master_master_names: HashSet<_> =
-                        next_master_masters.iter().map(UniCase::new).collect();
-
-                    let mut masters = plugin.masters()?;
-
-                    // Remove any masters that are also masters of the next master plugin.
-                    masters.retain(|m| !next_master_master_names.contains(&UniCase::new(m)));
-
-                    // Finally, check if any remaining masters are non-master plugins.
-                    if let Some(n) = masters.iter().find(|n| {
-                        load_order
-                            .find_plugin(n)
-                            // If the master isn't installed, assume it's a master file and so
-                            // doesn't prevent removal of the target plugin.
-                            .is_some_and(|p| !p.is_master_file())
-                    }) {
-                        return Err(Error::NonMasterBeforeMaster {
-                            master: plugin_name.to_owned(),
-                            non_master: n.to_owned(),
-                        });
+                // If this is a master file that depends on a non-master file, it shouldn't be removed
+                // without first moving the non-master file later in the load order, unless the next
+                // master file also depends on that same non-master file. The non-master file also
+                // doesn't need to be moved if this is the last master file in the load order.
+                if plugin.is_master_file() && !load_order.defers_validation() {
+                    let next_master = &load_order
+                        .plugins()
+                        .iter()
+                        .skip(index + 1)
+                        .find(|p| p.is_master_file());
+
+                    if let Some(next_master) = next_master {
+                        let next_master_masters = next_master.masters()?;
+                        let next_master_master_names: HashSet<_> =
+                            next_master_masters.iter().map(UniCase::new).collect();
+
+                        let mut masters = plugin.masters()?;
+
+                        // Remove any masters that are also masters of the next master plugin.
+                        masters.retain(|m| !next_master_master_names.contains(&UniCase::new(m)));
+
+                        // Finally, check if any remaining masters are non-master plugins.
+                        if let Some(n) = masters.iter().find(|n| {
+                            load_order
+                                .find_plugin(n)
+                                // If the master isn't installed, assume it's a master file and so
+                                // doesn't prevent removal of the target plugin.
+                                .is_some_and(|p| !p.is_master_file())
+                        }) {
+                            return Err(Error::NonMasterBeforeMaster {
+                                master: plugin_name.to_owned(),
+                                non_master: n.to_owned(),
+                            });
+                        }
                     }
                 }
-            }
 
-            load_order.plugins_mut().remove(index);
+                load_order.plugins_mut().remove(index);
 
-            Ok(())
+                Ok(())
+            }
+            None => Err(Error::PluginNotFound(plugin_name.to_owned())),
         }
-        None => Err(Error::PluginNotFound(plugin_name.to_owned())),
-    }
+    })
 }
 
 #[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
-struct PluginCounts {
+pub(super) struct PluginCounts {
     light: usize,
     medium: usize,
     full: usize,
 }
 
 impl PluginCounts {
-    fn count_plugin(&mut self, plugin: &Plugin) {
-        if plugin.is_light_plugin() {
+    /// Count the given plugin, returning the mod index that the game would
+    /// give it if all the plugins counted so far are active and load before
+    /// it, or None if the game doesn't give plugins mod indexes.
+    pub(super) fn count_plugin(&mut self, plugin: &Plugin) -> Option<ModIndex> {
+        let index = if plugin.is_light_plugin() {
+            let index = ModIndex::Light(self.light);
             self.light += 1;
+            index
         } else if plugin.is_medium_plugin() {
+            let index = ModIndex::Medium(self.medium);
             self.medium += 1;
+            index
         } else {
+            let index = ModIndex::Full(self.full);
             self.full += 1;
-        }
+            index
+        };
+
+        plugin.game_id().has_mod_indexes().then_some(index)
+    }
+
+    /// The maximum number of active full plugins, given the limit when no
+    /// light or medium plugins are active. Each of the light and medium
+    /// plugin scales that has any plugins counted takes up one full plugin
+    /// slot.
+    fn max_active_full_plugins(&self, max_active_plugins: usize) -> usize {
+        max_active_plugins
+            .saturating_sub(usize::from(self.light > 0))
+            .saturating_sub(usize::from(self.medium > 0))
     }
 }
 
@@ -163,13 +286,10 @@ fn count_active_plugins<T: ReadableLoadOrderBase>(load_order: &T) -> PluginCount
     counts
 }
 
-fn validate_plugin_counts(
-    counts: &PluginCounts,
-    max_active_full_plugins: usize,
-) -> Result<(), Error> {
+fn validate_plugin_counts(counts: &PluginCounts, max_active_plugins: usize) -> Result<(), Error> {
     if (counts.light > MAX_ACTIVE_LIGHT_PLUGINS)
         || (counts.medium > MAX_ACTIVE_MEDIUM_PLUGINS)
-        || (counts.full > max_active_full_plugins)
+        || (counts.full > counts.max_active_full_plugins(max_active_plugins))
     {
         Err(Error::TooManyActivePlugins {
             light_count: counts.light,
@@ -181,6 +301,46 @@ fn validate_plugin_counts(
     }
 }
 
+pub(super) fn plugin_slot_usage<T: ReadableLoadOrderBase>(load_order: &T) -> PluginSlotUsage {
+    let counts = count_active_plugins(load_order);
+    let game_id = load_order.game_settings().id();
+
+    let max_active_medium_plugins = if game_id.supports_medium_plugins() {
+        MAX_ACTIVE_MEDIUM_PLUGINS
+    } else {
+        0
+    };
+
+    let max_active_light_plugins = if game_id.supports_light_plugins() {
+        MAX_ACTIVE_LIGHT_PLUGINS
+    } else {
+        0
+    };
+
+    let full = SlotUsage::new(
+        counts.full,
+        counts.max_active_full_plugins(load_order.max_active_full_plugins()),
+    );
+
+    // Activating the first light or medium plugin lowers the full plugin
+    // limit by one, which isn't possible if all the full plugin slots are
+    // already in use.
+    let scale_usage = |used: usize, max: usize| {
+        let usage = SlotUsage::new(used, max);
+        if used == 0 && full.remaining() == 0 {
+            usage.without_remaining()
+        } else {
+            usage
+        }
+    };
+
+    PluginSlotUsage::new(
+        full,
+        scale_usage(counts.medium, max_active_medium_plugins),
+        scale_usage(counts.light, max_active_light_plugins),
+    )
+}
+
 fn count_plugins(existing_plugins: &[Plugin], existing_plugin_indexes: &[usize]) -> PluginCounts {
     let mut counts = PluginCounts::default();
 
@@ -193,31 +353,130 @@ fn count_plugins(existing_plugins: &[Plugin], existing_plugin_indexes: &[usize])
     counts
 }
 
+pub(super) fn validate<T: ReadableLoadOrderBase>(
+    load_order: &T,
+    plugin_names: &[&str],
+) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    let plugins = load_plugins(
+        plugin_names,
+        load_order.plugins(),
+        load_order.game_settings(),
+        &mut issues,
+    );
+
+    // Plugins whose masters can't be read can't be checked, so report them
+    // as invalid instead.
+    let mut plugin_entries = Vec::new();
+    for (pos, plugin) in &plugins {
+        match PluginEntry::new(*pos, plugin) {
+            Ok(entry) => plugin_entries.push(entry),
+            Err(_) => issues.push(ValidationIssue::InvalidPlugin {
+                name: plugin.name().to_owned(),
+                pos: *pos,
+            }),
+        }
+    }
+
+    find_load_order_issues(
+        &plugin_entries,
+        load_order.game_settings().early_loading_plugins(),
+        &mut issues,
+    );
+
+    let mut counts = PluginCounts::default();
+    for (_, plugin) in plugins.iter().filter(|(_, p)| p.is_active()) {
+        counts.count_plugin(plugin);
+    }
+
+    if let Err(Error::TooManyActivePlugins {
+        light_count,
+        medium_count,
+        full_count,
+    }) = validate_plugin_counts(&counts, load_order.max_active_full_plugins())
+    {
+        issues.push(ValidationIssue::TooManyActivePlugins {
+            light_count,
+            medium_count,
+            full_count,
+        });
+    }
+
+    issues
+}
+
+pub(super) fn set_load_order<T: MutableLoadOrder>(
+    load_order: &mut T,
+    plugin_names: &[&str],
+) -> Result<(), Error> {
+    record_edit(load_order, |load_order| {
+        load_order.replace_plugins(plugin_names)
+    })
+}
+
+pub(super) fn set_plugin_index<T: MutableLoadOrder>(
+    load_order: &mut T,
+    plugin_name: &str,
+    position: usize,
+) -> Result<usize, Error> {
+    record_edit(load_order, |load_order| {
+        load_order.set_plugin_index(plugin_name, position)
+    })
+}
+
 pub(super) fn activate<T: MutableLoadOrder>(
     load_order: &mut T,
     plugin_name: &str,
 ) -> Result<(), Error> {
-    if load_order
-        .game_settings()
-        .supports_blueprint_ships_plugins()
-    {
-        return activate_with_blueprint_ships_plugin(load_order, plugin_name);
-    }
+    record_edit(load_order, |load_order| {
+        if load_order
+            .game_settings()
+            .supports_blueprint_ships_plugins()
+        {
+            return activate_with_blueprint_ships_plugin(load_order, plugin_name);
+        }
 
-    let mut counts = count_active_plugins(load_order);
-    let max_active_full_plugins = load_order.max_active_full_plugins();
+        let mut counts = count_active_plugins(load_order);
+        let max_active_full_plugins = load_order.max_active_full_plugins();
+        let defers_validation = load_order.defers_validation();
 
-    let Some(plugin) = load_order.find_plugin_mut(plugin_name) else {
-        return Err(Error::PluginNotFound(plugin_name.to_owned()));
-    };
+        let Some(plugin) = load_order.find_plugin_mut(plugin_name) else {
+            return Err(Error::PluginNotFound(plugin_name.to_owned()));
+        };
 
-    if !plugin.is_active() {
-        counts.count_plugin(plugin);
+        if !plugin.is_active() && !defers_validation {
+            counts.count_plugin(plugin);
 
-        validate_plugin_counts(&counts, max_active_full_plugins)?;
-    }
+            validate_plugin_counts(&counts, max_active_full_plugins)?;
+        }
+
+        plugin.activate()
+    })
+}
+
+pub(super) fn activate_with_masters<T: MutableLoadOrder>(
+    load_order: &mut T,
+    plugin_name: &str,
+) -> Result<(), Error> {
+    record_edit(load_order, |load_order| {
+        let plugin_names: Vec<String> = plugin_and_masters(load_order.plugins(), plugin_name)?
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+
+        let previous_plugins = load_order.plugins().to_vec();
 
-    plugin.activate()
+        let result = plugin_names
+            .iter()
+            .try_for_each(|n| activate(load_order, n));
+
+        if result.is_err() {
+            restore_plugins(load_order, previous_plugins);
+        }
+
+        result
+    })
 }
 
 fn activate_with_blueprint_ships_plugin<T: MutableLoadOrder>(
@@ -233,7 +492,7 @@ fn activate_with_blueprint_ships_plugin<T: MutableLoadOrder>(
     let blueprint_ships_pair = find_blueprint_ships_plugin_for_plugin(load_order, plugin_name)
         .filter(|(_, p)| !p.is_active());
 
-    if !plugin.is_active() {
+    if !plugin.is_active() && !load_order.defers_validation() {
         let max_active_full_plugins = load_order.max_active_full_plugins();
         let mut counts = count_active_plugins(load_order);
 
@@ -280,31 +539,63 @@ pub(super) fn deactivate<T: MutableLoadOrder>(
     load_order: &mut T,
     plugin_name: &str,
 ) -> Result<(), Error> {
-    if is_implicitly_active(load_order, plugin_name) {
-        return Err(Error::ImplicitlyActivePlugin(plugin_name.to_owned()));
-    }
+    record_edit(load_order, |load_order| {
+        if is_implicitly_active(load_order, plugin_name) {
+            return Err(Error::ImplicitlyActivePlugin(plugin_name.to_owned()));
+        }
 
-    load_order
-        .find_plugin_mut(plugin_name)
-        .ok_or_else(|| Error::PluginNotFound(plugin_name.to_owned()))
-        .map(Plugin::deactivate)?;
+        load_order
+            .find_plugin_mut(plugin_name)
+            .ok_or_else(|| Error::PluginNotFound(plugin_name.to_owned()))
+            .map(Plugin::deactivate)?;
 
-    if load_order
-        .game_settings()
-        .supports_blueprint_ships_plugins()
-    {
-        // Find a BlueprintShips plugin that is implicitly active but not due to
-        // game config or another active plugin, and deactivate it.
-        if let Some(plugin) = blueprint_ships_plugin_name(plugin_name)
-            .filter(|n| !is_implicitly_active(load_order, n))
-            .and_then(|n| load_order.find_plugin_mut(&n))
-            .filter(|p| !p.is_explicitly_active())
+        if load_order
+            .game_settings()
+            .supports_blueprint_ships_plugins()
         {
-            plugin.deactivate();
+            // Find a BlueprintShips plugin that is implicitly active but not due to
+            // game config or another active plugin, and deactivate it.
+            if let Some(plugin) = blueprint_ships_plugin_name(plugin_name)
+                .filter(|n| !is_implicitly_active(load_order, n))
+                .and_then(|n| load_order.find_plugin_mut(&n))
+                .filter(|p| !p.is_explicitly_active())
+            {
+                plugin.deactivate();
+            }
         }
-    }
 
-    Ok(())
+        Ok(())
+    })
+}
+
+pub(super) fn deactivate_with_dependents<T: MutableLoadOrder>(
+    load_order: &mut T,
+    plugin_name: &str,
+) -> Result<(), Error> {
+    record_edit(load_order, |load_order| {
+        let dependents: Vec<String> = active_dependents(load_order.plugins(), plugin_name)
+            .into_iter()
+            .map(str::to_owned)
+            .collect();
+
+        // Check everything that would be deactivated before deactivating anything.
+        if let Some(n) = std::iter::once(plugin_name)
+            .chain(dependents.iter().map(String::as_str))
+            .find(|n| is_implicitly_active(load_order, n))
+        {
+            return Err(Error::ImplicitlyActivePlugin(n.to_owned()));
+        }
+
+        if load_order.find_plugin(plugin_name).is_none() {
+            return Err(Error::PluginNotFound(plugin_name.to_owned()));
+        }
+
+        for dependent in &dependents {
+            deactivate(load_order, dependent)?;
+        }
+
+        deactivate(load_order, plugin_name)
+    })
 }
 
 fn is_implicitly_active<T: ReadableLoadOrder + ReadableLoadOrderBase>(
@@ -342,49 +633,205 @@ pub(super) fn set_active_plugins<T: MutableLoadOrder>(
     load_order: &mut T,
     active_plugin_names: &[&str],
 ) -> Result<(), Error> {
-    let existing_plugin_indices = load_order.lookup_plugins(active_plugin_names)?;
+    record_edit(load_order, |load_order| {
+        let existing_plugin_indices = load_order.lookup_plugins(active_plugin_names)?;
 
-    let counts = count_plugins(load_order.plugins(), &existing_plugin_indices);
+        if !load_order.defers_validation() {
+            let counts = count_plugins(load_order.plugins(), &existing_plugin_indices);
 
-    validate_plugin_counts(&counts, load_order.max_active_full_plugins())?;
+            validate_plugin_counts(&counts, load_order.max_active_full_plugins())?;
+        }
 
-    for plugin_name in load_order.game_settings().implicitly_active_plugins() {
-        // If the plugin isn't installed, don't check that it's in the active
-        // plugins list. Installed plugins will have already been loaded.
-        validate_plugin_is_active(load_order, active_plugin_names, plugin_name)?;
-    }
+        for plugin_name in load_order.game_settings().implicitly_active_plugins() {
+            // If the plugin isn't installed, don't check that it's in the active
+            // plugins list. Installed plugins will have already been loaded.
+            validate_plugin_is_active(load_order, active_plugin_names, plugin_name)?;
+        }
 
-    if load_order
-        .game_settings()
-        .supports_blueprint_ships_plugins()
-    {
-        // Check that for any active plugins that would also cause a
-        // BlueprintShips to be implicitly active, that the BlueprintShips
-        // plugin is also listed.
-        for active_plugin in active_plugin_names {
-            if let Some(blueprint_ships_plugin_name) = blueprint_ships_plugin_name(active_plugin) {
-                validate_plugin_is_active(
-                    load_order,
-                    active_plugin_names,
-                    &blueprint_ships_plugin_name,
-                )?;
+        if load_order
+            .game_settings()
+            .supports_blueprint_ships_plugins()
+        {
+            // Check that for any active plugins that would also cause a
+            // BlueprintShips to be implicitly active, that the BlueprintShips
+            // plugin is also listed.
+            for active_plugin in active_plugin_names {
+                if let Some(blueprint_ships_plugin_name) =
+                    blueprint_ships_plugin_name(active_plugin)
+                {
+                    validate_plugin_is_active(
+                        load_order,
+                        active_plugin_names,
+                        &blueprint_ships_plugin_name,
+                    )?;
+                }
             }
         }
-    }
 
-    load_order.deactivate_all();
+        load_order.deactivate_all();
 
-    for index in existing_plugin_indices {
-        if let Some(plugin) = load_order.plugins_mut().get_mut(index) {
-            // set_active_plugins explicitly activates all given plugins,
-            // including those that were previously implicitly active.
-            plugin.activate()?;
+        for index in existing_plugin_indices {
+            if let Some(plugin) = load_order.plugins_mut().get_mut(index) {
+                // set_active_plugins explicitly activates all given plugins,
+                // including those that were previously implicitly active.
+                plugin.activate()?;
+            }
+        }
+
+        Ok(())
+    })
+}
+
+pub(super) fn activate_many<T: MutableLoadOrder>(
+    load_order: &mut T,
+    plugin_names: &[&str],
+    policy: ActivationPolicy,
+) -> Result<Vec<String>, Error> {
+    record_edit(load_order, |load_order| {
+        let previous_plugins = load_order.plugins().to_vec();
+        let mut skipped_plugins = Vec::new();
+
+        let result = plugin_names.iter().try_for_each(|plugin_name| {
+            match activate(load_order, plugin_name) {
+                Err(Error::TooManyActivePlugins { .. })
+                    if policy == ActivationPolicy::AsManyAsFit =>
+                {
+                    skipped_plugins.push((*plugin_name).to_owned());
+                    Ok(())
+                }
+                result => result,
+            }
+        });
+
+        if let Err(e) = result {
+            restore_plugins(load_order, previous_plugins);
+            return Err(e);
         }
+
+        Ok(skipped_plugins)
+    })
+}
+
+pub(super) fn transaction<T: MutableLoadOrder>(
+    load_order: &mut T,
+    edits: &mut dyn FnMut(&mut LoadOrderTransaction<'_>) -> Result<(), Error>,
+) -> Result<(), Error> {
+    record_edit(load_order, |load_order| {
+        // Edit a copy of the plugins so that the load order is left unchanged if
+        // the transaction fails.
+        let mut plugins = load_order.plugins().to_vec();
+
+        let result = {
+            let mut transaction = LoadOrderTransaction::new(
+                load_order.game_settings(),
+                &mut plugins,
+                load_order.max_active_full_plugins(),
+            );
+
+            edits(&mut transaction)
+                .and_then(|()| validate_transaction(&transaction, load_order.plugins()))
+        };
+
+        match result {
+            Ok(()) => {
+                *load_order.plugins_mut() = plugins;
+                Ok(())
+            }
+            Err(e) => {
+                restore_file_paths(load_order.plugins());
+                Err(e)
+            }
+        }
+    })
+}
+
+/// Check that the load order's files haven't changed since they were read,
+/// back them up, then write the load order and record the fingerprints of
+/// what was written.
+pub(super) fn save<T: WritableLoadOrder + MutableLoadOrder>(
+    load_order: &mut T,
+) -> Result<(), Error> {
+    let mut file_fingerprints = load_order
+        .file_fingerprints_mut()
+        .map(|f| f.clone())
+        .unwrap_or_default();
+
+    file_fingerprints.check_before_save(load_order.game_settings())?;
+
+    let plan = load_order.plan_save()?;
+
+    backup::back_up_files(load_order.game_settings())?;
+
+    apply_save_plan(load_order, &plan)?;
+
+    file_fingerprints.record_save(load_order.game_settings(), load_order.plugins(), &plan)?;
+
+    if let Some(f) = load_order.file_fingerprints_mut() {
+        *f = file_fingerprints;
+    }
+
+    Ok(())
+}
+
+pub(super) fn restore_backup<T: WritableLoadOrder>(
+    load_order: &mut T,
+    backup_id: &str,
+) -> Result<(), Error> {
+    backup::restore_backup(load_order.game_settings(), backup_id)?;
+
+    load_order.load()
+}
+
+fn validate_transaction<T: MutableLoadOrder>(
+    load_order: &T,
+    previous_plugins: &[Plugin],
+) -> Result<(), Error> {
+    // Only validate the load order if it has changed, as the state that the
+    // transaction started from may not have been valid (e.g. if it was loaded
+    // from files that were written by another application).
+    let order_changed = load_order.plugins().len() != previous_plugins.len()
+        || load_order
+            .plugins()
+            .iter()
+            .zip(previous_plugins)
+            .any(|(a, b)| a.name() != b.name());
+
+    if order_changed {
+        validate_load_order(
+            load_order.plugins(),
+            load_order.game_settings().early_loading_plugins(),
+        )?;
+    }
+
+    let counts = count_active_plugins(load_order);
+    validate_plugin_counts(&counts, load_order.max_active_full_plugins())?;
+
+    let active_plugin_names = load_order.active_plugin_names();
+
+    for plugin_name in load_order.game_settings().implicitly_active_plugins() {
+        validate_plugin_is_active(load_order, &active_plugin_names, plugin_name)?;
     }
 
     Ok(())
 }
 
+fn restore_plugins<T: MutableLoadOrder>(load_order: &mut T, plugins: Vec<Plugin>) {
+    *load_order.plugins_mut() = plugins;
+
+    restore_file_paths(load_order.plugins());
+}
+
+/// Activating a ghosted plugin unghosts it, so put the given plugins' files
+/// back to how they were when the plugins were loaded. This is best-effort:
+/// a file that can't be renamed is left as it is, because the in-memory state
+/// is still correct and the plugin's path is checked again when it's next
+/// loaded.
+fn restore_file_paths(plugins: &[Plugin]) {
+    for plugin in plugins {
+        drop(plugin.restore_file_path());
+    }
+}
+
 fn blueprint_ships_plugin_name(plugin_name: &str) -> Option<String> {
     // Supported extensions are .esm, .esp, .esl
     const EXTENSION_LENGTH: usize = 4;
@@ -423,20 +870,12 @@ fn validate_plugin_is_active<T: MutableLoadOrder>(
     Ok(())
 }
 
-pub(super) fn create_parent_dirs(path: &Path) -> Result<(), Error> {
-    if let Some(x) = path.parent() {
-        if !x.exists() {
-            create_dir_all(x).map_err(|e| Error::IoError(x.to_path_buf(), e))?;
-        }
-    }
-    Ok(())
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
 
     use std::fs::remove_file;
+    use std::path::Path;
 
     use tempfile::tempdir;
 
@@ -782,6 +1221,49 @@ mod tests {
         assert!(!load_order.is_active("Blank - Different.esp"));
     }
 
+    #[test]
+    fn activate_with_masters_should_activate_the_plugin_and_its_masters() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+        load_and_insert(&mut load_order, "Blank.esm");
+        load_and_insert(&mut load_order, "Blank - Master Dependent.esp");
+
+        assert!(activate_with_masters(&mut load_order, "Blank - Master Dependent.esp").is_ok());
+        assert!(load_order.is_active("Blank - Master Dependent.esp"));
+        assert!(load_order.is_active("Blank.esm"));
+        assert!(!load_order.is_active("Blank - Different.esp"));
+    }
+
+    #[test]
+    fn activate_with_masters_should_error_if_a_master_is_not_in_the_load_order() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+        load_and_insert(&mut load_order, "Blank - Master Dependent.esp");
+
+        match activate_with_masters(&mut load_order, "Blank - Master Dependent.esp") {
+            Err(Error::PluginNotFound(name)) => assert_eq!("Blank.esm", name),
+            other => panic!("Expected a plugin not found error, got {other:?}"),
+        }
+        assert!(!load_order.is_active("Blank - Master Dependent.esp"));
+    }
+
+    #[test]
+    fn activate_with_masters_should_activate_nothing_if_the_active_plugins_limit_is_exceeded() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+        load_and_insert(&mut load_order, "Blank.esm");
+        load_and_insert(&mut load_order, "Blank - Master Dependent.esp");
+
+        let plugins = prepare_bulk_full_plugins(&mut load_order);
+        for plugin in &plugins[..253] {
+            activate(&mut load_order, plugin).unwrap();
+        }
+
+        assert!(activate_with_masters(&mut load_order, "Blank - Master Dependent.esp").is_err());
+        assert!(!load_order.is_active("Blank - Master Dependent.esp"));
+        assert!(!load_order.is_active("Blank.esm"));
+    }
+
     #[test]
     fn activate_should_succeed_if_at_the_active_plugins_limit_and_the_plugin_is_already_active() {
         let tmp_dir = tempdir().unwrap();
@@ -834,6 +1316,129 @@ mod tests {
         assert!(!load_order.is_active(plugin));
     }
 
+    #[test]
+    fn activate_many_should_activate_nothing_if_the_limit_is_exceeded_and_the_policy_is_all_or_nothing(
+    ) {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+
+        let plugins = prepare_bulk_full_plugins(&mut load_order);
+        let plugin_names: Vec<&str> = plugins.iter().map(String::as_str).collect();
+
+        assert!(activate_many(
+            &mut load_order,
+            &plugin_names,
+            ActivationPolicy::AllOrNothing
+        )
+        .is_err());
+        assert_eq!(vec!["Blank.esp"], load_order.active_plugin_names());
+    }
+
+    #[test]
+    fn activate_many_should_skip_plugins_that_do_not_fit_if_the_policy_is_as_many_as_fit() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+
+        let plugins = prepare_bulk_full_plugins(&mut load_order);
+        let plugin_names: Vec<&str> = plugins.iter().map(String::as_str).collect();
+
+        let skipped = activate_many(
+            &mut load_order,
+            &plugin_names,
+            ActivationPolicy::AsManyAsFit,
+        )
+        .unwrap();
+
+        assert_eq!(&plugins[254..], skipped.as_slice());
+        assert!(load_order.is_active("Blank.esp"));
+        assert!(plugins[..254].iter().all(|p| load_order.is_active(p)));
+        assert_eq!(255, load_order.active_plugin_names().len());
+    }
+
+    #[test]
+    fn activate_many_should_activate_nothing_if_a_plugin_is_not_in_the_load_order() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+
+        let result = activate_many(
+            &mut load_order,
+            &["Blank - Different.esp", "missing.esp"],
+            ActivationPolicy::AsManyAsFit,
+        );
+
+        assert!(matches!(result, Err(Error::PluginNotFound(_))));
+        assert!(!load_order.is_active("Blank - Different.esp"));
+    }
+
+    #[test]
+    fn plugin_slot_usage_should_have_no_light_or_medium_slots_if_the_game_does_not_support_them() {
+        let tmp_dir = tempdir().unwrap();
+        let load_order = prepare(GameId::Oblivion, tmp_dir.path());
+
+        let usage = plugin_slot_usage(&load_order);
+
+        assert_eq!(SlotUsage::new(1, 255), usage.full());
+        assert_eq!(254, usage.full().remaining());
+        assert_eq!(SlotUsage::new(0, 0), usage.medium());
+        assert_eq!(SlotUsage::new(0, 0), usage.light());
+    }
+
+    #[test]
+    fn plugin_slot_usage_should_lower_the_full_plugin_limit_for_each_active_light_or_medium_plugin_scale(
+    ) {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Starfield, tmp_dir.path());
+
+        assert_eq!(
+            SlotUsage::new(1, 255),
+            plugin_slot_usage(&load_order).full()
+        );
+
+        load_and_insert(&mut load_order, "Blank.small.esm");
+        activate(&mut load_order, "Blank.small.esm").unwrap();
+
+        let usage = plugin_slot_usage(&load_order);
+        assert_eq!(SlotUsage::new(1, 254), usage.full());
+        assert_eq!(SlotUsage::new(1, 4096), usage.light());
+        assert_eq!(4095, usage.light().remaining());
+
+        load_and_insert(&mut load_order, "Blank.medium.esm");
+        activate(&mut load_order, "Blank.medium.esm").unwrap();
+
+        let usage = plugin_slot_usage(&load_order);
+        assert_eq!(SlotUsage::new(1, 253), usage.full());
+        assert_eq!(SlotUsage::new(1, 256), usage.medium());
+    }
+
+    #[test]
+    fn plugin_slot_usage_should_have_no_remaining_light_or_medium_slots_if_all_full_slots_are_used()
+    {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Starfield, tmp_dir.path());
+
+        let plugins = prepare_bulk_full_plugins(&mut load_order);
+        for plugin in &plugins[..253] {
+            activate(&mut load_order, plugin).unwrap();
+        }
+
+        let usage = plugin_slot_usage(&load_order);
+        assert_eq!(254, usage.full().used());
+        assert_eq!(256, usage.medium().remaining());
+        assert_eq!(4096, usage.light().remaining());
+
+        activate(&mut load_order, &plugins[253]).unwrap();
+
+        let usage = plugin_slot_usage(&load_order);
+        assert_eq!(SlotUsage::new(255, 255), usage.full());
+        assert_eq!(256, usage.medium().max());
+        assert_eq!(0, usage.medium().remaining());
+        assert_eq!(4096, usage.light().max());
+        assert_eq!(0, usage.light().remaining());
+
+        load_and_insert(&mut load_order, "Blank.small.esm");
+        assert!(activate(&mut load_order, "Blank.small.esm").is_err());
+    }
+
     #[test]
     fn activate_should_lower_the_full_plugin_limit_if_a_light_plugin_is_present() {
         let tmp_dir = tempdir().unwrap();
@@ -1153,6 +1758,56 @@ mod tests {
         assert!(load_order.is_active("Skyrim.esm"));
     }
 
+    #[test]
+    fn deactivate_with_dependents_should_deactivate_plugins_that_depend_on_the_plugin() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::Oblivion, tmp_dir.path());
+        let master_dependent_master = "Blank - Master Dependent.esm";
+        copy_to_test_dir(
+            master_dependent_master,
+            master_dependent_master,
+            load_order.game_settings(),
+        );
+        load_and_insert(&mut load_order, "Blank.esm");
+        load_and_insert(&mut load_order, master_dependent_master);
+        load_and_insert(&mut load_order, "Blank - Master Dependent.esp");
+        for plugin in [
+            "Blank.esm",
+            master_dependent_master,
+            "Blank - Master Dependent.esp",
+        ] {
+            activate(&mut load_order, plugin).unwrap();
+        }
+
+        assert!(deactivate_with_dependents(&mut load_order, "Blank.esm").is_ok());
+        assert!(!load_order.is_active("Blank.esm"));
+        assert!(!load_order.is_active(master_dependent_master));
+        assert!(!load_order.is_active("Blank - Master Dependent.esp"));
+        assert!(load_order.is_active("Blank.esp"));
+    }
+
+    #[test]
+    fn deactivate_with_dependents_should_error_if_a_dependent_is_implicitly_active() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = prepare(GameId::SkyrimSE, tmp_dir.path());
+        copy_to_test_dir(
+            "Blank - Master Dependent.esm",
+            "Update.esm",
+            load_order.game_settings(),
+        );
+        load_and_insert(&mut load_order, "Blank.esm");
+        load_and_insert(&mut load_order, "Update.esm");
+        activate(&mut load_order, "Blank.esm").unwrap();
+        activate(&mut load_order, "Update.esm").unwrap();
+
+        match deactivate_with_dependents(&mut load_order, "Blank.esm") {
+            Err(Error::ImplicitlyActivePlugin(name)) => assert_eq!("Update.esm", name),
+            other => panic!("Expected an implicitly active plugin error, got {other:?}"),
+        }
+        assert!(load_order.is_active("Blank.esm"));
+        assert!(load_order.is_active("Update.esm"));
+    }
+
     #[test]
     fn deactivate_should_error_if_given_a_missing_implicitly_active_plugin() {
         let tmp_dir = tempdir().unwrap();
@@ -35,7 +35,6 @@ guessed for all of the release distributions listed at
 use std::ffi::OsString;
 use std::{
     collections::HashSet,
-    fs::create_dir_all,
     path::{Path, PathBuf},
 };
 
@@ -77,13 +76,50 @@ pub(crate) fn read_active_plugin_names(user_config_path: &Path) -> Result<Vec<St
         return Ok(Vec::new());
     };
 
-    let active_plugin_names: Vec<_> = ini
-        .general_section()
+    Ok(active_plugin_names(&ini))
+}
+
+/// Equivalent to [read_active_plugin_names], but for openmw.cfg content that
+/// has already been read from the given path.
+pub(crate) fn parse_active_plugin_names(
+    user_config_path: &Path,
+    contents: &[u8],
+) -> Result<Vec<String>, Error> {
+    let contents =
+        std::str::from_utf8(contents).map_err(|_e| Error::DecodeError(contents.to_vec()))?;
+
+    let ini = ini::Ini::load_from_str_opt(contents, openmw_cfg_parse_option()).map_err(|e| {
+        Error::IniParsingError {
+            path: user_config_path.to_path_buf(),
+            line: e.line,
+            column: e.col,
+            message: e.msg.to_string(),
+        }
+    })?;
+
+    Ok(active_plugin_names(&ini))
+}
+
+fn active_plugin_names(ini: &ini::Ini) -> Vec<String> {
+    ini.general_section()
         .get_all("content")
         .map(ToOwned::to_owned)
-        .collect();
+        .collect()
+}
+
+/// Get the paths of the openmw.cfg files that are read when getting the
+/// non-user config, including any that don't currently exist.
+pub(crate) fn non_user_config_file_paths(game_path: &Path) -> Result<Vec<PathBuf>, Error> {
+    let fixed_paths = FixedPaths::new(game_path)?;
+    let mut config_state = load_game_configs(&fixed_paths)?;
+
+    config_state.loaded_config_dirs.pop();
 
-    Ok(active_plugin_names)
+    Ok(config_state
+        .loaded_config_dirs
+        .into_iter()
+        .map(|d| d.join("openmw.cfg"))
+        .collect())
 }
 
 pub(crate) fn non_user_active_plugin_names(game_path: &Path) -> Result<Vec<String>, Error> {
@@ -540,6 +576,7 @@ fn load_config(
 #[derive(Clone, Debug, Default, Eq, PartialEq, Ord, PartialOrd, Hash)]
 struct OpenMWConfigState {
     loaded_configs: Vec<OpenMWConfig>,
+    loaded_config_dirs: Vec<PathBuf>,
     user_config_dir: PathBuf,
 }
 
@@ -561,6 +598,7 @@ fn load_game_configs(fixed_paths: &FixedPaths) -> Result<OpenMWConfigState, Erro
     let Some(config) = config else {
         return Ok(OpenMWConfigState {
             loaded_configs: Vec::new(),
+            loaded_config_dirs: active_config_paths,
             user_config_dir: fixed_paths.global_config.clone(),
         });
     };
@@ -598,12 +636,15 @@ fn load_game_configs(fixed_paths: &FixedPaths) -> Result<OpenMWConfigState, Erro
         active_config_paths.push(path);
     }
 
+    let user_config_dir = active_config_paths
+        .last()
+        .ok_or(Error::NoUserConfigPath)?
+        .clone();
+
     Ok(OpenMWConfigState {
         loaded_configs: parsed_configs,
-        user_config_dir: active_config_paths
-            .last()
-            .ok_or(Error::NoUserConfigPath)?
-            .clone(),
+        loaded_config_dirs: active_config_paths,
+        user_config_dir,
     })
 }
 
@@ -642,24 +683,25 @@ fn read_openmw_cfg(openmw_cfg_path: &Path) -> Result<Option<ini::Ini>, Error> {
 
     // openmw.cfg is encoded in UTF-8, see:
     // <https://gitlab.com/OpenMW/openmw/-/blob/openmw-0.49.0/components/config/gamesettings.cpp#L293>
-    ini::Ini::load_from_file_opt(
-        openmw_cfg_path,
-        ini::ParseOption {
-            enabled_quote: false,
-            enabled_escape: false,
-            ..ini::ParseOption::default()
-        },
-    )
-    .map(Some)
-    .map_err(|e| match e {
-        ini::Error::Io(e) => Error::IoError(openmw_cfg_path.to_path_buf(), e),
-        ini::Error::Parse(e) => Error::IniParsingError {
-            path: openmw_cfg_path.to_path_buf(),
-            line: e.line,
-            column: e.col,
-            message: e.msg.to_string(),
-        },
-    })
+    ini::Ini::load_from_file_opt(openmw_cfg_path, openmw_cfg_parse_option())
+        .map(Some)
+        .map_err(|e| match e {
+            ini::Error::Io(e) => Error::IoError(openmw_cfg_path.to_path_buf(), e),
+            ini::Error::Parse(e) => Error::IniParsingError {
+                path: openmw_cfg_path.to_path_buf(),
+                line: e.line,
+                column: e.col,
+                message: e.msg.to_string(),
+            },
+        })
+}
+
+fn openmw_cfg_parse_option() -> ini::ParseOption {
+    ini::ParseOption {
+        enabled_quote: false,
+        enabled_escape: false,
+        ..ini::ParseOption::default()
+    }
 }
 
 fn escape_openmw_data_value(value: &Path) -> Result<String, Error> {
@@ -682,15 +724,14 @@ fn escape_openmw_data_value(value: &Path) -> Result<String, Error> {
     Ok(result)
 }
 
-pub(crate) fn write_openmw_cfg(
+/// Get the contents of the openmw.cfg at the given path with its data paths
+/// and load order replaced by those given.
+pub(crate) fn openmw_cfg_contents(
     openmw_cfg_path: &Path,
     data_paths: &[PathBuf],
     active_plugin_names: &[&str],
-) -> Result<(), Error> {
-    let mut ini = match read_openmw_cfg(openmw_cfg_path)? {
-        Some(ini) => ini,
-        None => ini::Ini::new(),
-    };
+) -> Result<Vec<u8>, Error> {
+    let mut ini = read_openmw_cfg(openmw_cfg_path)?.unwrap_or_default();
 
     // Remove existing data paths.
     let _ = ini.general_section_mut().remove_all("data").count();
@@ -701,6 +742,25 @@ pub(crate) fn write_openmw_cfg(
             .append("data", escape_openmw_data_value(data_path)?);
     }
 
+    replace_content_values(&mut ini, active_plugin_names);
+
+    write_openmw_cfg_contents(openmw_cfg_path, &ini)
+}
+
+/// Get the contents of the openmw.cfg at the given path with only its load
+/// order replaced by the one given.
+pub(crate) fn openmw_cfg_contents_with_load_order(
+    openmw_cfg_path: &Path,
+    active_plugin_names: &[&str],
+) -> Result<Vec<u8>, Error> {
+    let mut ini = read_openmw_cfg(openmw_cfg_path)?.unwrap_or_default();
+
+    replace_content_values(&mut ini, active_plugin_names);
+
+    write_openmw_cfg_contents(openmw_cfg_path, &ini)
+}
+
+fn replace_content_values(ini: &mut ini::Ini, active_plugin_names: &[&str]) {
     // Remove existing load order.
     let _ = ini.general_section_mut().remove_all("content").count();
 
@@ -708,21 +768,25 @@ pub(crate) fn write_openmw_cfg(
     for plugin_name in active_plugin_names {
         ini.general_section_mut().append("content", *plugin_name);
     }
+}
 
-    if let Some(parent_path) = openmw_cfg_path.parent().filter(|p| !p.exists()) {
-        create_dir_all(parent_path).map_err(|e| Error::IoError(parent_path.to_path_buf(), e))?;
-    }
-
+fn write_openmw_cfg_contents(openmw_cfg_path: &Path, ini: &ini::Ini) -> Result<Vec<u8>, Error> {
     // OpenMW's launcher doesn't escape backslashes.
-    ini.write_to_file_policy(openmw_cfg_path, ini::EscapePolicy::Nothing)
-        .map_err(|e| Error::IoError(openmw_cfg_path.to_path_buf(), e))
+    let mut contents = Vec::new();
+    ini.write_to_policy(&mut contents, ini::EscapePolicy::Nothing)
+        .map_err(|e| Error::IoError(openmw_cfg_path.to_path_buf(), e))?;
+
+    Ok(contents)
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs::create_dir_all;
+
     use tempfile::tempdir;
 
     use super::*;
+    use crate::atomic_write::write_file_atomically;
 
     fn fixed_paths() -> FixedPaths {
         FixedPaths {
@@ -735,6 +799,16 @@ mod tests {
         }
     }
 
+    fn write_openmw_cfg(
+        openmw_cfg_path: &Path,
+        data_paths: &[PathBuf],
+        active_plugin_names: &[&str],
+    ) -> Result<(), Error> {
+        let contents = openmw_cfg_contents(openmw_cfg_path, data_paths, active_plugin_names)?;
+
+        write_file_atomically(openmw_cfg_path, &contents)
+    }
+
     #[test]
     fn resources_vfs_path_should_be_relative_to_game_path_if_not_defined_in_config() {
         let tmp_dir = tempdir().unwrap();
@@ -17,8 +17,9 @@ use std::ffi::OsStr;
  * You should have received a copy of the GNU General Public License
  * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
  */
-use std::fs::{File, FileTimes};
-use std::path::Path;
+use std::collections::HashMap;
+use std::fs::{metadata, rename, File, FileTimes};
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 use esplugin::ParseOptions;
@@ -40,7 +41,11 @@ const VALID_EXTENSIONS_WITH_ESL: &[&str] = &[
 
 const VALID_EXTENSIONS_OPENMW: &[&str] = &[".esp", ".esm", ".omwaddon", ".omwgame", ".omwscripts"];
 
+/// Whether a plugin is active, and if so whether it was explicitly activated or
+/// is active because the game activates it implicitly.
 #[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[non_exhaustive]
 pub enum ActiveState {
     Inactive,
     ImplicitlyActive,
@@ -48,7 +53,7 @@ pub enum ActiveState {
 }
 
 impl ActiveState {
-    fn is_active(self) -> bool {
+    pub fn is_active(self) -> bool {
         !matches!(self, Self::Inactive)
     }
 }
@@ -57,6 +62,7 @@ impl ActiveState {
 pub struct Plugin {
     active: ActiveState,
     modification_time: SystemTime,
+    file_size: u64,
     data: esplugin::Plugin,
     name: String,
     game_id: GameId,
@@ -72,19 +78,7 @@ impl Plugin {
         game_settings: &GameSettings,
         active: ActiveState,
     ) -> Result<Plugin, Error> {
-        let filepath = game_settings.plugin_path(filename);
-
-        let filepath = if game_settings.id().allow_plugin_ghosting() {
-            use crate::ghostable_path::GhostablePath;
-
-            if active.is_active() {
-                filepath.unghost()?
-            } else {
-                filepath.resolve_path()?
-            }
-        } else {
-            filepath
-        };
+        let filepath = resolve_plugin_path(filename, game_settings, active)?;
 
         Plugin::with_path(&filepath, game_settings.id(), active)
     }
@@ -103,9 +97,9 @@ impl Plugin {
         }
 
         let file = File::open(path).map_err(|e| Error::IoError(path.to_path_buf(), e))?;
-        let modification_time = file
+        let (modification_time, file_size) = file
             .metadata()
-            .and_then(|m| m.modified())
+            .and_then(|m| Ok((m.modified()?, m.len())))
             .map_err(|e| Error::IoError(path.to_path_buf(), e))?;
 
         let mut data = esplugin::Plugin::new(game_id.to_esplugin_id(), path);
@@ -121,6 +115,7 @@ impl Plugin {
         Ok(Plugin {
             active,
             modification_time,
+            file_size,
             data,
             name: trim_dot_ghost(filename, game_id).to_owned(),
             game_id,
@@ -146,10 +141,32 @@ impl Plugin {
         self.modification_time
     }
 
+    pub(crate) fn file_size(&self) -> u64 {
+        self.file_size
+    }
+
+    pub(crate) fn game_id(&self) -> GameId {
+        self.game_id
+    }
+
+    pub fn path(&self) -> &Path {
+        self.data.path()
+    }
+
+    pub fn is_ghosted(&self) -> bool {
+        use crate::ghostable_path::GhostablePath;
+
+        self.game_id.allow_plugin_ghosting() && self.data.path().has_ghost_extension()
+    }
+
     pub fn is_active(&self) -> bool {
         self.active.is_active()
     }
 
+    pub fn active_state(&self) -> ActiveState {
+        self.active
+    }
+
     pub fn is_explicitly_active(&self) -> bool {
         self.active == ActiveState::ExplicitlyActive
     }
@@ -240,6 +257,32 @@ impl Plugin {
         Ok(())
     }
 
+    /// Rename the plugin's file back to the path that this plugin was loaded
+    /// from if it has since been ghosted or unghosted (e.g. by activating a
+    /// clone of it).
+    pub(crate) fn restore_file_path(&self) -> Result<(), Error> {
+        use crate::ghostable_path::GhostablePath;
+
+        let path = self.data.path();
+        if path.exists() {
+            return Ok(());
+        }
+
+        let other_path = if path.has_ghost_extension() {
+            path.as_unghosted_path()?
+        } else if self.game_id.allow_plugin_ghosting() {
+            path.as_ghosted_path()?
+        } else {
+            return Ok(());
+        };
+
+        if other_path.exists() {
+            rename(&other_path, path).map_err(|e| Error::IoError(other_path.clone(), e))?;
+        }
+
+        Ok(())
+    }
+
     /// This should only be called after checking that the plugin isn't
     /// considered implicitly active.
     pub fn deactivate(&mut self) {
@@ -247,6 +290,69 @@ impl Plugin {
     }
 }
 
+/// Plugins that have previously been loaded, so that they can be reused instead
+/// of being parsed again if their files haven't changed since.
+#[derive(Debug, Default)]
+pub(crate) struct PluginCache(HashMap<PathBuf, Plugin>);
+
+impl PluginCache {
+    pub(crate) fn new(plugins: Vec<Plugin>) -> Self {
+        Self(
+            plugins
+                .into_iter()
+                .map(|p| (p.data.path().to_path_buf(), p))
+                .collect(),
+        )
+    }
+
+    /// Equivalent to [Plugin::with_active], but if there's a cached plugin
+    /// with the same path and its file still has the same size and
+    /// modification time, that plugin is reused instead of parsing the file.
+    pub(crate) fn get_or_load(
+        &self,
+        filename: &str,
+        game_settings: &GameSettings,
+        active: ActiveState,
+    ) -> Result<Plugin, Error> {
+        let filepath = resolve_plugin_path(filename, game_settings, active)?;
+
+        match self.0.get(&filepath) {
+            Some(plugin) if is_file_unchanged(&filepath, plugin) => {
+                let mut plugin = plugin.clone();
+                plugin.active = active;
+                Ok(plugin)
+            }
+            _ => Plugin::with_path(&filepath, game_settings.id(), active),
+        }
+    }
+}
+
+fn is_file_unchanged(path: &Path, plugin: &Plugin) -> bool {
+    metadata(path).is_ok_and(|m| {
+        m.len() == plugin.file_size && m.modified().is_ok_and(|t| t == plugin.modification_time)
+    })
+}
+
+fn resolve_plugin_path(
+    filename: &str,
+    game_settings: &GameSettings,
+    active: ActiveState,
+) -> Result<PathBuf, Error> {
+    let filepath = game_settings.plugin_path(filename);
+
+    if game_settings.id().allow_plugin_ghosting() {
+        use crate::ghostable_path::GhostablePath;
+
+        if active.is_active() {
+            filepath.unghost()
+        } else {
+            filepath.resolve_path()
+        }
+    } else {
+        Ok(filepath)
+    }
+}
+
 pub(crate) fn has_plugin_extension(filename: &str, game: GameId) -> bool {
     let valid_extensions = if game == GameId::OpenMW {
         VALID_EXTENSIONS_OPENMW
@@ -308,7 +414,6 @@ mod tests {
     use super::*;
 
     use crate::tests::{copy_to_dir, copy_to_test_dir, create_file, symlink_file};
-    use std::path::PathBuf;
     use std::time::{Duration, UNIX_EPOCH};
     use tempfile::tempdir;
 
@@ -728,6 +833,7 @@ mod tests {
         let mut plugin = Plugin {
             active: ActiveState::Inactive,
             modification_time: SystemTime::now(),
+            file_size: 0,
             data,
             name: plugin_name.to_owned(),
             game_id: GameId::OpenMW,
@@ -896,6 +1002,51 @@ mod tests {
         assert!(has_plugin_extension("plugin.esl.ghost", GameId::Starfield));
     }
 
+    #[test]
+    fn plugin_cache_get_or_load_should_reuse_a_cached_plugin_if_its_file_is_unchanged() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings(GameId::Oblivion, tmp_dir.path());
+        copy_to_test_dir("Blank.esp", "Blank.esp", &settings);
+
+        let mut plugin = Plugin::new("Blank.esp", &settings).unwrap();
+        // Change the cached plugin's name so that it can be told apart.
+        plugin.name = "cached".into();
+        let cache = PluginCache::new(vec![plugin]);
+
+        let plugin = cache
+            .get_or_load("Blank.esp", &settings, ActiveState::ExplicitlyActive)
+            .unwrap();
+
+        assert_eq!("cached", plugin.name());
+        assert!(plugin.is_explicitly_active());
+    }
+
+    #[test]
+    fn plugin_cache_get_or_load_should_parse_the_file_if_it_has_changed() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings(GameId::Oblivion, tmp_dir.path());
+        copy_to_test_dir("Blank.esp", "Blank.esp", &settings);
+
+        let mut plugin = Plugin::new("Blank.esp", &settings).unwrap();
+        plugin.name = "cached".into();
+        plugin
+            .set_modification_time(UNIX_EPOCH + Duration::from_secs(1))
+            .unwrap();
+        let cache = PluginCache::new(vec![plugin]);
+
+        crate::tests::set_file_timestamps(&settings.plugin_path("Blank.esp"), 2);
+
+        let plugin = cache
+            .get_or_load("Blank.esp", &settings, ActiveState::Inactive)
+            .unwrap();
+
+        assert_eq!("Blank.esp", plugin.name());
+        assert_eq!(
+            UNIX_EPOCH + Duration::from_secs(2),
+            plugin.modification_time()
+        );
+    }
+
     #[test]
     fn trim_dot_ghost_should_trim_the_ghost_extension_if_the_game_allows_ghosting() {
         let ghosted = "plugin.esp.ghost";
@@ -0,0 +1,487 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::fs::{create_dir_all, read_dir, read_to_string, remove_file};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::atomic_write::write_file_atomically;
+use crate::enums::{Error, GameId};
+use crate::load_order::{
+    LoadOrderSnapshot, PluginSnapshot, ReadableLoadOrder, ValidationIssue, WritableLoadOrder,
+};
+use crate::plugin::ActiveState;
+
+/// A directory of named load order profiles for a game. Profiles record only
+/// plugin names and active states, so they can be shared between installs of
+/// the same game.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+pub struct ProfileStore {
+    game_id: GameId,
+    directory: PathBuf,
+}
+
+impl ProfileStore {
+    /// Create a store for the given game's profiles in the given directory.
+    /// The directory is created when the first profile is saved.
+    pub fn new(game_id: GameId, directory: PathBuf) -> Self {
+        Self { game_id, directory }
+    }
+
+    pub fn game_id(&self) -> GameId {
+        self.game_id
+    }
+
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Get the names of the profiles in the store, sorted by name.
+    pub fn profile_names(&self) -> Result<Vec<String>, Error> {
+        if !self.directory.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut names: Vec<_> = read_dir(&self.directory)
+            .map_err(|e| Error::IoError(self.directory.clone(), e))?
+            .flatten()
+            .filter(|e| e.file_type().is_ok_and(|t| !t.is_dir()))
+            .filter_map(|e| e.file_name().into_string().ok())
+            .filter_map(|f| f.strip_suffix(PROFILE_EXTENSION).map(str::to_owned))
+            .filter(|n| is_valid_profile_name(n))
+            .collect();
+
+        names.sort();
+
+        Ok(names)
+    }
+
+    /// Save the given load order's plugin order and active plugins as the
+    /// named profile, replacing any existing profile with the same name.
+    pub fn save<T: ReadableLoadOrder + ?Sized>(
+        &self,
+        name: &str,
+        load_order: &T,
+    ) -> Result<(), Error> {
+        let path = self.profile_path(name)?;
+
+        let snapshot = load_order.snapshot();
+        self.check_game_id(name, snapshot.game_id())?;
+
+        create_dir_all(&self.directory).map_err(|e| Error::IoError(self.directory.clone(), e))?;
+
+        write_file_atomically(&path, serialise_profile(&snapshot).as_bytes())
+    }
+
+    /// Read the named profile.
+    pub fn load(&self, name: &str) -> Result<LoadOrderSnapshot, Error> {
+        let path = self.profile_path(name)?;
+
+        let content = read_to_string(&path).map_err(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                Error::ProfileNotFound(name.to_owned())
+            } else {
+                Error::IoError(path.clone(), e)
+            }
+        })?;
+
+        let snapshot = deserialise_profile(&content).ok_or(Error::InvalidProfile(path))?;
+
+        self.check_game_id(name, snapshot.game_id())?;
+
+        Ok(snapshot)
+    }
+
+    /// Delete the named profile.
+    pub fn delete(&self, name: &str) -> Result<(), Error> {
+        let path = self.profile_path(name)?;
+
+        remove_file(&path).map_err(|e| {
+            if e.kind() == io::ErrorKind::NotFound {
+                Error::ProfileNotFound(name.to_owned())
+            } else {
+                Error::IoError(path, e)
+            }
+        })
+    }
+
+    /// Set the given load order's plugin order and active plugins to match the
+    /// named profile, in a single transaction.
+    ///
+    /// Plugins in the profile that aren't installed are left out instead of
+    /// causing an error, and their names are returned.
+    pub fn apply<T: WritableLoadOrder + ?Sized>(
+        &self,
+        name: &str,
+        load_order: &mut T,
+    ) -> Result<Vec<String>, Error> {
+        self.check_game_id(name, load_order.game_settings().id())?;
+
+        let snapshot = self.load(name)?;
+
+        let missing_plugins: Vec<String> = load_order
+            .validate(&snapshot.plugin_names())
+            .into_iter()
+            .filter_map(|issue| match issue {
+                ValidationIssue::MissingPlugin { name, .. } => Some(name),
+                _ => None,
+            })
+            .collect();
+
+        let is_installed = |name: &&str| !missing_plugins.iter().any(|m| m == name);
+
+        let plugin_names: Vec<_> = snapshot
+            .plugin_names()
+            .into_iter()
+            .filter(is_installed)
+            .collect();
+        let active_plugin_names: Vec<_> = snapshot
+            .active_plugin_names()
+            .into_iter()
+            .filter(is_installed)
+            .collect();
+
+        load_order.transaction(&mut |lo| {
+            lo.set_load_order(&plugin_names)?;
+            lo.set_active_plugins(&active_plugin_names)
+        })?;
+
+        Ok(missing_plugins)
+    }
+
+    /// Check that the given game, of a profile or a load order, is the
+    /// store's game.
+    fn check_game_id(&self, name: &str, game_id: GameId) -> Result<(), Error> {
+        if game_id == self.game_id {
+            Ok(())
+        } else {
+            Err(Error::ProfileGameMismatch {
+                name: name.to_owned(),
+                game_id,
+            })
+        }
+    }
+
+    fn profile_path(&self, name: &str) -> Result<PathBuf, Error> {
+        if is_valid_profile_name(name) {
+            Ok(self.directory.join(format!("{name}{PROFILE_EXTENSION}")))
+        } else {
+            Err(Error::InvalidProfileName(name.to_owned()))
+        }
+    }
+}
+
+const PROFILE_EXTENSION: &str = ".txt";
+
+const GAME_LINE_PREFIX: &str = "game=";
+
+/// Profile names are used as file names, so must not be able to refer to a
+/// path outside the store's directory or contain characters that are invalid
+/// in file names on Windows.
+fn is_valid_profile_name(name: &str) -> bool {
+    const INVALID_CHARS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+    !name.is_empty()
+        && !name.starts_with('.')
+        && !name.ends_with(' ')
+        && !name
+            .chars()
+            .any(|c| c.is_control() || INVALID_CHARS.contains(&c))
+}
+
+/// Profiles are stored as UTF-8 text, with a line giving the game ID followed
+/// by one line per plugin in load order. Active plugins are prefixed with an
+/// asterisk, as in the plugins.txt files of games that use them.
+fn serialise_profile(snapshot: &LoadOrderSnapshot) -> String {
+    let mut content = format!("{GAME_LINE_PREFIX}{}\n", game_id_name(snapshot.game_id()));
+
+    for plugin in snapshot.plugins() {
+        if plugin.is_active() {
+            content.push('*');
+        }
+        content.push_str(plugin.name());
+        content.push('\n');
+    }
+
+    content
+}
+
+fn deserialise_profile(content: &str) -> Option<LoadOrderSnapshot> {
+    let mut lines = content.lines().filter(|l| !l.trim().is_empty());
+
+    let game_id = lines
+        .next()
+        .and_then(|l| l.trim().strip_prefix(GAME_LINE_PREFIX))
+        .and_then(parse_game_id)?;
+
+    let plugins = lines
+        .map(str::trim)
+        .map(|line| match line.strip_prefix('*') {
+            Some(name) => PluginSnapshot::new(name.to_owned(), ActiveState::ExplicitlyActive),
+            None => PluginSnapshot::new(line.to_owned(), ActiveState::Inactive),
+        })
+        .collect();
+
+    Some(LoadOrderSnapshot::new(game_id, plugins))
+}
+
+fn game_id_name(game_id: GameId) -> &'static str {
+    match game_id {
+        GameId::Morrowind => "Morrowind",
+        GameId::Oblivion => "Oblivion",
+        GameId::Skyrim => "Skyrim",
+        GameId::Fallout3 => "Fallout3",
+        GameId::FalloutNV => "FalloutNV",
+        GameId::Fallout4 => "Fallout4",
+        GameId::SkyrimSE => "SkyrimSE",
+        GameId::Fallout4VR => "Fallout4VR",
+        GameId::SkyrimVR => "SkyrimVR",
+        GameId::Starfield => "Starfield",
+        GameId::OpenMW => "OpenMW",
+        GameId::OblivionRemastered => "OblivionRemastered",
+        GameId::Enderal => "Enderal",
+        GameId::EnderalSE => "EnderalSE",
+    }
+}
+
+fn parse_game_id(name: &str) -> Option<GameId> {
+    match name {
+        "Morrowind" => Some(GameId::Morrowind),
+        "Oblivion" => Some(GameId::Oblivion),
+        "Skyrim" => Some(GameId::Skyrim),
+        "Fallout3" => Some(GameId::Fallout3),
+        "FalloutNV" => Some(GameId::FalloutNV),
+        "Fallout4" => Some(GameId::Fallout4),
+        "SkyrimSE" => Some(GameId::SkyrimSE),
+        "Fallout4VR" => Some(GameId::Fallout4VR),
+        "SkyrimVR" => Some(GameId::SkyrimVR),
+        "Starfield" => Some(GameId::Starfield),
+        "OpenMW" => Some(GameId::OpenMW),
+        "OblivionRemastered" => Some(GameId::OblivionRemastered),
+        "Enderal" => Some(GameId::Enderal),
+        "EnderalSE" => Some(GameId::EnderalSE),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempfile::tempdir;
+
+    use crate::tests::copy_to_test_dir;
+    use crate::GameSettings;
+
+    fn load_order(game_dir: &Path) -> Box<dyn WritableLoadOrder + Send + Sync> {
+        let settings =
+            GameSettings::with_local_path(GameId::Oblivion, game_dir, &game_dir.join("local"))
+                .unwrap();
+
+        copy_to_test_dir("Blank.esm", "Blank.esm", &settings);
+        copy_to_test_dir("Blank.esp", "Blank.esp", &settings);
+        copy_to_test_dir("Blank - Different.esp", "Blank - Different.esp", &settings);
+
+        let mut load_order = settings.into_load_order();
+        load_order.load().unwrap();
+        load_order
+    }
+
+    #[test]
+    fn profile_names_should_be_empty_if_the_directory_does_not_exist() {
+        let tmp_dir = tempdir().unwrap();
+        let store = ProfileStore::new(GameId::Oblivion, tmp_dir.path().join("profiles"));
+
+        assert!(store.profile_names().unwrap().is_empty());
+    }
+
+    #[test]
+    fn save_should_write_a_profile_that_load_reads() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = load_order(tmp_dir.path());
+        load_order.activate("Blank.esp").unwrap();
+        let store = ProfileStore::new(GameId::Oblivion, tmp_dir.path().join("profiles"));
+
+        store.save("default", load_order.as_ref()).unwrap();
+
+        let content = read_to_string(tmp_dir.path().join("profiles/default.txt")).unwrap();
+        assert_eq!(
+            "game=Oblivion\nBlank.esm\n*Blank.esp\nBlank - Different.esp\n",
+            content
+        );
+
+        let snapshot = store.load("default").unwrap();
+        assert_eq!(load_order.snapshot(), snapshot);
+        assert_eq!(vec!["default"], store.profile_names().unwrap());
+    }
+
+    #[test]
+    fn save_should_error_if_the_load_order_is_for_a_different_game() {
+        let tmp_dir = tempdir().unwrap();
+        let load_order = load_order(tmp_dir.path());
+        let store = ProfileStore::new(GameId::Skyrim, tmp_dir.path().join("profiles"));
+
+        match store.save("default", load_order.as_ref()).unwrap_err() {
+            Error::ProfileGameMismatch { name, game_id } => {
+                assert_eq!("default", name);
+                assert_eq!(GameId::Oblivion, game_id);
+            }
+            e => panic!("Unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn save_should_error_if_the_profile_name_is_invalid() {
+        let tmp_dir = tempdir().unwrap();
+        let load_order = load_order(tmp_dir.path());
+        let store = ProfileStore::new(GameId::Oblivion, tmp_dir.path().join("profiles"));
+
+        for name in ["", "..", "../default", "a/b", "a\\b", "a:b"] {
+            match store.save(name, load_order.as_ref()).unwrap_err() {
+                Error::InvalidProfileName(n) => assert_eq!(name, n),
+                e => panic!("Unexpected error: {e}"),
+            }
+        }
+
+        assert!(!tmp_dir.path().join("profiles").exists());
+    }
+
+    #[test]
+    fn load_should_error_if_the_profile_does_not_exist() {
+        let tmp_dir = tempdir().unwrap();
+        let store = ProfileStore::new(GameId::Oblivion, tmp_dir.path().to_path_buf());
+
+        match store.load("default").unwrap_err() {
+            Error::ProfileNotFound(name) => assert_eq!("default", name),
+            e => panic!("Unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn load_should_error_if_the_profile_is_for_a_different_game() {
+        let tmp_dir = tempdir().unwrap();
+        std::fs::write(
+            tmp_dir.path().join("default.txt"),
+            "game=Skyrim\n*Skyrim.esm\n",
+        )
+        .unwrap();
+        let store = ProfileStore::new(GameId::Oblivion, tmp_dir.path().to_path_buf());
+
+        match store.load("default").unwrap_err() {
+            Error::ProfileGameMismatch { name, game_id } => {
+                assert_eq!("default", name);
+                assert_eq!(GameId::Skyrim, game_id);
+            }
+            e => panic!("Unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn load_should_error_if_the_profile_has_no_valid_game_line() {
+        let tmp_dir = tempdir().unwrap();
+        let path = tmp_dir.path().join("default.txt");
+        std::fs::write(&path, "*Blank.esm\n").unwrap();
+        let store = ProfileStore::new(GameId::Oblivion, tmp_dir.path().to_path_buf());
+
+        match store.load("default").unwrap_err() {
+            Error::InvalidProfile(p) => assert_eq!(path, p),
+            e => panic!("Unexpected error: {e}"),
+        }
+    }
+
+    #[test]
+    fn delete_should_remove_the_profile() {
+        let tmp_dir = tempdir().unwrap();
+        let load_order = load_order(tmp_dir.path());
+        let store = ProfileStore::new(GameId::Oblivion, tmp_dir.path().join("profiles"));
+        store.save("a", load_order.as_ref()).unwrap();
+        store.save("b", load_order.as_ref()).unwrap();
+
+        store.delete("a").unwrap();
+
+        assert_eq!(vec!["b"], store.profile_names().unwrap());
+        assert!(matches!(
+            store.delete("a").unwrap_err(),
+            Error::ProfileNotFound(_)
+        ));
+    }
+
+    #[test]
+    fn apply_should_set_the_load_order_and_return_missing_plugins() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = load_order(tmp_dir.path());
+        std::fs::write(
+            tmp_dir.path().join("default.txt"),
+            "game=Oblivion\n*Blank.esm\n*missing.esp\nBlank - Different.esp\n*Blank.esp\n",
+        )
+        .unwrap();
+        let store = ProfileStore::new(GameId::Oblivion, tmp_dir.path().to_path_buf());
+
+        let missing = store.apply("default", load_order.as_mut()).unwrap();
+
+        assert_eq!(vec!["missing.esp"], missing);
+        assert_eq!(
+            vec!["Blank.esm", "Blank - Different.esp", "Blank.esp"],
+            load_order.plugin_names()
+        );
+        assert_eq!(
+            vec!["Blank.esm", "Blank.esp"],
+            load_order.active_plugin_names()
+        );
+    }
+
+    #[test]
+    fn apply_should_error_if_the_load_order_is_for_a_different_game() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = load_order(tmp_dir.path());
+        let previous_snapshot = load_order.snapshot();
+        std::fs::write(
+            tmp_dir.path().join("default.txt"),
+            "game=Skyrim
+*Skyrim.esm
+",
+        )
+        .unwrap();
+        let store = ProfileStore::new(GameId::Skyrim, tmp_dir.path().to_path_buf());
+
+        match store.apply("default", load_order.as_mut()).unwrap_err() {
+            Error::ProfileGameMismatch { name, game_id } => {
+                assert_eq!("default", name);
+                assert_eq!(GameId::Oblivion, game_id);
+            }
+            e => panic!("Unexpected error: {e}"),
+        }
+        assert_eq!(previous_snapshot, load_order.snapshot());
+    }
+
+    #[test]
+    fn apply_should_leave_the_load_order_unchanged_if_the_profile_is_invalid() {
+        let tmp_dir = tempdir().unwrap();
+        let mut load_order = load_order(tmp_dir.path());
+        let previous_snapshot = load_order.snapshot();
+        std::fs::write(
+            tmp_dir.path().join("default.txt"),
+            "game=Oblivion\nBlank.esp\nBlank.esm\n",
+        )
+        .unwrap();
+        let store = ProfileStore::new(GameId::Oblivion, tmp_dir.path().to_path_buf());
+
+        assert!(store.apply("default", load_order.as_mut()).is_err());
+        assert_eq!(previous_snapshot, load_order.snapshot());
+    }
+}
@@ -0,0 +1,334 @@
+/*
+ * This file is part of libloadorder
+ *
+ * Copyright (C) 2017 Oliver Hamlet
+ *
+ * libloadorder is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * libloadorder is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU General Public License for more details.
+ *
+ * You should have received a copy of the GNU General Public License
+ * along with libloadorder. If not, see <http://www.gnu.org/licenses/>.
+ */
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::enums::Error;
+use crate::game_settings::GameSettings;
+use crate::load_order::FileState;
+
+/// A change to a file that affects a game's load order.
+#[derive(Clone, Debug, Eq, PartialEq, Hash)]
+#[non_exhaustive]
+pub enum LoadOrderEvent {
+    PluginInstalled(PathBuf),
+    PluginRemoved(PathBuf),
+    PluginModified(PathBuf),
+    ActivePluginsFileChanged,
+    LoadOrderFileChanged,
+    /// A file that early-loading plugins are read from (e.g. a CCC file) was
+    /// created, changed or deleted.
+    EarlyLoadersChanged(PathBuf),
+    /// An ini file that test files are read from was created, changed or
+    /// deleted.
+    IniFileChanged(PathBuf),
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum SourceFileType {
+    ActivePlugins,
+    LoadOrder,
+    EarlyLoaders,
+    Ini,
+}
+
+impl SourceFileType {
+    fn to_event(self, path: PathBuf) -> LoadOrderEvent {
+        match self {
+            SourceFileType::ActivePlugins => LoadOrderEvent::ActivePluginsFileChanged,
+            SourceFileType::LoadOrder => LoadOrderEvent::LoadOrderFileChanged,
+            SourceFileType::EarlyLoaders => LoadOrderEvent::EarlyLoadersChanged(path),
+            SourceFileType::Ini => LoadOrderEvent::IniFileChanged(path),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+struct WatchedState {
+    source_files: BTreeMap<PathBuf, (SourceFileType, Option<FileState>)>,
+    plugins: BTreeMap<PathBuf, Option<FileState>>,
+}
+
+impl WatchedState {
+    fn read(game_settings: &GameSettings) -> Result<Self, Error> {
+        let mut source_files = BTreeMap::new();
+
+        let mut add_source_files = |paths: Vec<PathBuf>, file_type| {
+            for path in paths {
+                // A file's first type takes precedence. Files that can't be
+                // read (e.g. because they're being written) are treated as
+                // absent until the next poll.
+                let state = FileState::read(&path, true).ok().flatten();
+                source_files.entry(path).or_insert((file_type, state));
+            }
+        };
+
+        add_source_files(
+            vec![game_settings.active_plugins_file().clone()],
+            SourceFileType::ActivePlugins,
+        );
+        add_source_files(
+            game_settings
+                .load_order_file()
+                .cloned()
+                .into_iter()
+                .collect(),
+            SourceFileType::LoadOrder,
+        );
+        add_source_files(
+            game_settings.early_loading_plugins_source_files()?,
+            SourceFileType::EarlyLoaders,
+        );
+        add_source_files(
+            game_settings.test_files_source_files()?,
+            SourceFileType::Ini,
+        );
+
+        let plugins = game_settings
+            .find_plugins()
+            .into_iter()
+            .map(|p| {
+                // Plugins can be large, and their contents don't affect the
+                // load order, so only their timestamps and sizes are compared.
+                let state = FileState::read(&p, false).ok().flatten();
+                (p, state)
+            })
+            .collect();
+
+        Ok(WatchedState {
+            source_files,
+            plugins,
+        })
+    }
+
+    fn events_since(&self, previous: &WatchedState) -> Vec<LoadOrderEvent> {
+        let mut events = Vec::new();
+
+        for (path, (file_type, state)) in &self.source_files {
+            let previous_state = previous.source_files.get(path).and_then(|(_, s)| *s);
+            if *state != previous_state {
+                events.push(file_type.to_event(path.clone()));
+            }
+        }
+
+        for (path, state) in &self.plugins {
+            match previous.plugins.get(path) {
+                None => events.push(LoadOrderEvent::PluginInstalled(path.clone())),
+                Some(previous_state) if previous_state != state => {
+                    events.push(LoadOrderEvent::PluginModified(path.clone()));
+                }
+                Some(_) => {}
+            }
+        }
+
+        for path in previous.plugins.keys() {
+            if !self.plugins.contains_key(path) {
+                events.push(LoadOrderEvent::PluginRemoved(path.clone()));
+            }
+        }
+
+        events
+    }
+}
+
+/// Watches the files and directories that a game's load order is read from
+/// by polling them for changes.
+///
+/// The watched paths are the plugins directory and any additional plugins
+/// directories, the active plugins and load order files, and the files that
+/// early-loading plugins and test files are read from.
+#[derive(Clone, Debug)]
+pub struct LoadOrderWatcher {
+    game_settings: GameSettings,
+    state: WatchedState,
+}
+
+impl LoadOrderWatcher {
+    /// Create a watcher, recording the current state of the watched paths.
+    pub fn new(game_settings: GameSettings) -> Result<Self, Error> {
+        let state = WatchedState::read(&game_settings)?;
+
+        Ok(LoadOrderWatcher {
+            game_settings,
+            state,
+        })
+    }
+
+    pub fn game_settings(&self) -> &GameSettings {
+        &self.game_settings
+    }
+
+    /// Check the watched paths for changes since the watcher was created or
+    /// last polled.
+    pub fn poll(&mut self) -> Result<Vec<LoadOrderEvent>, Error> {
+        let state = WatchedState::read(&self.game_settings)?;
+
+        let events = state.events_since(&self.state);
+        self.state = state;
+
+        Ok(events)
+    }
+
+    /// Poll for changes on a background thread every `interval`, sending any
+    /// events or errors to the returned receiver. Polling stops when the
+    /// returned handle is stopped or dropped, or when the receiver is
+    /// dropped.
+    pub fn spawn(
+        mut self,
+        interval: Duration,
+    ) -> (WatcherHandle, Receiver<Result<LoadOrderEvent, Error>>) {
+        let (stop_sender, stop_receiver) = channel::<()>();
+        let (event_sender, event_receiver) = channel();
+
+        let thread = thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stop_receiver.recv_timeout(interval) {
+                let results = match self.poll() {
+                    Ok(events) => events.into_iter().map(Ok).collect(),
+                    Err(e) => vec![Err(e)],
+                };
+
+                if results.into_iter().any(|r| event_sender.send(r).is_err()) {
+                    break;
+                }
+            }
+        });
+
+        let handle = WatcherHandle {
+            stop_sender,
+            thread,
+        };
+
+        (handle, event_receiver)
+    }
+}
+
+/// A handle to a [LoadOrderWatcher] that is polling on a background thread.
+#[derive(Debug)]
+pub struct WatcherHandle {
+    stop_sender: Sender<()>,
+    thread: JoinHandle<()>,
+}
+
+impl WatcherHandle {
+    /// Stop polling and wait for the background thread to finish.
+    pub fn stop(self) -> thread::Result<()> {
+        drop(self.stop_sender);
+        self.thread.join()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::{create_dir_all, remove_file, write};
+    use std::path::Path;
+
+    use tempfile::tempdir;
+
+    use crate::enums::GameId;
+    use crate::tests::{copy_to_test_dir, set_file_timestamps};
+
+    fn game_settings(game_path: &Path) -> GameSettings {
+        let settings =
+            GameSettings::with_local_path(GameId::SkyrimSE, game_path, &game_path.join("local"))
+                .unwrap();
+        create_dir_all(settings.plugins_directory()).unwrap();
+        settings
+    }
+
+    #[test]
+    fn poll_should_return_no_events_if_nothing_has_changed() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings(tmp_dir.path());
+        copy_to_test_dir("Blank.esp", "Blank.esp", &settings);
+
+        let mut watcher = LoadOrderWatcher::new(settings).unwrap();
+
+        assert!(watcher.poll().unwrap().is_empty());
+    }
+
+    #[test]
+    fn poll_should_return_plugin_installed_removed_and_modified_events() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings(tmp_dir.path());
+        copy_to_test_dir("Blank.esp", "Blank.esp", &settings);
+        copy_to_test_dir("Blank.esm", "Blank.esm", &settings);
+        let plugins_dir = settings.plugins_directory();
+
+        let mut watcher = LoadOrderWatcher::new(settings.clone()).unwrap();
+
+        copy_to_test_dir("Blank - Different.esp", "Blank - Different.esp", &settings);
+        remove_file(plugins_dir.join("Blank.esm")).unwrap();
+        set_file_timestamps(&plugins_dir.join("Blank.esp"), 1);
+        write(plugins_dir.join("readme.txt"), "").unwrap();
+
+        assert_eq!(
+            vec![
+                LoadOrderEvent::PluginInstalled(plugins_dir.join("Blank - Different.esp")),
+                LoadOrderEvent::PluginModified(plugins_dir.join("Blank.esp")),
+                LoadOrderEvent::PluginRemoved(plugins_dir.join("Blank.esm")),
+            ],
+            watcher.poll().unwrap()
+        );
+        assert!(watcher.poll().unwrap().is_empty());
+    }
+
+    #[test]
+    fn poll_should_return_active_plugins_file_and_early_loader_events() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings(tmp_dir.path());
+
+        let mut watcher = LoadOrderWatcher::new(settings.clone()).unwrap();
+
+        create_dir_all(settings.active_plugins_file().parent().unwrap()).unwrap();
+        write(settings.active_plugins_file(), "*Blank.esp").unwrap();
+        let ccc_path = tmp_dir.path().join("Skyrim.ccc");
+        write(&ccc_path, "Blank.esm").unwrap();
+
+        let events = watcher.poll().unwrap();
+
+        assert_eq!(2, events.len());
+        assert!(events.contains(&LoadOrderEvent::ActivePluginsFileChanged));
+        assert!(events.contains(&LoadOrderEvent::EarlyLoadersChanged(ccc_path)));
+    }
+
+    #[test]
+    fn spawn_should_send_events_until_stopped() {
+        let tmp_dir = tempdir().unwrap();
+        let settings = game_settings(tmp_dir.path());
+
+        let watcher = LoadOrderWatcher::new(settings.clone()).unwrap();
+        let (handle, receiver) = watcher.spawn(Duration::from_millis(10));
+
+        copy_to_test_dir("Blank.esp", "Blank.esp", &settings);
+
+        let event = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(
+            LoadOrderEvent::PluginInstalled(settings.plugins_directory().join("Blank.esp")),
+            event.unwrap()
+        );
+
+        handle.stop().unwrap();
+        assert!(receiver.recv().is_err());
+    }
+}